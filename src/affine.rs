@@ -45,31 +45,37 @@ impl<const CHANNELS: usize> ImageCloner<'_, CHANNELS> {
     }
 }
 
-impl<const CHANNELS: usize, T: AsMut<[u8]> + AsRef<[u8]>> Image<T, CHANNELS> {
+impl<const CHANNELS: usize, T> Image<T, CHANNELS> {
     /// Flip an image vertically.
-    pub fn flip_v(&mut self) {
-        for y in 0..self.height() / 2 {
-            for x in 0..self.width() {
-                let y2 = self.height() - y - 1;
-                #[allow(clippy::multiple_unsafe_ops_per_block)]
+    pub fn flip_v<U: Copy>(&mut self)
+    where
+        T: AsMut<[U]> + AsRef<[U]>,
+    {
+        let w = self.width() as usize;
+        let h = self.height() as usize;
+        let b = self.flatten_mut();
+        for y in 0..h / 2 {
+            for x in 0..w {
+                let y2 = h - y - 1;
                 // SAFETY: within bounds
-                unsafe {
-                    self.swap_pixel((x, y2), (x, y))
-                }
+                unsafe { b.swap_unchecked(y2 * w + x, y * w + x) };
             }
         }
     }
 
     /// Flip an image horizontally.
-    pub fn flip_h(&mut self) {
-        for y in 0..self.height() {
-            for x in 0..self.width() / 2 {
-                let x2 = self.width() - x - 1;
-                #[allow(clippy::multiple_unsafe_ops_per_block)]
+    pub fn flip_h<U: Copy>(&mut self)
+    where
+        T: AsMut<[U]> + AsRef<[U]>,
+    {
+        let w = self.width() as usize;
+        let h = self.height() as usize;
+        let b = self.flatten_mut();
+        for y in 0..h {
+            for x in 0..w / 2 {
+                let x2 = w - x - 1;
                 // SAFETY: bounded
-                unsafe {
-                    self.swap_pixel((x2, y), (x, y))
-                }
+                unsafe { b.swap_unchecked(y * w + x2, y * w + x) };
             }
         }
     }
@@ -121,9 +127,12 @@ impl<const CHANNELS: usize> ImageCloner<'_, CHANNELS> {
     }
 }
 
-impl<const CHANNELS: usize, T: AsMut<[u8]> + AsRef<[u8]>> Image<T, CHANNELS> {
+impl<const CHANNELS: usize, T> Image<T, CHANNELS> {
     /// Rotate an image 180 degrees clockwise.
-    pub fn rot_180(&mut self) {
+    pub fn rot_180<U: Copy>(&mut self)
+    where
+        T: AsMut<[U]> + AsRef<[U]>,
+    {
         self.flatten_mut().reverse();
     }
 
@@ -132,7 +141,10 @@ impl<const CHANNELS: usize, T: AsMut<[u8]> + AsRef<[u8]>> Image<T, CHANNELS> {
     ///
     /// UB if the image is not square
     #[inline]
-    pub unsafe fn rot_90(&mut self) {
+    pub unsafe fn rot_90<U: Copy>(&mut self)
+    where
+        T: AsMut<[U]> + AsRef<[U]>,
+    {
         // This is done by first flipping
         self.flip_v();
         // Then transposing the image, as to not allocate.
@@ -145,7 +157,10 @@ impl<const CHANNELS: usize, T: AsMut<[u8]> + AsRef<[u8]>> Image<T, CHANNELS> {
     ///
     /// UB if the image is not square
     #[inline]
-    pub unsafe fn rot_270(&mut self) {
+    pub unsafe fn rot_270<U: Copy>(&mut self)
+    where
+        T: AsMut<[U]> + AsRef<[U]>,
+    {
         self.flip_h();
         // SAFETY: caller ensures squareness
         unsafe { transpose(self) };
@@ -156,7 +171,9 @@ impl<const CHANNELS: usize, T: AsMut<[u8]> + AsRef<[u8]>> Image<T, CHANNELS> {
 /// # Safety
 ///
 /// UB if supplied image not square
-unsafe fn crev<const CHANNELS: usize, T: AsMut<[u8]> + AsRef<[u8]>>(mut img: Image<T, CHANNELS>) {
+unsafe fn crev<U: Copy, const CHANNELS: usize, T: AsMut<[U]> + AsRef<[U]>>(
+    mut img: Image<T, CHANNELS>,
+) {
     debug_assert_eq!(img.width(), img.height());
     let size = img.width() as usize;
     let b = img.flatten_mut();
@@ -196,7 +213,7 @@ unsafe fn transpose_out<const CHANNELS: usize>(
 /// # Safety
 ///
 /// UB if supplied image rectangular
-unsafe fn transpose<const CHANNELS: usize, T: AsMut<[u8]> + AsRef<[u8]>>(
+unsafe fn transpose<U: Copy, const CHANNELS: usize, T: AsMut<[U]> + AsRef<[U]>>(
     img: &mut Image<T, CHANNELS>,
 ) {
     debug_assert_eq!(img.width(), img.height());
@@ -214,7 +231,7 @@ unsafe fn transpose<const CHANNELS: usize, T: AsMut<[u8]> + AsRef<[u8]>>(
 /// # Safety
 ///
 /// UB if image not square
-unsafe fn transpose_non_power_of_two<const CHANNELS: usize, T: AsMut<[u8]> + AsRef<[u8]>>(
+unsafe fn transpose_non_power_of_two<U: Copy, const CHANNELS: usize, T: AsMut<[U]> + AsRef<[U]>>(
     img: &mut Image<T, CHANNELS>,
 ) {
     debug_assert_eq!(img.width(), img.height());
@@ -233,7 +250,7 @@ const TILE: usize = 4;
 /// # Safety
 ///
 /// be careful
-unsafe fn transpose_tile<const CHANNELS: usize, T: AsMut<[u8]> + AsRef<[u8]>>(
+unsafe fn transpose_tile<U: Copy, const CHANNELS: usize, T: AsMut<[U]> + AsRef<[U]>>(
     img: &mut Image<T, CHANNELS>,
     row: usize,
     col: usize,
@@ -269,7 +286,7 @@ unsafe fn transpose_tile<const CHANNELS: usize, T: AsMut<[u8]> + AsRef<[u8]>>(
 /// # Safety
 ///
 /// be careful
-unsafe fn transpose_diag<const CHANNELS: usize, T: AsMut<[u8]> + AsRef<[u8]>>(
+unsafe fn transpose_diag<U: Copy, const CHANNELS: usize, T: AsMut<[U]> + AsRef<[U]>>(
     img: &mut Image<T, CHANNELS>,
     pos: usize,
     size: usize,
@@ -435,4 +452,13 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn rotate_16() {
+        let mut from = Image::<_, 1>::build(2, 2).buf(vec![0u16, 1000, 2000, 65535]);
+        unsafe { from.rot_90() };
+        assert_eq!(from.buffer(), &[2000, 0, 65535, 1000]);
+        from.flip_h();
+        assert_eq!(from.buffer(), &[0, 2000, 1000, 65535]);
+    }
 }
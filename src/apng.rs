@@ -0,0 +1,332 @@
+//! [APNG](https://wiki.mozilla.org/APNG_Specification) (animated png) reading and writing.
+//!
+//! ```no_run
+//! # use fimg::apng::{Animation, ReadApng};
+//! let a = Animation::<4>::read(&mut std::io::BufReader::new(std::fs::File::open("animation.png")?))?;
+//! for (i, frame) in a.composite().into_iter().enumerate() {
+//!     frame.save(format!("{i}.png"));
+//! }
+//! # Ok::<(), fimg::Error>(())
+//! ```
+use crate::{DynImage, Error, Image};
+
+/// Alpha blend `fg` over `bg`, rounding to the nearest value.
+/// Images with 1 or 3 channels have no alpha, so `fg` simply replaces `bg`.
+fn over<const N: usize>(bg: &mut [u8; N], fg: [u8; N]) {
+    if N % 2 == 1 || fg[N - 1] == 255 {
+        *bg = fg;
+        return;
+    }
+    let (fa, ba) = (f32::from(fg[N - 1]) / 255.0, f32::from(bg[N - 1]) / 255.0);
+    let a = ba.mul_add(-fa, ba + fa);
+    if fa == 0.0 || a == 0.0 {
+        return;
+    }
+    let round = |x: f32| 255.0f32.mul_add(x, 0.5) as u8;
+    for i in 0..N - 1 {
+        let (f, b) = (f32::from(fg[i]) / 255.0, f32::from(bg[i]) / 255.0);
+        bg[i] = round((b * ba).mul_add(1.0 - fa, f * fa) / a);
+    }
+    bg[N - 1] = round(a);
+}
+
+/// What happens to the area of a frame, once it has been shown.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Dispose {
+    /// Leave it as it is.
+    #[default]
+    None,
+    /// Clear it to transparent black.
+    Background,
+    /// Restore it to what it was before this frame was drawn.
+    Previous,
+}
+
+/// How a frame is drawn onto the canvas.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Blend {
+    /// Replace the area of the frame.
+    #[default]
+    Source,
+    /// Alpha blend the frame over the canvas.
+    Over,
+}
+
+/// A frame of an [`Animation`]: an image (which may be smaller than the canvas), placed on the canvas.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame<const N: usize> {
+    /// The image.
+    pub image: Image<Box<[u8]>, N>,
+    /// The position of the image on the canvas.
+    pub offset: (u32, u32),
+    /// How long this frame is shown, in seconds, as `(numerator, denominator)`.
+    pub delay: (u16, u16),
+    /// What happens to this frame, once it has been shown.
+    pub dispose: Dispose,
+    /// How this frame is drawn.
+    pub blend: Blend,
+}
+
+impl<const N: usize> Frame<N> {
+    /// Create a frame that covers the canvas, shown for `delay` (in seconds, as `(numerator, denominator)`).
+    #[must_use]
+    pub const fn new(image: Image<Box<[u8]>, N>, delay: (u16, u16)) -> Self {
+        Self {
+            image,
+            offset: (0, 0),
+            delay,
+            dispose: Dispose::None,
+            blend: Blend::Source,
+        }
+    }
+}
+
+/// A sequence of frames.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Animation<const N: usize> {
+    /// Width of the canvas.
+    pub width: u32,
+    /// Height of the canvas.
+    pub height: u32,
+    /// How many times the animation is played, 0 for forever.
+    pub plays: u32,
+    /// The frames.
+    pub frames: Vec<Frame<N>>,
+}
+
+impl<const N: usize> Animation<N> {
+    /// Create an empty animation, that plays forever.
+    #[must_use]
+    pub const fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            plays: 0,
+            frames: vec![],
+        }
+    }
+
+    /// Draw each frame onto the canvas, producing a full image for every frame.
+    /// Parts of frames outside the canvas are ignored, and frames entirely outside it draw nothing.
+    #[must_use]
+    pub fn composite(&self) -> Vec<Image<Box<[u8]>, N>> {
+        let (w, h) = (self.width as usize, self.height as usize);
+        let mut canvas = vec![0; w * h * N];
+        let mut out = Vec::with_capacity(self.frames.len());
+        for f in &self.frames {
+            let (x, y) = (f.offset.0 as usize, f.offset.1 as usize);
+            if x >= w || y >= h {
+                out.push(
+                    Image::build(self.width, self.height).buf(canvas.clone().into_boxed_slice()),
+                );
+                continue;
+            }
+            let (fw, fh) = (f.image.width() as usize, f.image.height() as usize);
+            let (cw, ch) = (fw.min(w.saturating_sub(x)), fh.min(h.saturating_sub(y)));
+            let previous = (f.dispose == Dispose::Previous).then(|| canvas.clone());
+            for (row, from) in f.image.buffer().chunks_exact(fw * N).take(ch).enumerate() {
+                let to = &mut canvas[((y + row) * w + x) * N..][..cw * N];
+                match f.blend {
+                    Blend::Source => to.copy_from_slice(&from[..cw * N]),
+                    Blend::Over => {
+                        for (a, &b) in to
+                            .as_chunks_mut::<N>()
+                            .0
+                            .iter_mut()
+                            .zip(from.as_chunks::<N>().0)
+                        {
+                            over(a, b);
+                        }
+                    }
+                }
+            }
+            out.push(Image::build(self.width, self.height).buf(canvas.clone().into_boxed_slice()));
+            for row in y..y + ch {
+                let area = ((row * w + x) * N)..((row * w + x + cw) * N);
+                match &previous {
+                    Some(p) => canvas[area.clone()].copy_from_slice(&p[area]),
+                    None if f.dispose == Dispose::Background => canvas[area].fill(0),
+                    None => {}
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Read an apng.
+pub trait ReadApng: Sized {
+    /// Read an apng. A png without animation becomes a single frame.
+    fn read(f: &mut (impl std::io::BufRead + std::io::Seek)) -> Result<Self, Error>;
+}
+
+/// Write an apng.
+pub trait WriteApng {
+    /// Write this animation as an apng.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error>;
+}
+
+impl<const N: usize> ReadApng for Animation<N>
+where
+    Image<Box<[u8]>, N>: From<DynImage<Box<[u8]>>>,
+{
+    /// Read an apng, converting the frames to this many channels.
+    fn read(f: &mut (impl std::io::BufRead + std::io::Seek)) -> Result<Self, Error> {
+        use png::Transformations as T;
+        let mut dec = png::Decoder::new(f);
+        dec.set_transformations(match N {
+            2 | 4 => T::STRIP_16 | T::ALPHA,
+            _ => T::STRIP_16 | T::EXPAND,
+        });
+        let mut reader = dec.read_info()?;
+        let size = reader
+            .output_buffer_size()
+            .ok_or(Error::Decode("image too large".into()))?;
+        let mut buf = vec![0; size].into_boxed_slice();
+        let info = reader.info();
+        let mut me = Self::new(info.width, info.height);
+        let n = match info.animation_control {
+            Some(a) => {
+                me.plays = a.num_plays;
+                if info.frame_control.is_none() {
+                    // the default image is not a part of the animation
+                    reader.next_frame(&mut buf)?;
+                }
+                a.num_frames
+            }
+            None => 1,
+        };
+        for _ in 0..n {
+            let out = reader.next_frame(&mut buf)?;
+            let image = crate::png_frame(buf[..out.buffer_size()].into(), &out)?.into();
+            me.frames.push(match reader.info().frame_control {
+                Some(c) => Frame {
+                    image,
+                    offset: (c.x_offset, c.y_offset),
+                    delay: (c.delay_num, c.delay_den),
+                    dispose: match c.dispose_op {
+                        png::DisposeOp::None => Dispose::None,
+                        png::DisposeOp::Background => Dispose::Background,
+                        png::DisposeOp::Previous => Dispose::Previous,
+                    },
+                    blend: match c.blend_op {
+                        png::BlendOp::Source => Blend::Source,
+                        png::BlendOp::Over => Blend::Over,
+                    },
+                },
+                None => Frame::new(image, (0, 1)),
+            });
+        }
+        Ok(me)
+    }
+}
+
+impl<const N: usize> WriteApng for Animation<N>
+where
+    [(); ((N >= 1) & (N <= 4)) as usize - 1]:,
+{
+    /// Write this animation. The first frame must cover the canvas.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        use png::ColorType::*;
+        let color = match N {
+            1 => Grayscale,
+            2 => GrayscaleAlpha,
+            3 => Rgb,
+            _ => Rgba,
+        };
+        let Some(first) = self.frames.first() else {
+            return Err(Error::Encode("apng: no frames".into()));
+        };
+        if first.offset != (0, 0)
+            || first.image.width() != self.width
+            || first.image.height() != self.height
+        {
+            return Err(Error::Encode(
+                "apng: the first frame must cover the canvas".into(),
+            ));
+        }
+        let mut enc = crate::png_encoder(
+            f,
+            (self.width, self.height),
+            (color, png::BitDepth::Eight),
+            &crate::PngOptions::new(),
+        );
+        enc.set_animated(self.frames.len() as u32, self.plays)?;
+        let mut w = enc.write_header()?;
+        for f in &self.frames {
+            w.reset_frame_position()?;
+            w.set_frame_dimension(f.image.width(), f.image.height())?;
+            w.set_frame_position(f.offset.0, f.offset.1)?;
+            w.set_frame_delay(f.delay.0, f.delay.1)?;
+            w.set_dispose_op(match f.dispose {
+                Dispose::None => png::DisposeOp::None,
+                Dispose::Background => png::DisposeOp::Background,
+                Dispose::Previous => png::DisposeOp::Previous,
+            })?;
+            w.set_blend_op(match f.blend {
+                Blend::Source => png::BlendOp::Source,
+                Blend::Over => png::BlendOp::Over,
+            })?;
+            w.write_image_data(f.image.buffer())?;
+        }
+        w.finish()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image<const N: usize>(w: u32, h: u32, px: [u8; N]) -> Image<Box<[u8]>, N> {
+        Image::build(w, h).buf(px.repeat((w * h) as usize).into())
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut a = Animation::<4>::new(4, 3);
+        a.plays = 2;
+        a.frames
+            .push(Frame::new(image(4, 3, [255, 0, 0, 255]), (1, 10)));
+        a.frames.push(Frame {
+            image: image(2, 2, [0, 0, 255, 128]),
+            offset: (1, 1),
+            delay: (1, 5),
+            dispose: Dispose::Previous,
+            blend: Blend::Over,
+        });
+        a.frames.push(Frame {
+            image: image(1, 1, [0, 255, 0, 255]),
+            offset: (3, 0),
+            delay: (1, 5),
+            dispose: Dispose::Background,
+            blend: Blend::Source,
+        });
+        let mut out = vec![];
+        a.write(&mut out).unwrap();
+        let b = Animation::<4>::read(&mut std::io::Cursor::new(out)).unwrap();
+        assert_eq!(a, b);
+
+        let c = b.composite();
+        assert_eq!(c.len(), 3);
+        assert_eq!(c[0], image(4, 3, [255, 0, 0, 255]));
+        // blended over the red
+        assert_eq!(c[1].get_pixel(1, 1), Some(&[127, 0, 128, 255]));
+        assert_eq!(c[1].get_pixel(0, 0), Some(&[255, 0, 0, 255]));
+        // the second frame was disposed of
+        assert_eq!(c[2].get_pixel(1, 1), Some(&[255, 0, 0, 255]));
+        assert_eq!(c[2].get_pixel(3, 0), Some(&[0, 255, 0, 255]));
+
+        // frames past the canvas draw nothing
+        let mut a = Animation::<4>::new(4, 3);
+        a.frames.push(Frame::new(image(4, 3, [255; 4]), (1, 10)));
+        for offset in [(4, 0), (0, 3), (9, 9)] {
+            a.frames.push(Frame {
+                offset,
+                dispose: Dispose::Background,
+                ..Frame::new(image(2, 2, [0; 4]), (1, 10))
+            });
+        }
+        assert!(a.composite().iter().all(|x| *x == image(4, 3, [255; 4])));
+    }
+}
@@ -0,0 +1,467 @@
+//! [BMP](https://en.wikipedia.org/wiki/BMP_file_format) encoding and decoding.
+//!
+//! Reads 1, 4, 8 (paletted, optionally RLE), 16, 24, and 32 bit bitmaps. Writes 24 bit (RGB) and 32 bit (RGBA) bitmaps.
+use crate::{
+    DynImage, Error, Image,
+    indexed::{IndexedImage, Paletted},
+};
+
+/// Read a bmp image.
+pub trait ReadBmp: Sized {
+    /// Read a bmp into an image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error>;
+}
+
+/// Write a bmp image.
+pub trait WriteBmp {
+    /// Write this bmp image.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error>;
+}
+
+/// The most pixels of a decoded image.
+const MAX_PIXELS: usize = 400_000_000;
+
+macro_rules! fail {
+    ($x:literal) => {
+        return Err(Error::Decode(concat!("bmp: ", $x).into()))
+    };
+}
+
+fn u16le(d: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([d[at], d[at + 1]])
+}
+
+fn u32le(d: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([d[at], d[at + 1], d[at + 2], d[at + 3]])
+}
+
+/// Scale the bits under `mask` to 0-255.
+const fn channel(px: u32, mask: u32) -> u8 {
+    let shift = mask.trailing_zeros();
+    let max = (mask >> shift) as u64;
+    let v = ((px & mask) >> shift) as u64;
+    ((v * 255 + max / 2) / max) as u8
+}
+
+enum Pixels {
+    Indexed(Box<[u8]>, Box<[[u8; 4]]>),
+    Rgb(Box<[u8]>),
+    Rgba(Box<[u8]>),
+}
+
+fn parse(d: &[u8]) -> Result<(u32, u32, Pixels), Error> {
+    if d.len() < 26 || &d[..2] != b"BM" {
+        fail!("bad magic")
+    }
+    let offset = u32le(d, 10) as usize;
+    let header = u32le(d, 14) as usize;
+    if d.len() < 14 + header || d.len() < offset {
+        fail!("unexpected end of data")
+    }
+    let (width, height, bpp, compression, colors, entry) = match header {
+        12 => (
+            u16le(d, 18) as i32,
+            u16le(d, 20) as i32,
+            u16le(d, 24),
+            0,
+            0,
+            3,
+        ),
+        40.. => (
+            u32le(d, 18) as i32,
+            u32le(d, 22) as i32,
+            u16le(d, 28),
+            u32le(d, 30),
+            u32le(d, 46) as usize,
+            4,
+        ),
+        _ => fail!("unsupported header"),
+    };
+    let top_down = height < 0;
+    let (Ok(width), height) = (u32::try_from(width), height.unsigned_abs()) else {
+        fail!("negative width")
+    };
+    let (w, h) = (width as usize, height as usize);
+    if w == 0 || h == 0 {
+        fail!("zero width or height")
+    }
+    // masks after the header, in the 40 byte header
+    let extra = match (header, compression) {
+        (40, 3) => 12,
+        (40, 6) => 16,
+        _ => 0,
+    };
+    let [r, g, b, a] = match compression {
+        3 | 6 if header == 40 => {
+            if d.len() < 54 + extra {
+                fail!("unexpected end of data")
+            }
+            [0, 4, 8, 12].map(|x| if x < extra { u32le(d, 54 + x) } else { 0 })
+        }
+        // only the masks that fit in the header
+        3 | 6 => [0, 4, 8, 12].map(|x| {
+            if 44 + x <= header {
+                u32le(d, 54 + x)
+            } else {
+                0
+            }
+        }),
+        _ if bpp == 16 => [0x7c00, 0x03e0, 0x001f, 0],
+        _ => [0xff0000, 0xff00, 0xff, 0],
+    };
+    if [r, g, b].contains(&0) {
+        fail!("bad bitfields")
+    }
+
+    let data = &d[offset..];
+    let stride = (w * bpp as usize).div_ceil(32) * 4;
+    // output row of a row in the file
+    let row = |y: usize| if top_down { y } else { h - 1 - y };
+    let (Some(size), Some(len)) = (w.checked_mul(h), stride.checked_mul(h)) else {
+        fail!("image too large")
+    };
+    if size > MAX_PIXELS {
+        fail!("image too large")
+    }
+    if !matches!(compression, 1 | 2) && data.len() < len {
+        fail!("unexpected end of data")
+    }
+
+    let pixels = match bpp {
+        1 | 2 | 4 | 8 => {
+            let start = 14 + header + extra;
+            let count = match colors {
+                0 => 1 << bpp,
+                n => n.min(256),
+            };
+            let mut palette = d
+                .get(start..start + count * entry)
+                .unwrap_or_default()
+                .chunks_exact(entry)
+                .map(|x| [x[2], x[1], x[0], 255])
+                .collect::<Vec<_>>();
+            palette.resize(1 << bpp, [0, 0, 0, 255]);
+            let mut out = vec![0; size];
+            match compression {
+                0 => {
+                    let mask = ((1u16 << bpp) - 1) as u8;
+                    let bpp = bpp as usize;
+                    for (y, data) in data.chunks_exact(stride).take(h).enumerate() {
+                        let out = &mut out[row(y) * w..][..w];
+                        for (x, o) in out.iter_mut().enumerate() {
+                            let bit = x * bpp;
+                            *o = data[bit / 8] >> (8 - bpp - bit % 8) & mask;
+                        }
+                    }
+                }
+                // rle8 is only for 8 bit images, and rle4 for 4 bit ones
+                1 if bpp == 8 => rle(data, &mut out, (w, h), false, row)?,
+                2 if bpp == 4 => rle(data, &mut out, (w, h), true, row)?,
+                _ => fail!("unsupported compression"),
+            }
+            Pixels::Indexed(out.into(), palette.into())
+        }
+        16 | 24 | 32 => {
+            if !matches!(compression, 0 | 3 | 6) {
+                fail!("unsupported compression")
+            }
+            let n = if a == 0 { 3 } else { 4 };
+            let mut out = vec![0; size * n];
+            let bytes = bpp as usize / 8;
+            for (y, data) in data.chunks_exact(stride).take(h).enumerate() {
+                let out = &mut out[row(y) * w * n..][..w * n];
+                for (o, px) in out.chunks_exact_mut(n).zip(data.chunks_exact(bytes)) {
+                    let px = match *px {
+                        [a, b] => u16::from_le_bytes([a, b]) as u32,
+                        [b, g, r] => u32::from_le_bytes([b, g, r, 0]),
+                        [a, b, c, d] => u32::from_le_bytes([a, b, c, d]),
+                        _ => unreachable!(),
+                    };
+                    o[0] = channel(px, r);
+                    o[1] = channel(px, g);
+                    o[2] = channel(px, b);
+                    if n == 4 {
+                        o[3] = channel(px, a);
+                    }
+                }
+            }
+            match n {
+                3 => Pixels::Rgb(out.into()),
+                _ => Pixels::Rgba(out.into()),
+            }
+        }
+        _ => fail!("unsupported bit depth"),
+    };
+    Ok((width, height, pixels))
+}
+
+/// Decode RLE8 (or RLE4) data.
+fn rle(
+    mut data: &[u8],
+    out: &mut [u8],
+    (w, h): (usize, usize),
+    four: bool,
+    row: impl Fn(usize) -> usize,
+) -> Result<(), Error> {
+    let (mut x, mut y) = (0, 0);
+    let mut put = |x: &mut usize, y: usize, v: u8| {
+        if *x < w && y < h {
+            out[row(y) * w + *x] = v;
+        }
+        *x += 1;
+    };
+    loop {
+        let Some((&[n, v], rest)) = data.split_first_chunk::<2>() else {
+            // missing end of bitmap
+            return Ok(());
+        };
+        data = rest;
+        match (n, v) {
+            (0, 0) => (x, y) = (0, y + 1),
+            (0, 1) => return Ok(()),
+            (0, 2) => {
+                let Some((&[dx, dy], rest)) = data.split_first_chunk::<2>() else {
+                    fail!("unexpected end of data")
+                };
+                data = rest;
+                (x, y) = (x + dx as usize, y + dy as usize);
+            }
+            (0, n) => {
+                let n = n as usize;
+                let bytes = if four { n.div_ceil(2) } else { n };
+                let Some(run) = data.get(..bytes) else {
+                    fail!("unexpected end of data")
+                };
+                for i in 0..n {
+                    match four {
+                        true => put(&mut x, y, run[i / 2] >> (4 - i % 2 * 4) & 0xf),
+                        false => put(&mut x, y, run[i]),
+                    }
+                }
+                // word aligned
+                data = data.get((bytes + 1) & !1..).unwrap_or_default();
+            }
+            (n, v) => {
+                for i in 0..n as usize {
+                    match four {
+                        true => put(&mut x, y, v >> (4 - i % 2 * 4) & 0xf),
+                        false => put(&mut x, y, v),
+                    }
+                }
+            }
+        }
+        if y >= h {
+            return Ok(());
+        }
+    }
+}
+
+/// Decode a bmp image. Produces a [`DynImage::Rgb`] or a [`DynImage::Rgba`]. Paletted images are expanded.
+pub fn decode(data: &[u8]) -> Result<DynImage<Box<[u8]>>, Error> {
+    let (width, height, pixels) = parse(data)?;
+    Ok(match pixels {
+        Pixels::Indexed(i, p) => Image::<_, 3>::build(width, height)
+            .try_buf(
+                i.iter()
+                    .flat_map(|&i| &p[i as usize][..3])
+                    .copied()
+                    .collect(),
+            )?
+            .into(),
+        Pixels::Rgb(x) => Image::<_, 3>::build(width, height).try_buf(x)?.into(),
+        Pixels::Rgba(x) => Image::<_, 4>::build(width, height).try_buf(x)?.into(),
+    })
+}
+
+/// Decode a paletted (1, 2, 4, or 8 bit) bmp image.
+pub fn decode_indexed(data: &[u8]) -> Result<Paletted, Error> {
+    let (width, height, pixels) = parse(data)?;
+    let Pixels::Indexed(i, p) = pixels else {
+        return Err(Error::UnsupportedColor("bmp is not paletted"));
+    };
+    IndexedImage::from_raw_parts(Image::build(width, height).try_buf(i)?, p)
+        .map_err(|x| Error::Decode(x.into()))
+}
+
+/// Encode an image to a 24 bit (RGB) or 32 bit (RGBA) bmp.
+pub fn encode<const N: usize>(image: Image<&[u8], N>) -> Vec<u8>
+where
+    // bmp images are RGB or RGBA
+    [(); ((N == 3) | (N == 4)) as usize - 1]:,
+{
+    let (w, h) = (image.width() as usize, image.height() as usize);
+    let stride = (w * N).next_multiple_of(4);
+    let header = if N == 4 { 108 } else { 40 };
+    let offset = 14 + header;
+    let mut out = Vec::with_capacity(offset + stride * h);
+    out.extend(b"BM");
+    out.extend(((offset + stride * h) as u32).to_le_bytes());
+    out.extend([0; 4]);
+    out.extend((offset as u32).to_le_bytes());
+    out.extend((header as u32).to_le_bytes());
+    out.extend(image.width().to_le_bytes());
+    out.extend(image.height().to_le_bytes());
+    out.extend(1u16.to_le_bytes());
+    out.extend((N as u16 * 8).to_le_bytes());
+    out.extend((if N == 4 { 3u32 } else { 0 }).to_le_bytes());
+    out.extend(((stride * h) as u32).to_le_bytes());
+    // 72 dpi
+    out.extend(2835u32.to_le_bytes());
+    out.extend(2835u32.to_le_bytes());
+    out.extend([0; 8]);
+    if N == 4 {
+        for mask in [0x00ff0000u32, 0x0000ff00, 0x000000ff, 0xff000000] {
+            out.extend(mask.to_le_bytes());
+        }
+        out.extend(b"BGRs");
+        // endpoints and gamma: unused for sRGB
+        out.extend([0; 48]);
+    }
+    for row in image.buffer().chunks_exact(w * N).rev() {
+        for px in row.chunks_exact(N) {
+            out.extend([px[2], px[1], px[0]]);
+            if N == 4 {
+                out.push(px[3]);
+            }
+        }
+        out.resize(out.len() + stride - w * N, 0);
+    }
+    out
+}
+
+impl ReadBmp for DynImage<Box<[u8]>> {
+    /// Read a bmp image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        let mut data = vec![];
+        f.read_to_end(&mut data)?;
+        decode(&data)
+    }
+}
+
+impl ReadBmp for Paletted {
+    /// Read a paletted bmp image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        let mut data = vec![];
+        f.read_to_end(&mut data)?;
+        decode_indexed(&data)
+    }
+}
+
+impl<const N: usize> ReadBmp for Image<Box<[u8]>, N>
+where
+    Self: From<DynImage<Box<[u8]>>>,
+{
+    /// Read a bmp image, converting it to this many channels.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        DynImage::read(f).map(Into::into)
+    }
+}
+
+macro_rules! writer {
+    ($n:literal) => {
+        impl<T: AsRef<[u8]>> WriteBmp for Image<T, $n> {
+            /// Write this image as a bmp.
+            fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+                f.write_all(&encode(self.as_ref()))?;
+                Ok(())
+            }
+        }
+    };
+}
+writer!(3);
+writer!(4);
+
+impl<T: AsRef<[u8]> + crate::Buffer> WriteBmp for DynImage<T> {
+    /// Write this image as a bmp. Y images become RGB, YA images become RGBA, and 16 bit images are reduced to 8 bit.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        match self {
+            Self::Rgb(x) => x.write(f),
+            Self::Rgba(x) => x.write(f),
+            Self::Ya(_) | Self::Ya16(_) | Self::Rgba16(_) => self.rgba().write(f),
+            Self::Y(_) | Self::Y16(_) | Self::Rgb16(_) => self.rgb().write(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "save")]
+    #[test]
+    fn roundtrip() {
+        let cat = Image::<_, 4>::open("tdata/small_cat.png");
+        let rgb = Image::<Box<[u8]>, 3>::from(cat.as_ref());
+        assert_eq!(decode(&encode(cat.as_ref())).unwrap().bytes(), cat.bytes());
+        assert_eq!(decode(&encode(rgb.as_ref())).unwrap().bytes(), rgb.bytes());
+    }
+
+    #[test]
+    fn paletted() {
+        fn bmp(bpp: u16, compression: u32, height: i32, data: &[u8]) -> Vec<u8> {
+            let mut out = b"BM".to_vec();
+            out.extend(0u32.to_le_bytes());
+            out.extend(0u32.to_le_bytes());
+            out.extend((14u32 + 40 + 8).to_le_bytes());
+            out.extend(40u32.to_le_bytes());
+            out.extend(3u32.to_le_bytes());
+            out.extend(height.to_le_bytes());
+            out.extend(1u16.to_le_bytes());
+            out.extend(bpp.to_le_bytes());
+            out.extend(compression.to_le_bytes());
+            out.extend([0; 12]);
+            out.extend(2u32.to_le_bytes());
+            out.extend([0; 4]);
+            out.extend([0, 0, 255, 0, 255, 0, 0, 0]);
+            out.extend(data);
+            out
+        }
+        // 3x2, bottom up: [0 1 0] [1 0 1]
+        let expect = [0, 1, 0, 1, 0, 1];
+        for data in [
+            bmp(8, 0, 2, &[1, 0, 1, 0, 0, 1, 0, 0]),
+            bmp(8, 0, -2, &[0, 1, 0, 0, 1, 0, 1, 0]),
+            bmp(4, 0, 2, &[0x10, 0x10, 0, 0, 0x01, 0, 0, 0]),
+            bmp(1, 0, 2, &[0b10100000, 0, 0, 0, 0b01000000, 0, 0, 0]),
+            bmp(8, 1, 2, &[1, 1, 1, 0, 1, 1, 0, 0, 0, 3, 0, 1, 0, 0, 0, 1]),
+            bmp(4, 2, 2, &[3, 0x10, 0, 0, 3, 0x01, 0, 1]),
+        ] {
+            let i = decode_indexed(&data).unwrap();
+            let (buf, pal) = i.into_raw_parts();
+            assert_eq!(&**buf.buffer(), expect);
+            assert_eq!(&pal[..2], [[255, 0, 0, 255], [0, 0, 255, 255]]);
+            assert_eq!(decode(&data).unwrap().bytes()[..3], [255, 0, 0]);
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        fn bmp(header: u32, width: i32, height: i32, bpp: u16, compression: u32) -> Vec<u8> {
+            let mut out = b"BM".to_vec();
+            out.extend([0; 8]);
+            out.extend((14 + header).to_le_bytes());
+            out.extend(header.to_le_bytes());
+            out.extend(width.to_le_bytes());
+            out.extend(height.to_le_bytes());
+            out.extend(1u16.to_le_bytes());
+            out.extend(bpp.to_le_bytes());
+            out.extend(compression.to_le_bytes());
+            out.resize(14 + header as usize, 0);
+            out
+        }
+        assert!(decode(&bmp(40, 0, 2, 8, 0)).is_err());
+        assert!(decode(&bmp(40, 2, 0, 8, 0)).is_err());
+        // masks that dont fit in the header
+        for header in 41..44 {
+            assert!(decode(&bmp(header, 2, 2, 8, 3)).is_err());
+        }
+        // a huge rle image, from a tiny file
+        assert!(decode(&bmp(40, 1 << 20, 1 << 20, 8, 1)).is_err());
+        // rle with indices past the palette of a small bit depth
+        for (bpp, compression) in [(1, 1), (4, 1), (8, 2), (1, 2)] {
+            let mut data = bmp(40, 1, 1, bpp, compression);
+            data.extend([1, 200, 0, 1]);
+            assert!(matches!(decode(&data), Err(Error::Decode(_))));
+        }
+    }
+}
@@ -3,7 +3,7 @@
 //! does not let you do funny things
 use std::marker::PhantomData;
 
-use crate::Image;
+use crate::{Error, Image};
 
 impl<B, const C: usize> Image<B, C> {
     /// creates a builder
@@ -51,6 +51,21 @@ impl<B, const C: usize> Builder<B, C> {
         // SAFETY: checked!
         unsafe { self.buf_unchecked(buffer) }
     }
+
+    /// apply a buffer, and build, returning a [`Error::DimensionMismatch`] if the buffer size is wrong (or the image is zero sized).
+    pub fn try_buf<I>(self, buffer: B) -> Result<Image<B, C>, Error>
+    where
+        B: AsRef<[I]>,
+    {
+        let expected = C * self.width as usize * self.height as usize;
+        let got = buffer.as_ref().len();
+        if expected == 0 || got != expected {
+            return Err(Error::DimensionMismatch { expected, got });
+        }
+        // SAFETY: checked!
+        Ok(unsafe { self.buf_unchecked(buffer) })
+    }
+
     /// apply a buffer, and build (length unchecked)
     #[track_caller]
     #[must_use = "what is it going to do?"]
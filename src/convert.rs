@@ -8,25 +8,25 @@ use std::{
     simd::{SimdElement, StdFloat, prelude::*},
 };
 
-fn map<const A: usize, const B: usize>(image: Image<&[u8], A>) -> Image<Box<[u8]>, B>
+fn map<C: Copy, const A: usize, const B: usize>(image: Image<&[C], A>) -> Image<Box<[C]>, B>
 where
-    [u8; B]: PFrom<A>,
+    [C; B]: PFrom<A, C>,
 {
     // SAFETY: size unchanged, just change pixels
     unsafe {
         image.mapped(|buf| {
             buf.array_chunks::<A>()
                 .copied()
-                .flat_map(<[u8; B] as PFrom<A>>::pfrom)
+                .flat_map(<[C; B] as PFrom<A, C>>::pfrom)
                 .collect()
         })
     }
 }
 
 macro_rules! convert {
-    ($a:literal => $b:literal) => {
-        impl From<Image<&[u8], $b>> for Image<Box<[u8]>, $a> {
-            fn from(value: Image<&[u8], $b>) -> Self {
+    ($t:ty: $a:literal => $b:literal) => {
+        impl From<Image<&[$t], $b>> for Image<Box<[$t]>, $a> {
+            fn from(value: Image<&[$t], $b>) -> Self {
                 map(value)
             }
         }
@@ -34,41 +34,46 @@ macro_rules! convert {
 }
 
 macro_rules! cv {
-    [$($n:literal),+] => {
-        $(convert!($n => 1);
-        convert!($n => 2);
-        convert!($n => 3);
-        convert!($n => 4);)+
+    [$t:ty: $($n:literal),+] => {
+        $(convert!($t: $n => 1);
+        convert!($t: $n => 2);
+        convert!($t: $n => 3);
+        convert!($t: $n => 4);)+
     };
 }
 
-cv![1, 2, 3, 4];
+cv![u8: 1, 2, 3, 4];
+cv![u16: 1, 2, 3, 4];
 
 macro_rules! boxconv {
-    ($a:literal => $b: literal) => {
-        impl From<Image<Box<[u8]>, $b>> for Image<Box<[u8]>, $a> {
-            fn from(value: Image<Box<[u8]>, $b>) -> Self {
+    ($t:ty: $a:literal => $b: literal) => {
+        impl From<Image<Box<[$t]>, $b>> for Image<Box<[$t]>, $a> {
+            fn from(value: Image<Box<[$t]>, $b>) -> Self {
                 value.as_ref().into()
             }
         }
     };
-}
+    ($t:ty) => {
+        boxconv!($t: 1 => 2);
+        boxconv!($t: 1 => 3);
+        boxconv!($t: 1 => 4);
 
-boxconv!(1 => 2);
-boxconv!(1 => 3);
-boxconv!(1 => 4);
+        boxconv!($t: 2 => 1);
+        boxconv!($t: 2 => 3);
+        boxconv!($t: 2 => 4);
 
-boxconv!(2 => 1);
-boxconv!(2 => 3);
-boxconv!(2 => 4);
+        boxconv!($t: 3 => 1);
+        boxconv!($t: 3 => 2);
+        boxconv!($t: 3 => 4);
 
-boxconv!(3 => 1);
-boxconv!(3 => 2);
-boxconv!(3 => 4);
+        boxconv!($t: 4 => 1);
+        boxconv!($t: 4 => 2);
+        boxconv!($t: 4 => 3);
+    };
+}
 
-boxconv!(4 => 1);
-boxconv!(4 => 2);
-boxconv!(4 => 3);
+boxconv!(u8);
+boxconv!(u16);
 
 impl<const N: usize> From<Image<&[u8], N>> for Image<Box<[u32]>, 1>
 where
@@ -100,7 +105,7 @@ where
     }
 }
 
-fn u8_to_f32(x: u8) -> f32 {
+pub(crate) fn u8_to_f32(x: u8) -> f32 {
     let magic = 2.0f32.powf(23.);
     // x = 2^23 + x
     let x = f32::from_bits((x as u32) ^ magic.to_bits());
@@ -116,17 +121,17 @@ fn u8s_to_f32s(x: u8x8) -> f32x8 {
 }
 
 // notice: this f32 better be in range 0.0-1.0
-fn f32_to_u8(x: f32) -> u8 {
+pub(crate) fn f32_to_u8(x: f32) -> u8 {
     let magic = (1 << 23) as f32;
     (x.mul_add(255.0, magic).to_bits() ^ magic.to_bits()) as u8
 }
 
-fn f32s_to_u8s(x: f32x8) -> u8x8 {
+pub(crate) fn f32s_to_u8s(x: f32x8) -> u8x8 {
     let magic = (1 << 23) as f32;
     (x.mul_add(Simd::splat(255.0), Simd::splat(magic)).cast() ^ Simd::splat(magic.to_bits())).cast()
 }
 
-fn mapping<T, U>(
+pub(crate) fn mapping<T, U>(
     x: &[T],
     mut f: impl FnMut(Simd<T, 8>) -> Simd<U, 8>,
     mut single: impl FnMut(T) -> U,
@@ -169,6 +174,64 @@ impl<const N: usize> From<Image<&[f32], N>> for Image<Box<[u8]>, N> {
     }
 }
 
+fn u8_to_u16(x: u8) -> u16 {
+    x as u16 * 257
+}
+
+// rounds to nearest
+pub(crate) fn u16_to_u8(x: u16) -> u8 {
+    ((x as u32 * 255 + 32895) >> 16) as u8
+}
+
+fn u16s_to_f32s(x: u16x8) -> f32x8 {
+    x.cast::<f32>() * Simd::splat(1.0 / 65535.0)
+}
+
+fn u16_to_f32(x: u16) -> f32 {
+    fmul_algebraic(x as f32, 1.0 / 65535.0)
+}
+
+// notice: this f32 better be in range 0.0-1.0
+fn f32s_to_u16s(x: f32x8) -> u16x8 {
+    x.mul_add(Simd::splat(65535.0), Simd::splat(0.5)).cast()
+}
+
+fn f32_to_u16(x: f32) -> u16 {
+    x.mul_add(65535.0, 0.5) as u16
+}
+
+impl<const N: usize> From<Image<&[u8], N>> for Image<Box<[u16]>, N> {
+    /// Expand to 0-65535 from 0-255.
+    fn from(value: Image<&[u8], N>) -> Self {
+        // SAFETY: length unchanged
+        unsafe { value.mapped(|x| x.iter().copied().map(u8_to_u16).collect()) }
+    }
+}
+
+impl<const N: usize> From<Image<&[u16], N>> for Image<Box<[u8]>, N> {
+    /// Reduce to 0-255 from 0-65535.
+    fn from(value: Image<&[u16], N>) -> Self {
+        // SAFETY: length unchanged
+        unsafe { value.mapped(|x| x.iter().copied().map(u16_to_u8).collect()) }
+    }
+}
+
+impl<const N: usize> From<Image<&[u16], N>> for Image<Box<[f32]>, N> {
+    /// Reduce to 0.0-1.0 from 0-65535.
+    fn from(value: Image<&[u16], N>) -> Self {
+        // SAFETY: length unchanged
+        unsafe { value.mapped(|x| mapping(x, u16s_to_f32s, u16_to_f32).into_boxed_slice()) }
+    }
+}
+
+impl<const N: usize> From<Image<&[f32], N>> for Image<Box<[u16]>, N> {
+    /// Expand to 0-65535 from 0.0-1.0
+    fn from(value: Image<&[f32], N>) -> Self {
+        // SAFETY: length unchanged
+        unsafe { value.mapped(|x| mapping(x, f32s_to_u16s, f32_to_u16).into_boxed_slice()) }
+    }
+}
+
 #[test]
 fn roundtrip() {
     let original = Image::<_, 3>::open("tdata/small_cat.png");
@@ -180,16 +243,39 @@ fn roundtrip() {
     );
 }
 
-impl<const N: usize, T: AsRef<[u8]>> Image<T, N> {
+impl<const N: usize, T> Image<T, N> {
     /// just an `into` wrapper
-    pub fn to_f32(&self) -> Image<Box<[f32]>, N> {
+    pub fn to_f32<U>(&self) -> Image<Box<[f32]>, N>
+    where
+        T: AsRef<[U]>,
+        for<'a> Image<Box<[f32]>, N>: From<Image<&'a [U], N>>,
+    {
+        self.as_ref().into()
+    }
+
+    /// just an `into` wrapper
+    pub fn to_u8<U>(&self) -> Image<Box<[u8]>, N>
+    where
+        T: AsRef<[U]>,
+        for<'a> Image<Box<[u8]>, N>: From<Image<&'a [U], N>>,
+    {
         self.as_ref().into()
     }
-}
 
-impl<const N: usize, T: AsRef<[f32]>> Image<T, N> {
     /// just an `into` wrapper
-    pub fn to_u8(&self) -> Image<Box<[u8]>, N> {
+    pub fn to_u16<U>(&self) -> Image<Box<[u16]>, N>
+    where
+        T: AsRef<[U]>,
+        for<'a> Image<Box<[u16]>, N>: From<Image<&'a [U], N>>,
+    {
         self.as_ref().into()
     }
 }
+
+#[test]
+fn roundtrip16() {
+    let original = Image::<_, 3>::open("tdata/small_cat.png");
+    let wide = original.to_u16();
+    assert_eq!(wide.to_u8().bytes(), original.bytes());
+    assert_eq!(wide.to_f32().to_u16().buffer(), wide.buffer());
+}
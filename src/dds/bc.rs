@@ -0,0 +1,199 @@
+//! BC1 to BC5 block encoding and decoding.
+
+const fn rgb565(c: u16) -> [u8; 3] {
+    let (r, g, b) = ((c >> 11) as u8, (c >> 5 & 63) as u8, (c & 31) as u8);
+    [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2]
+}
+
+fn to565([r, g, b]: [f32; 3]) -> u16 {
+    let q = |x: f32, max: f32| (x.clamp(0.0, 255.0) * max / 255.0).round() as u16;
+    q(r, 31.0) << 11 | q(g, 63.0) << 5 | q(b, 31.0)
+}
+
+/// The colors of a BC1 block. With `c0 <= c1`, and `punch`, the last color is transparent black.
+fn colors(c0: u16, c1: u16, punch: bool) -> [[u8; 4]; 4] {
+    let (a, b) = (rgb565(c0), rgb565(c1));
+    let mix = |f: fn(u32, u32) -> u32| {
+        let [x, y, z] = std::array::from_fn(|i| f(a[i] as u32, b[i] as u32) as u8);
+        [x, y, z, 255]
+    };
+    let [a, b] = [a, b].map(|[x, y, z]| [x, y, z, 255]);
+    if c0 > c1 || !punch {
+        [
+            a,
+            b,
+            mix(|a, b| (2 * a + b) / 3),
+            mix(|a, b| (a + 2 * b) / 3),
+        ]
+    } else {
+        [a, b, mix(|a, b| (a + b) / 2), [0; 4]]
+    }
+}
+
+/// Decode a BC1 color block. Without `punch` (in BC2 and BC3), the block always has four colors.
+pub fn decode_bc1(block: &[u8; 8], punch: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let colors = colors(c0, c1, punch);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    std::array::from_fn(|i| colors[(indices >> (2 * i) & 3) as usize])
+}
+
+/// The values of a BC4 block.
+fn values(a0: u8, a1: u8) -> [u8; 8] {
+    let (a, b) = (a0 as u32, a1 as u32);
+    if a0 > a1 {
+        std::array::from_fn(|i| match i {
+            0 => a0,
+            1 => a1,
+            _ => (((8 - i as u32) * a + (i as u32 - 1) * b) / 7) as u8,
+        })
+    } else {
+        std::array::from_fn(|i| match i {
+            0 => a0,
+            1 => a1,
+            6 => 0,
+            7 => 255,
+            _ => (((6 - i as u32) * a + (i as u32 - 1) * b) / 5) as u8,
+        })
+    }
+}
+
+/// Decode a BC4 block (also the alpha of BC3, and either channel of BC5).
+pub fn decode_bc4(block: &[u8; 8]) -> [u8; 16] {
+    let values = values(block[0], block[1]);
+    let mut indices = [0; 8];
+    indices[..6].copy_from_slice(&block[2..]);
+    let indices = u64::from_le_bytes(indices);
+    std::array::from_fn(|i| values[(indices >> (3 * i) & 7) as usize])
+}
+
+/// Decode the explicit 4 bit alpha of a BC2 block.
+pub fn decode_bc2_alpha(block: &[u8; 8]) -> [u8; 16] {
+    std::array::from_fn(|i| (block[i / 2] >> (4 * (i % 2)) & 15) * 17)
+}
+
+fn distance(a: [u8; 4], b: [u8; 4]) -> u32 {
+    (0..3)
+        .map(|i| (a[i] as i32 - b[i] as i32).pow(2) as u32)
+        .sum()
+}
+
+/// Encode 16 pixels as a BC1 color block. With `punch`, pixels with alpha below 128 become transparent.
+pub fn encode_bc1(px: &[[u8; 4]; 16], punch: bool) -> [u8; 8] {
+    let punch = punch && px.iter().any(|p| p[3] < 128);
+    let opaque = px
+        .iter()
+        .filter(|p| !punch || p[3] >= 128)
+        .map(|p| [p[0], p[1], p[2]].map(f32::from))
+        .collect::<Vec<_>>();
+    let (c0, c1) = if opaque.is_empty() {
+        (0, 0)
+    } else {
+        // the ends of the colors along their principal axis
+        let n = opaque.len() as f32;
+        let mean: [f32; 3] = std::array::from_fn(|i| opaque.iter().map(|p| p[i]).sum::<f32>() / n);
+        let mut cov = [[0.0; 3]; 3];
+        for p in &opaque {
+            let d: [f32; 3] = std::array::from_fn(|i| p[i] - mean[i]);
+            for i in 0..3 {
+                for j in 0..3 {
+                    cov[i][j] = d[i].mul_add(d[j], cov[i][j]);
+                }
+            }
+        }
+        // power iteration, from the channel that varies most
+        let widest = (0..3)
+            .max_by(|&i, &j| cov[i][i].total_cmp(&cov[j][j]))
+            .unwrap();
+        let mut axis = std::array::from_fn(|i| (i == widest) as u8 as f32);
+        for _ in 0..8 {
+            let next: [f32; 3] = std::array::from_fn(|i| (0..3).map(|j| cov[i][j] * axis[j]).sum());
+            let len = next.iter().map(|x| x * x).sum::<f32>().sqrt();
+            if len < 1e-6 {
+                break;
+            }
+            axis = next.map(|x| x / len);
+        }
+        let t = |p: &[f32; 3]| (0..3).map(|i| (p[i] - mean[i]) * axis[i]).sum::<f32>();
+        let (lo, hi) = opaque
+            .iter()
+            .map(t)
+            .fold((f32::MAX, f32::MIN), |(lo, hi), t| (lo.min(t), hi.max(t)));
+        let at = |t: f32| std::array::from_fn(|i| axis[i].mul_add(t, mean[i]));
+        (to565(at(hi)), to565(at(lo)))
+    };
+    // four colors need c0 > c1, three (with transparency) c0 <= c1
+    let (c0, c1) = if (c0 < c1) != punch && c0 != c1 {
+        (c1, c0)
+    } else {
+        (c0, c1)
+    };
+    let colors = colors(c0, c1, true);
+    let usable = if punch || c0 == c1 { 3 } else { 4 };
+    let mut indices = 0u32;
+    for (i, &p) in px.iter().enumerate() {
+        let index = if punch && p[3] < 128 {
+            3
+        } else {
+            (0..usable).min_by_key(|&j| distance(colors[j], p)).unwrap()
+        };
+        indices |= (index as u32) << (2 * i);
+    }
+    let mut out = [0; 8];
+    out[..2].copy_from_slice(&c0.to_le_bytes());
+    out[2..4].copy_from_slice(&c1.to_le_bytes());
+    out[4..].copy_from_slice(&indices.to_le_bytes());
+    out
+}
+
+/// Encode 16 values as a BC4 block.
+pub fn encode_bc4(px: &[u8; 16]) -> [u8; 8] {
+    let (lo, hi) = (*px.iter().min().unwrap(), *px.iter().max().unwrap());
+    let values = values(hi, lo);
+    let mut indices = 0u64;
+    for (i, &p) in px.iter().enumerate() {
+        let index = (0..8).min_by_key(|&j| values[j].abs_diff(p)).unwrap();
+        indices |= (index as u64) << (3 * i);
+    }
+    let mut out = [hi, lo, 0, 0, 0, 0, 0, 0];
+    out[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+    out
+}
+
+/// Encode the alpha of 16 pixels as the explicit 4 bit alpha of a BC2 block.
+pub fn encode_bc2_alpha(px: &[[u8; 4]; 16]) -> [u8; 8] {
+    let a = |i: usize| (px[i][3] as u32 * 15 + 127) / 255;
+    std::array::from_fn(|i| (a(2 * i) | a(2 * i + 1) << 4) as u8)
+}
+
+#[test]
+fn blocks() {
+    // solid colors, exact in 565
+    let px = [[255, 0, 255, 255]; 16];
+    assert_eq!(decode_bc1(&encode_bc1(&px, false), true), px);
+    // four colors on a line
+    let px: [[u8; 4]; 16] = std::array::from_fn(|i| {
+        let t = (i % 4) as u8 * 85;
+        [t, 128, 255 - t, 255]
+    });
+    let out = decode_bc1(&encode_bc1(&px, false), true);
+    for (a, b) in out.iter().zip(&px) {
+        assert!(distance(*a, *b) < 3 * 12 * 12, "{a:?} {b:?}");
+    }
+    // transparency
+    let mut px = px;
+    px[5][3] = 0;
+    let out = decode_bc1(&encode_bc1(&px, true), true);
+    assert_eq!(out[5], [0; 4]);
+    assert!(out.iter().enumerate().all(|(i, p)| i == 5 || p[3] == 255));
+
+    let v: [u8; 16] = std::array::from_fn(|i| (i * i) as u8);
+    let out = decode_bc4(&encode_bc4(&v));
+    assert!(out.iter().zip(&v).all(|(a, b)| a.abs_diff(*b) <= 16));
+    assert_eq!(out[0], 0);
+    assert_eq!(out[15], 225);
+    assert_eq!(decode_bc4(&encode_bc4(&[7; 16])), [7; 16]);
+    let px: [[u8; 4]; 16] = std::array::from_fn(|i| [0, 0, 0, i as u8 * 17]);
+    assert_eq!(decode_bc2_alpha(&encode_bc2_alpha(&px)), px.map(|p| p[3]));
+}
@@ -0,0 +1,221 @@
+//! BC7 block decoding.
+
+/// The subset of each pixel (a bit each) of the partitions of two subsets.
+const PARTITION2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800,
+    0xffe8, 0xff00, 0xfff0, 0xf000, 0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c, 0xaaaa, 0xf0f0, 0x5a5a, 0x33cc,
+    0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718,
+    0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+/// The subset of each pixel (two bits each) of the partitions of three subsets.
+const PARTITION3: [u32; 64] = [
+    0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050,
+    0xaa550000, 0xaa555500, 0xaaaa5500, 0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250,
+    0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040, 0xa4a45000, 0x1a1a0500,
+    0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200,
+    0xa9a58000, 0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50,
+    0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0, 0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600,
+    0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414, 0x96960000,
+    0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254,
+];
+
+/// The anchor pixel of the second subset, of the partitions of two subsets.
+const ANCHOR2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// The anchor pixel of the second subset, of the partitions of three subsets.
+const ANCHOR3_1: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5,
+    15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8, 5, 10, 5,
+    10, 8, 13, 15, 12, 3, 3,
+];
+
+/// The anchor pixel of the third subset, of the partitions of three subsets.
+const ANCHOR3_2: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6,
+    10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+/// Interpolation weights, out of 64, by index bits.
+const WEIGHTS2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+struct Mode {
+    subsets: usize,
+    partition: u32,
+    rotation: u32,
+    selection: u32,
+    color: u32,
+    alpha: u32,
+    /// a p-bit per endpoint
+    unique_p: bool,
+    /// a p-bit per subset
+    shared_p: bool,
+    index: u32,
+    /// the bits of the second set of indices, or 0
+    index2: u32,
+}
+
+const fn mode(
+    subsets: usize,
+    [partition, rotation, selection, color, alpha]: [u32; 5],
+    unique_p: bool,
+    shared_p: bool,
+    index: u32,
+    index2: u32,
+) -> Mode {
+    Mode {
+        subsets,
+        partition,
+        rotation,
+        selection,
+        color,
+        alpha,
+        unique_p,
+        shared_p,
+        index,
+        index2,
+    }
+}
+
+const MODES: [Mode; 8] = [
+    mode(3, [4, 0, 0, 4, 0], true, false, 3, 0),
+    mode(2, [6, 0, 0, 6, 0], false, true, 3, 0),
+    mode(3, [6, 0, 0, 5, 0], false, false, 2, 0),
+    mode(2, [6, 0, 0, 7, 0], true, false, 2, 0),
+    mode(1, [0, 2, 1, 5, 6], false, false, 2, 3),
+    mode(1, [0, 2, 0, 7, 8], false, false, 2, 2),
+    mode(1, [0, 0, 0, 7, 7], true, false, 4, 0),
+    mode(2, [6, 0, 0, 5, 5], true, false, 2, 0),
+];
+
+struct Bits(u128);
+
+impl Bits {
+    const fn read(&mut self, bits: u32) -> u8 {
+        let x = (self.0 & ((1 << bits) - 1)) as u8;
+        self.0 >>= bits;
+        x
+    }
+}
+
+const fn interpolate(e0: u8, e1: u8, index: u8, bits: u32) -> u8 {
+    let w = match bits {
+        2 => WEIGHTS2[index as usize],
+        3 => WEIGHTS3[index as usize],
+        _ => WEIGHTS4[index as usize],
+    };
+    (((64 - w) * e0 as u32 + w * e1 as u32 + 32) >> 6) as u8
+}
+
+/// Decode a BC7 block into 16 RGBA pixels. Reserved blocks decode to transparent black.
+pub fn decode(block: &[u8; 16]) -> [[u8; 4]; 16] {
+    let Some(m) = MODES.get(block[0].trailing_zeros() as usize) else {
+        return [[0; 4]; 16];
+    };
+    let mut b = Bits(u128::from_le_bytes(*block) >> (block[0].trailing_zeros() + 1));
+    let partition = b.read(m.partition) as usize;
+    let rotation = b.read(m.rotation);
+    let selection = b.read(m.selection);
+
+    // [subset][endpoint][channel]
+    let mut ends = [[[0u8; 4]; 2]; 3];
+    for c in 0..4 {
+        let bits = if c == 3 { m.alpha } else { m.color };
+        for s in &mut ends[..m.subsets] {
+            for e in s {
+                e[c] = b.read(bits);
+            }
+        }
+    }
+    let mut p = [[0; 2]; 3];
+    if m.unique_p {
+        p[..m.subsets]
+            .iter_mut()
+            .flatten()
+            .for_each(|x| *x = b.read(1));
+    } else if m.shared_p {
+        p[..m.subsets].iter_mut().for_each(|x| *x = [b.read(1); 2]);
+    }
+    let extra = (m.unique_p || m.shared_p) as u32;
+    for (s, p) in ends.iter_mut().zip(p) {
+        for (e, p) in s.iter_mut().zip(p) {
+            for (c, x) in e.iter_mut().enumerate() {
+                let bits = if c == 3 { m.alpha } else { m.color };
+                if bits == 0 {
+                    *x = 255;
+                    continue;
+                }
+                let (v, bits) = ((*x as u32) << extra | p as u32, bits + extra);
+                *x = (v << (8 - bits) | v >> (2 * bits - 8)) as u8;
+            }
+        }
+    }
+
+    let subset = |i: usize| match m.subsets {
+        1 => 0,
+        2 => (PARTITION2[partition] >> i & 1) as usize,
+        _ => (PARTITION3[partition] >> (2 * i) & 3) as usize,
+    };
+    let anchor = |i: usize| {
+        i == 0
+            || match m.subsets {
+                1 => false,
+                2 => i == ANCHOR2[partition] as usize,
+                _ => i == ANCHOR3_1[partition] as usize || i == ANCHOR3_2[partition] as usize,
+            }
+    };
+    let indices: [u8; 16] = std::array::from_fn(|i| b.read(m.index - anchor(i) as u32));
+    let indices2: [u8; 16] = match m.index2 {
+        0 => indices,
+        bits => std::array::from_fn(|i| b.read(bits - (i == 0) as u32)),
+    };
+    let ((color, cb), (alpha, ab)) = match selection {
+        0 => ((indices, m.index), (indices2, m.index2.max(m.index))),
+        _ => ((indices2, m.index2), (indices, m.index)),
+    };
+    std::array::from_fn(|i| {
+        let [e0, e1] = ends[subset(i)];
+        let mut px: [u8; 4] = std::array::from_fn(|c| interpolate(e0[c], e1[c], color[i], cb));
+        px[3] = interpolate(e0[3], e1[3], alpha[i], ab);
+        if rotation != 0 {
+            px.swap(3, rotation as usize - 1);
+        }
+        px
+    })
+}
+
+#[test]
+fn mode6() {
+    // mode 6, red 0 → 254, green 127 → 127, blue 254 → 0, opaque, indices 0, 1, .., 15
+    let mut bits = 1u128 << 6;
+    let mut at = 7;
+    let mut put = |x: u128, n: u32| {
+        bits |= x << at;
+        at += n;
+    };
+    for (e0, e1) in [(0, 127), (63, 63), (127, 0), (127, 127)] {
+        put(e0, 7);
+        put(e1, 7);
+    }
+    put(0, 1);
+    put(0, 1);
+    put(0, 3);
+    for i in 1..16 {
+        put(i, 4);
+    }
+    let px = decode(&bits.to_le_bytes());
+    assert_eq!(px[0], [0, 126, 254, 254]);
+    assert_eq!(px[15], [254, 126, 0, 254]);
+    assert_eq!(px[8][0], interpolate(0, 254, 8, 4));
+    // reserved
+    assert_eq!(decode(&[0; 16]), [[0; 4]; 16]);
+}
@@ -0,0 +1,638 @@
+//! [DDS](https://learn.microsoft.com/en-us/windows/win32/direct3ddds/dx-graphics-dds-pguide) texture encoding and decoding,
+//! with block compression.
+//!
+//! Reads BC1 to BC5 and BC7 compressed, and uncompressed 8 bit, textures, with their mip chains.
+//! Only the first image of cube maps and texture arrays is read. Volume textures are not supported.
+//! Writes BC1 to BC5 compressed, and uncompressed, textures. BC7 can not be written.
+//!
+//! BC4 produces an [`Image<_, 1>`], BC5 (red and green) an [`Image<_, 3>`] with a blue of 0, and everything else an [`Image<_, 4>`].
+use crate::{DynImage, Error, Image, pixels::convert::PFrom};
+mod bc;
+mod bc7;
+
+/// Read a dds texture.
+pub trait ReadDds: Sized {
+    /// Read a dds into an image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error>;
+}
+
+/// Write a dds texture.
+pub trait WriteDds {
+    /// Write this texture as a dds, with the default [`Compression`] for its channels:
+    /// [`Compression::Bc4`] for 1, [`Compression::Bc5`] for 2, [`Compression::Bc1`] for 3, and [`Compression::Bc3`] for 4.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error>;
+
+    /// Write this texture as a dds, with this compression.
+    fn write_with(
+        &self,
+        f: &mut impl std::io::Write,
+        compression: Compression,
+    ) -> Result<(), Error>;
+}
+
+macro_rules! fail {
+    ($x:literal) => {
+        return Err(Error::Decode(concat!("dds: ", $x).into()))
+    };
+}
+
+/// How the pixels of a dds are stored.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Compression {
+    /// Uncompressed, 8 bits per channel.
+    None,
+    /// RGB, with 1 bit alpha. 8 bytes per block.
+    Bc1,
+    /// RGB, with explicit 4 bit alpha. 16 bytes per block.
+    Bc2,
+    /// RGB, with interpolated alpha. 16 bytes per block.
+    Bc3,
+    /// One channel. 8 bytes per block.
+    Bc4,
+    /// Two channels, red and green. 16 bytes per block.
+    Bc5,
+    /// High quality RGBA. 16 bytes per block. Can only be decoded.
+    Bc7,
+}
+
+impl Compression {
+    /// The channels of a decoded image.
+    #[must_use]
+    pub const fn channels(self) -> Option<usize> {
+        match self {
+            Self::None => None,
+            Self::Bc4 => Some(1),
+            Self::Bc5 => Some(3),
+            _ => Some(4),
+        }
+    }
+
+    /// The bytes per 4 × 4 block.
+    const fn block(self) -> usize {
+        match self {
+            Self::None => 0,
+            Self::Bc1 | Self::Bc4 => 8,
+            _ => 16,
+        }
+    }
+
+    const fn four_cc(self) -> &'static [u8; 4] {
+        match self {
+            Self::Bc1 => b"DXT1",
+            Self::Bc2 => b"DXT3",
+            Self::Bc3 => b"DXT5",
+            Self::Bc4 => b"ATI1",
+            Self::Bc5 => b"ATI2",
+            Self::None | Self::Bc7 => &[0; 4],
+        }
+    }
+}
+
+const MAGIC: &[u8; 4] = b"DDS ";
+
+/// The most pixels of a decoded image.
+const MAX_PIXELS: usize = 400_000_000;
+
+/// The pixels of a `w` × `h` image, or a [`Error::Decode`] if there are too many.
+fn pixels((w, h): (u32, u32)) -> Result<usize, Error> {
+    match (w as usize).checked_mul(h as usize) {
+        Some(n) if n <= MAX_PIXELS => Ok(n),
+        _ => fail!("image too large"),
+    }
+}
+
+/// The blocks of an image, with edge pixels repeated to fill the last blocks.
+fn blocks<const N: usize>(image: Image<&[u8], N>) -> impl Iterator<Item = [[u8; N]; 16]> + '_ {
+    let (w, h) = (image.width(), image.height());
+    (0..h.div_ceil(4)).flat_map(move |by| {
+        (0..w.div_ceil(4)).map(move |bx| {
+            std::array::from_fn(|i| {
+                let x = (bx * 4 + i as u32 % 4).min(w - 1);
+                let y = (by * 4 + i as u32 / 4).min(h - 1);
+                // SAFETY: clamped to the image
+                unsafe { *image.pixel(x, y) }
+            })
+        })
+    })
+}
+
+/// Compress an image. It is converted to the channels of the compression,
+/// except for [`Compression::Bc5`], which keeps the first two channels (the grey of a grey image).
+///
+/// Returns [`Error::Encode`] for [`Compression::Bc7`].
+pub fn compress<const N: usize>(
+    image: Image<&[u8], N>,
+    compression: Compression,
+) -> Result<Vec<u8>, Error>
+where
+    [u8; 4]: PFrom<N>,
+    [u8; 1]: PFrom<N>,
+{
+    if compression == Compression::None {
+        return Ok(image.bytes().to_vec());
+    }
+    let rgba = |b: [[u8; N]; 16]| b.map(<[u8; 4] as PFrom<N>>::pfrom);
+    let mut out = vec![];
+    for b in blocks(image) {
+        match compression {
+            Compression::Bc1 => out.extend(bc::encode_bc1(&rgba(b), true)),
+            Compression::Bc2 => {
+                let b = rgba(b);
+                out.extend(bc::encode_bc2_alpha(&b));
+                out.extend(bc::encode_bc1(&b, false));
+            }
+            Compression::Bc3 => {
+                let b = rgba(b);
+                out.extend(bc::encode_bc4(&b.map(|p| p[3])));
+                out.extend(bc::encode_bc1(&b, false));
+            }
+            Compression::Bc4 => {
+                out.extend(bc::encode_bc4(
+                    &b.map(|p| <[u8; 1] as PFrom<N>>::pfrom(p)[0]),
+                ));
+            }
+            Compression::Bc5 => {
+                out.extend(bc::encode_bc4(&b.map(|p| p[0])));
+                out.extend(bc::encode_bc4(&b.map(|p| p[1 % N])));
+            }
+            Compression::None | Compression::Bc7 => {
+                return Err(Error::Encode("dds: bc7 encoding is not supported".into()));
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Decompress the blocks of a `width` × `height` image.
+/// Use [`decode`] for uncompressed data, which needs the layout in the header.
+pub fn decompress(
+    data: &[u8],
+    (w, h): (u32, u32),
+    compression: Compression,
+) -> Result<DynImage<Box<[u8]>>, Error> {
+    let Some(n) = compression.channels() else {
+        fail!("uncompressed data has no blocks")
+    };
+    let pixels = pixels((w, h))?;
+    let (bw, bh) = (w.div_ceil(4) as usize, h.div_ceil(4) as usize);
+    let size = compression.block();
+    let Some(expected) = bw.checked_mul(bh).and_then(|x| x.checked_mul(size)) else {
+        fail!("image too large")
+    };
+    if w == 0 || h == 0 || data.len() < expected {
+        return Err(Error::DimensionMismatch {
+            expected,
+            got: data.len(),
+        });
+    }
+    let Some(len) = pixels.checked_mul(n) else {
+        fail!("image too large")
+    };
+    let mut out = vec![0; len];
+    for (i, block) in data.chunks_exact(size).take(bw * bh).enumerate() {
+        let (half, rest) = block.split_first_chunk::<8>().unwrap();
+        let px: [[u8; 4]; 16] = match compression {
+            Compression::Bc1 => bc::decode_bc1(half, true),
+            Compression::Bc2 | Compression::Bc3 => {
+                let color = rest.first_chunk::<8>().unwrap();
+                let alpha = match compression {
+                    Compression::Bc2 => bc::decode_bc2_alpha(half),
+                    _ => bc::decode_bc4(half),
+                };
+                let mut px = bc::decode_bc1(color, false);
+                px.iter_mut().zip(alpha).for_each(|(p, a)| p[3] = a);
+                px
+            }
+            Compression::Bc4 => bc::decode_bc4(half).map(|x| [x, 0, 0, 0]),
+            Compression::Bc5 => {
+                let g = bc::decode_bc4(rest.first_chunk::<8>().unwrap());
+                let r = bc::decode_bc4(half);
+                std::array::from_fn(|i| [r[i], g[i], 0, 0])
+            }
+            _ => bc7::decode(block.first_chunk::<16>().unwrap()),
+        };
+        let (bx, by) = (i % bw * 4, i / bw * 4);
+        for (j, p) in px.iter().enumerate() {
+            let (x, y) = (bx + j % 4, by + j / 4);
+            if x < w as usize && y < h as usize {
+                let at = (y * w as usize + x) * n;
+                out[at..at + n].copy_from_slice(&p[..n]);
+            }
+        }
+    }
+    let out = out.into_boxed_slice();
+    Ok(match n {
+        1 => DynImage::Y(Image::build(w, h).buf(out)),
+        3 => DynImage::Rgb(Image::build(w, h).buf(out)),
+        _ => DynImage::Rgba(Image::build(w, h).buf(out)),
+    })
+}
+
+/// The mip chain of an image: itself, then halvings (with a box filter) down to 1 × 1.
+pub fn mipmaps<const N: usize>(image: Image<&[u8], N>) -> Vec<Image<Box<[u8]>, N>> {
+    let mut chain = vec![Image::build(image.width(), image.height()).buf(image.bytes().into())];
+    while let Some(last) = chain.last()
+        && (last.width() > 1 || last.height() > 1)
+    {
+        let (w, h) = ((last.width() / 2).max(1), (last.height() / 2).max(1));
+        let mut buf = Vec::with_capacity(w as usize * h as usize * N);
+        for y in 0..h {
+            for x in 0..w {
+                let mut sum = [0u32; N];
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let sx = (x * 2 + dx).min(last.width() - 1);
+                    let sy = (y * 2 + dy).min(last.height() - 1);
+                    // SAFETY: clamped to the image
+                    let p = unsafe { last.pixel(sx, sy) };
+                    sum.iter_mut().zip(p).for_each(|(s, &p)| *s += p as u32);
+                }
+                buf.extend(sum.map(|s| ((s + 2) / 4) as u8));
+            }
+        }
+        chain.push(Image::build(w, h).buf(buf.into()));
+    }
+    chain
+}
+
+fn u32le(d: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(d[at..at + 4].try_into().unwrap())
+}
+
+/// The layout of uncompressed pixels: bits per pixel, and the mask of each channel (Y, YA, RGB, or RGBA).
+struct Masks {
+    bits: u32,
+    masks: Vec<u32>,
+}
+
+impl Masks {
+    fn decode(&self, data: &[u8], (w, h): (u32, u32)) -> Result<DynImage<Box<[u8]>>, Error> {
+        let bytes = self.bits as usize / 8;
+        if !matches!(bytes, 1..=4) || !self.bits.is_multiple_of(8) {
+            fail!("unsupported pixel format")
+        }
+        let n = pixels((w, h))?;
+        let Some(expected) = n.checked_mul(bytes) else {
+            fail!("image too large")
+        };
+        if data.len() < expected {
+            return Err(Error::DimensionMismatch {
+                expected,
+                got: data.len(),
+            });
+        }
+        if self.masks.contains(&0) {
+            fail!("unsupported pixel format")
+        }
+        let channel = |x: u32, mask: u32| {
+            let max = mask >> mask.trailing_zeros();
+            (((x & mask) >> mask.trailing_zeros()) as u64 * 255 / max as u64) as u8
+        };
+        let buf = data[..n * bytes]
+            .chunks_exact(bytes)
+            .flat_map(|p| {
+                let mut x = [0; 4];
+                x[..bytes].copy_from_slice(p);
+                let x = u32::from_le_bytes(x);
+                self.masks.iter().map(move |&m| channel(x, m))
+            })
+            .collect::<Box<[u8]>>();
+        Ok(match self.masks.len() {
+            1 => DynImage::Y(Image::build(w, h).buf(buf)),
+            2 => DynImage::Ya(Image::build(w, h).buf(buf)),
+            3 => DynImage::Rgb(Image::build(w, h).buf(buf)),
+            _ => DynImage::Rgba(Image::build(w, h).buf(buf)),
+        })
+    }
+
+    const fn size(&self, (w, h): (u32, u32)) -> usize {
+        w as usize * h as usize * (self.bits as usize / 8)
+    }
+}
+
+enum Layout {
+    Blocks(Compression),
+    Masks(Masks),
+}
+
+/// Decode a dds texture: its mip chain, largest first.
+pub fn decode(data: &[u8]) -> Result<Vec<DynImage<Box<[u8]>>>, Error> {
+    let Some((magic, data)) = data.split_first_chunk::<4>() else {
+        fail!("missing header")
+    };
+    if magic != MAGIC {
+        fail!("bad magic")
+    }
+    let Some(header) = data.get(..124) else {
+        fail!("missing header")
+    };
+    let (flags, h, w) = (u32le(header, 4), u32le(header, 8), u32le(header, 12));
+    if w == 0 || h == 0 {
+        fail!("empty image")
+    }
+    pixels((w, h))?;
+    if flags & 0x800000 != 0 && u32le(header, 20) > 1 {
+        fail!("volume textures are not supported")
+    }
+    let mips = u32le(header, 24).clamp(1, 32);
+    // the pixel format
+    let (pf_flags, four_cc) = (u32le(header, 76), &header[80..84]);
+    let mut at = 124;
+    let layout = if pf_flags & 0x4 != 0 {
+        match four_cc {
+            b"DXT1" => Layout::Blocks(Compression::Bc1),
+            b"DXT2" | b"DXT3" => Layout::Blocks(Compression::Bc2),
+            b"DXT4" | b"DXT5" => Layout::Blocks(Compression::Bc3),
+            b"ATI1" | b"BC4U" => Layout::Blocks(Compression::Bc4),
+            b"ATI2" | b"BC5U" => Layout::Blocks(Compression::Bc5),
+            b"DX10" => {
+                let Some(dx10) = data.get(124..144) else {
+                    fail!("missing dx10 header")
+                };
+                at = 144;
+                if u32le(dx10, 4) == 4 {
+                    fail!("volume textures are not supported")
+                }
+                let masks = |bits, masks: &[u32]| {
+                    Layout::Masks(Masks {
+                        bits,
+                        masks: masks.to_vec(),
+                    })
+                };
+                match u32le(dx10, 0) {
+                    70..=72 => Layout::Blocks(Compression::Bc1),
+                    73..=75 => Layout::Blocks(Compression::Bc2),
+                    76..=78 => Layout::Blocks(Compression::Bc3),
+                    79 | 80 => Layout::Blocks(Compression::Bc4),
+                    82 | 83 => Layout::Blocks(Compression::Bc5),
+                    97..=99 => Layout::Blocks(Compression::Bc7),
+                    27..=29 => masks(32, &[0xff, 0xff00, 0xff0000, 0xff000000]),
+                    87 | 90 | 91 => masks(32, &[0xff0000, 0xff00, 0xff, 0xff000000]),
+                    48 | 49 => masks(16, &[0xff, 0xff00]),
+                    60 | 61 => masks(8, &[0xff]),
+                    _ => fail!("unsupported pixel format"),
+                }
+            }
+            _ => fail!("unsupported pixel format"),
+        }
+    } else {
+        let [r, g, b, a] = std::array::from_fn(|i| u32le(header, 88 + 4 * i));
+        // rgb, luminance, or alpha only, and then alpha pixels
+        let mut masks = if pf_flags & 0x40 != 0 {
+            vec![r, g, b]
+        } else if pf_flags & 0x20000 != 0 {
+            vec![r]
+        } else if pf_flags & 0x2 != 0 {
+            vec![a]
+        } else {
+            fail!("unsupported pixel format")
+        };
+        if pf_flags & 0x1 != 0 {
+            masks.push(a);
+        }
+        Layout::Masks(Masks {
+            bits: u32le(header, 84),
+            masks,
+        })
+    };
+    let mut data = &data[at..];
+    let mut chain = vec![];
+    for level in 0..mips {
+        let size = ((w >> level).max(1), (h >> level).max(1));
+        let (image, len) = match &layout {
+            Layout::Blocks(c) => (
+                decompress(data, size, *c)?,
+                size.0.div_ceil(4) as usize * size.1.div_ceil(4) as usize * c.block(),
+            ),
+            Layout::Masks(m) => (m.decode(data, size)?, m.size(size)),
+        };
+        chain.push(image);
+        data = &data[len..];
+    }
+    Ok(chain)
+}
+
+/// Encode a mip chain (largest first) as a dds texture. Each level must be half the size of the last, see [`mipmaps`].
+pub fn encode<T: AsRef<[u8]>, const N: usize>(
+    mips: &[Image<T, N>],
+    compression: Compression,
+) -> Result<Vec<u8>, Error>
+where
+    [u8; 4]: PFrom<N>,
+    [u8; 1]: PFrom<N>,
+{
+    let Some(top) = mips.first() else {
+        return Err(Error::Encode("dds: no images".into()));
+    };
+    let (w, h) = (top.width(), top.height());
+    for (level, m) in mips.iter().enumerate() {
+        if (m.width(), m.height()) != ((w >> level).max(1), (h >> level).max(1)) {
+            return Err(Error::Encode("dds: bad mip size".into()));
+        }
+    }
+    let mut out = Vec::with_capacity(128);
+    out.extend(MAGIC);
+    let field = |out: &mut Vec<u8>, x: u32| out.extend(x.to_le_bytes());
+    // caps, height, width, pixel format, and pitch or linear size
+    let mut flags = 0x1 | 0x2 | 0x4 | 0x1000;
+    let pitch = if compression == Compression::None {
+        flags |= 0x8;
+        w * N as u32
+    } else {
+        flags |= 0x80000;
+        (w.div_ceil(4) * h.div_ceil(4)) * compression.block() as u32
+    };
+    if mips.len() > 1 {
+        flags |= 0x20000;
+    }
+    for x in [124, flags, h, w, pitch, 0, mips.len() as u32] {
+        field(&mut out, x);
+    }
+    out.extend([0; 44]);
+    field(&mut out, 32);
+    if compression == Compression::None {
+        let (pf, masks) = match N {
+            1 => (0x20000, [0xff, 0, 0, 0]),
+            2 => (0x20001, [0xff, 0, 0, 0xff00]),
+            3 => (0x40, [0xff, 0xff00, 0xff0000, 0]),
+            _ => (0x41, [0xff, 0xff00, 0xff0000, 0xff000000]),
+        };
+        for x in [pf, 0, N as u32 * 8] {
+            field(&mut out, x);
+        }
+        masks.into_iter().for_each(|x| field(&mut out, x));
+    } else {
+        field(&mut out, 0x4);
+        out.extend(compression.four_cc());
+        out.extend([0; 20]);
+    }
+    // texture, and complex and mipmap
+    let caps = if mips.len() > 1 { 0x401008 } else { 0x1000 };
+    for x in [caps, 0, 0, 0, 0] {
+        field(&mut out, x);
+    }
+    for m in mips {
+        out.extend(compress(m.as_ref(), compression)?);
+    }
+    Ok(out)
+}
+
+impl ReadDds for Vec<DynImage<Box<[u8]>>> {
+    /// Read the mip chain of a dds, largest first.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        let mut data = vec![];
+        f.read_to_end(&mut data)?;
+        decode(&data)
+    }
+}
+
+impl ReadDds for DynImage<Box<[u8]>> {
+    /// Read the largest image of a dds.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        // decode() always produces the first level
+        Ok(Vec::<Self>::read(f)?.swap_remove(0))
+    }
+}
+
+impl<const N: usize> ReadDds for Image<Box<[u8]>, N>
+where
+    Self: From<DynImage<Box<[u8]>>>,
+{
+    /// Read the largest image of a dds, converting it to this many channels.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        DynImage::read(f).map(Into::into)
+    }
+}
+
+macro_rules! writer {
+    ($n:literal, $default:ident) => {
+        impl<T: AsRef<[u8]>> WriteDds for [Image<T, $n>] {
+            /// Write this mip chain as a dds.
+            fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+                self.write_with(f, Compression::$default)
+            }
+
+            /// Write this mip chain as a dds.
+            fn write_with(
+                &self,
+                f: &mut impl std::io::Write,
+                compression: Compression,
+            ) -> Result<(), Error> {
+                f.write_all(&encode(self, compression)?)?;
+                Ok(())
+            }
+        }
+
+        impl<T: AsRef<[u8]>> WriteDds for Image<T, $n> {
+            /// Write this image as a dds, without mips.
+            fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+                [self.as_ref()].write(f)
+            }
+
+            /// Write this image as a dds, without mips.
+            fn write_with(
+                &self,
+                f: &mut impl std::io::Write,
+                compression: Compression,
+            ) -> Result<(), Error> {
+                [self.as_ref()].write_with(f, compression)
+            }
+        }
+    };
+}
+writer!(1, Bc4);
+writer!(2, Bc5);
+writer!(3, Bc1);
+writer!(4, Bc3);
+
+impl<T: AsRef<[u8]> + crate::Buffer> WriteDds for DynImage<T> {
+    /// Write this image as a dds, without mips. 16 bit images are reduced to 8 bit.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        match self {
+            Self::Y(_) | Self::Y16(_) => self.y().write(f),
+            Self::Ya(_) | Self::Ya16(_) => self.ya().write(f),
+            Self::Rgb(_) | Self::Rgb16(_) => self.rgb().write(f),
+            Self::Rgba(_) | Self::Rgba16(_) => self.rgba().write(f),
+        }
+    }
+
+    /// Write this image as a dds, without mips. 16 bit images are reduced to 8 bit.
+    fn write_with(
+        &self,
+        f: &mut impl std::io::Write,
+        compression: Compression,
+    ) -> Result<(), Error> {
+        match self {
+            Self::Y(_) | Self::Y16(_) => self.y().write_with(f, compression),
+            Self::Ya(_) | Self::Ya16(_) => self.ya().write_with(f, compression),
+            Self::Rgb(_) | Self::Rgb16(_) => self.rgb().write_with(f, compression),
+            Self::Rgba(_) | Self::Rgba16(_) => self.rgba().write_with(f, compression),
+        }
+    }
+}
+
+#[cfg(feature = "save")]
+#[test]
+fn roundtrip() {
+    let cat = Image::<_, 4>::open("tdata/small_cat.png");
+    let chain = mipmaps(cat.as_ref());
+    assert_eq!(chain.len(), 9);
+    assert_eq!((chain[8].width(), chain[8].height()), (1, 1));
+    for c in [
+        Compression::None,
+        Compression::Bc1,
+        Compression::Bc2,
+        Compression::Bc3,
+        Compression::Bc4,
+        Compression::Bc5,
+    ] {
+        let mut out = vec![];
+        chain.write_with(&mut out, c).unwrap();
+        let d = Vec::<DynImage<Box<[u8]>>>::read(&mut &out[..]).unwrap();
+        assert_eq!(d.len(), chain.len());
+        for (d, m) in d.iter().zip(&chain) {
+            assert_eq!((d.width(), d.height()), (m.width(), m.height()));
+        }
+        let d = d.into_iter().next().unwrap();
+        let error = match c {
+            Compression::None => {
+                assert_eq!(d.bytes(), cat.bytes());
+                continue;
+            }
+            Compression::Bc4 => {
+                mean_error(d.bytes(), Image::<Box<[u8]>, 1>::from(cat.as_ref()).bytes())
+            }
+            Compression::Bc5 => {
+                let rg = cat.chunked().flat_map(|&[r, g, ..]| [r, g, 0]);
+                mean_error(d.bytes(), &rg.collect::<Vec<_>>())
+            }
+            _ => mean_error(d.bytes(), cat.bytes()),
+        };
+        assert!(error < 6.0, "{c:?}: {error}");
+    }
+    assert!(chain.write_with(&mut vec![], Compression::Bc7).is_err());
+    assert!(chain[1..].write(&mut vec![]).is_ok());
+    // not a mip chain
+    assert!(
+        [chain[0].as_ref(), chain[2].as_ref()]
+            .write(&mut vec![])
+            .is_err()
+    );
+    // huge sizes, from a small file
+    assert!(decompress(&[0; 64], (u32::MAX, u32::MAX), Compression::Bc1).is_err());
+    for c in [Compression::None, Compression::Bc1] {
+        let mut out = vec![];
+        cat.write_with(&mut out, c).unwrap();
+        out[12..20].fill(0xff);
+        assert!(Vec::<DynImage<Box<[u8]>>>::read(&mut &out[..]).is_err());
+    }
+}
+
+#[cfg(test)]
+fn mean_error(a: &[u8], b: &[u8]) -> f32 {
+    assert_eq!(a.len(), b.len());
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| a.abs_diff(*b) as f32)
+        .sum::<f32>()
+        / a.len() as f32
+}
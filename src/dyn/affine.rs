@@ -1,6 +1,9 @@
-use super::{e, DynImage};
+use super::{Buffer, DynImage, e};
 
-impl<T: AsMut<[u8]> + AsRef<[u8]>> DynImage<T> {
+impl<T: AsMut<[u8]> + AsRef<[u8]> + Buffer> DynImage<T>
+where
+    T::Wide: AsMut<[u16]>,
+{
     /// Rotate this image 90 degrees clockwise.
     ///
     /// # Safety
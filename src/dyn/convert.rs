@@ -1,29 +1,27 @@
 #![allow(clippy::useless_conversion)]
-use super::{e, DynImage, Image};
+use super::{Buffer, DynImage, Image, e};
 
-impl From<DynImage<Box<[u8]>>> for Image<Box<[u8]>, 1> {
-    fn from(value: DynImage<Box<[u8]>>) -> Self {
-        e!(value, |i| i.into())
-    }
-}
+macro_rules! into {
+    ($n:literal) => {
+        impl From<DynImage<Box<[u8]>>> for Image<Box<[u8]>, $n> {
+            fn from(value: DynImage<Box<[u8]>>) -> Self {
+                e!(value, |i| i.into(), |i| i.to_u8().into())
+            }
+        }
 
-impl From<DynImage<Box<[u8]>>> for Image<Box<[u8]>, 2> {
-    fn from(value: DynImage<Box<[u8]>>) -> Self {
-        e!(value, |i| i.into())
-    }
-}
-impl From<DynImage<Box<[u8]>>> for Image<Box<[u8]>, 3> {
-    fn from(value: DynImage<Box<[u8]>>) -> Self {
-        e!(value, |i| i.into())
-    }
-}
-impl From<DynImage<Box<[u8]>>> for Image<Box<[u8]>, 4> {
-    fn from(value: DynImage<Box<[u8]>>) -> Self {
-        e!(value, |i| i.into())
-    }
+        impl From<DynImage<Box<[u8]>>> for Image<Box<[u16]>, $n> {
+            fn from(value: DynImage<Box<[u8]>>) -> Self {
+                e!(value, |i| i.to_u16().into(), |i| i.into())
+            }
+        }
+    };
 }
+into!(1);
+into!(2);
+into!(3);
+into!(4);
 
-impl<T> DynImage<T> {
+impl<T: Buffer> DynImage<T> {
     /// Gets out the Y image, if its there, else returning self.
     ///
     /// If you want to convert, see [`DynImage::to_y`].
@@ -87,24 +85,29 @@ impl DynImage<Box<[u8]>> {
     }
 }
 
-impl<T: AsRef<[u8]>> DynImage<T> {
+impl<T: AsRef<[u8]> + Buffer> DynImage<T> {
+    /// Produce a 8 bit image from this dyn image, with the same channels. 16 bit images are reduced to 8 bit.
+    pub fn to_u8(&self) -> DynImage<Box<[u8]>> {
+        e!(self, |i| i.as_ref().boxed().into(), |i| i.to_u8().into())
+    }
+
     /// Produce a Y image from this dyn image.
     pub fn y(&self) -> Image<Box<[u8]>, 1> {
-        e!(self, |i| i.as_ref().into())
+        e!(narrow self, |i| i.as_ref().into())
     }
 
     /// Produce a YA image from this dyn image.
     pub fn ya(&self) -> Image<Box<[u8]>, 2> {
-        e!(self, |i| i.as_ref().into())
+        e!(narrow self, |i| i.as_ref().into())
     }
 
     /// Produce a RGB image from this dyn image.
     pub fn rgb(&self) -> Image<Box<[u8]>, 3> {
-        e!(self, |i| i.as_ref().into())
+        e!(narrow self, |i| i.as_ref().into())
     }
 
     /// Produce a RGBA image from this dyn image.
     pub fn rgba(&self) -> Image<Box<[u8]>, 4> {
-        e!(self, |i| i.as_ref().into())
+        e!(narrow self, |i| i.as_ref().into())
     }
 }
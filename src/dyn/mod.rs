@@ -4,11 +4,42 @@ mod convert;
 #[cfg(feature = "scale")]
 mod scale;
 
+/// A byte buffer that has a 16 bit counterpart, for the 16 bit variants of a [`DynImage`].
+pub trait Buffer {
+    /// The 16 bit buffer. (`Vec<u8>` => `Vec<u16>`)
+    type Wide: AsRef<[u16]>;
+}
+
+impl Buffer for Vec<u8> {
+    type Wide = Vec<u16>;
+}
+
+impl Buffer for Box<[u8]> {
+    type Wide = Box<[u16]>;
+}
+
+impl<'a> Buffer for &'a [u8] {
+    type Wide = &'a [u16];
+}
+
+impl<'a> Buffer for &'a mut [u8] {
+    type Wide = &'a mut [u16];
+}
+
+impl<const N: usize> Buffer for [u8; N] {
+    type Wide = [u16; N];
+}
+
 #[derive(Clone, Debug, Hash)]
 #[derive_const(PartialEq)]
 /// Dynamic image.
-/// Can be any of {`Y8`, `YA8`, `RGB8`, `RGB16`}.
-pub enum DynImage<T> {
+/// Can be any of {`Y8`, `YA8`, `RGB8`, `RGBA8`, `Y16`, `YA16`, `RGB16`, `RGBA16`}.
+///
+/// # Breaking changes
+///
+/// The 16 bit variants hold a [`Buffer::Wide`], so `T` must be a [`Buffer`]. (`Vec<u8>`, `Box<[u8]>`, `&[u8]`, `&mut [u8]`, or `[u8; N]`)
+/// Matches on a `DynImage` must handle the new variants, and `mapped` takes a second closure, for the 16 bit buffer.
+pub enum DynImage<T: Buffer> {
     /// Y image
     Y(Image<T, 1>),
     /// YA image
@@ -17,29 +48,37 @@ pub enum DynImage<T> {
     Rgb(Image<T, 3>),
     /// RGBA image
     Rgba(Image<T, 4>),
+    /// 16 bit Y image
+    Y16(Image<T::Wide, 1>),
+    /// 16 bit YA image
+    Ya16(Image<T::Wide, 2>),
+    /// 16 bit RGB image
+    Rgb16(Image<T::Wide, 3>),
+    /// 16 bit RGBA image
+    Rgba16(Image<T::Wide, 4>),
 }
 
 impl Copy for DynImage<&[u8]> {}
 
-impl<T> const From<Image<T, 1>> for DynImage<T> {
+impl<T: Buffer> const From<Image<T, 1>> for DynImage<T> {
     fn from(x: Image<T, 1>) -> Self {
         Self::Y(x)
     }
 }
 
-impl<T> const From<Image<T, 2>> for DynImage<T> {
+impl<T: Buffer> const From<Image<T, 2>> for DynImage<T> {
     fn from(x: Image<T, 2>) -> Self {
         Self::Ya(x)
     }
 }
 
-impl<T> const From<Image<T, 3>> for DynImage<T> {
+impl<T: Buffer> const From<Image<T, 3>> for DynImage<T> {
     fn from(x: Image<T, 3>) -> Self {
         Self::Rgb(x)
     }
 }
 
-impl<T> const From<Image<T, 4>> for DynImage<T> {
+impl<T: Buffer> const From<Image<T, 4>> for DynImage<T> {
     fn from(x: Image<T, 4>) -> Self {
         Self::Rgba(x)
     }
@@ -47,46 +86,69 @@ impl<T> const From<Image<T, 4>> for DynImage<T> {
 
 macro_rules! e {
     ($dyn:expr => |$image: pat_param| $do:expr) => {
+        $crate::r#dyn::e!($dyn => |$image| $do, |$image| $do)
+    };
+    ($dyn:expr => |$image: pat_param| $do:expr, |$wide: pat_param| $wdo:expr) => {
         match $dyn {
             DynImage::Y($image) => DynImage::Y($do),
             DynImage::Ya($image) => DynImage::Ya($do),
             DynImage::Rgb($image) => DynImage::Rgb($do),
             DynImage::Rgba($image) => DynImage::Rgba($do),
+            DynImage::Y16($wide) => DynImage::Y16($wdo),
+            DynImage::Ya16($wide) => DynImage::Ya16($wdo),
+            DynImage::Rgb16($wide) => DynImage::Rgb16($wdo),
+            DynImage::Rgba16($wide) => DynImage::Rgba16($wdo),
         }
     };
     ($dyn:expr, |$image: pat_param| $do:expr) => {
+        $crate::r#dyn::e!($dyn, |$image| $do, |$image| $do)
+    };
+    // 16 bit images are reduced to 8 bit first.
+    (narrow $dyn:expr, |$image: pat_param| $do:expr) => {
+        $crate::r#dyn::e!($dyn, |$image| $do, |i| {
+            let $image = &i.to_u8();
+            $do
+        })
+    };
+    ($dyn:expr, |$image: pat_param| $do:expr, |$wide: pat_param| $wdo:expr) => {
         match $dyn {
             DynImage::Y($image) => $do,
             DynImage::Ya($image) => $do,
             DynImage::Rgb($image) => $do,
             DynImage::Rgba($image) => $do,
+            DynImage::Y16($wide) => $wdo,
+            DynImage::Ya16($wide) => $wdo,
+            DynImage::Rgb16($wide) => $wdo,
+            DynImage::Rgba16($wide) => $wdo,
         }
     };
 }
 pub(crate) use e;
 
 #[cfg(feature = "term")]
-impl<T: AsRef<[u8]>> std::fmt::Display for crate::term::Display<DynImage<T>> {
+impl<T: AsRef<[u8]> + Buffer> std::fmt::Display for crate::term::Display<DynImage<T>> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        e!(&self.0, |x| crate::term::Display(x.as_ref()).write(f))
+        let i = &self.0;
+        e!(narrow i, |x| crate::term::Display(x.as_ref()).write(f))
     }
 }
 
 #[cfg(feature = "term")]
-impl<T: AsRef<[u8]>> std::fmt::Debug for crate::term::Display<DynImage<T>> {
+impl<T: AsRef<[u8]> + Buffer> std::fmt::Debug for crate::term::Display<DynImage<T>> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        e!(&self.0, |x| crate::term::Display(x.as_ref()).write(f))
+        let i = &self.0;
+        e!(narrow i, |x| crate::term::Display(x.as_ref()).write(f))
     }
 }
 
 #[cfg(feature = "term")]
-impl<T: AsRef<[u8]>> std::fmt::Display for DynImage<T> {
+impl<T: AsRef<[u8]> + Buffer> std::fmt::Display for DynImage<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        e!(&self, |x| crate::term::Display(x.as_ref()).write(f))
+        e!(narrow self, |x| crate::term::Display(x.as_ref()).write(f))
     }
 }
 
-impl<T> DynImage<T> {
+impl<T: Buffer> DynImage<T> {
     /// Get the width of this image.
     pub const fn width(&self) -> u32 {
         e!(self, |i| i.width())
@@ -97,20 +159,50 @@ impl<T> DynImage<T> {
         e!(self, |i| i.height())
     }
 
+    /// Is this a 16 bit image?
+    pub const fn is_16(&self) -> bool {
+        matches!(
+            self,
+            Self::Y16(_) | Self::Ya16(_) | Self::Rgb16(_) | Self::Rgba16(_)
+        )
+    }
+
     #[doc(hidden)]
-    pub unsafe fn mapped<U, F: FnOnce(T) -> U>(self, f: F) -> DynImage<U> {
+    pub unsafe fn mapped<U: Buffer>(
+        self,
+        f: impl FnOnce(T) -> U,
+        g: impl FnOnce(T::Wide) -> U::Wide,
+    ) -> DynImage<U> {
         // SAFETY: we dont change anything, why check
-        unsafe { e!(self => |i| i.mapped(f)) }
+        unsafe { e!(self => |i| i.mapped(f), |i| i.mapped(g)) }
     }
 
     /// Get the image buffer.
+    ///
+    /// # Panics
+    ///
+    /// if this is a 16 bit image. See [`DynImage::buffer16`].
     pub const fn buffer(&self) -> &T {
-        e!(self, |i| i.buffer())
+        e!(self, |i| i.buffer(), |_| panic!("16 bit image"))
     }
 
     /// Take the image buffer.
+    ///
+    /// # Panics
+    ///
+    /// if this is a 16 bit image. See [`DynImage::take_buffer16`].
     pub fn take_buffer(self) -> T {
-        e!(self, |i| i.take_buffer())
+        e!(self, |i| i.take_buffer(), |_| panic!("16 bit image"))
+    }
+
+    /// Get the buffer of a 16 bit image. (`None` if this is a 8 bit image)
+    pub const fn buffer16(&self) -> Option<&T::Wide> {
+        e!(self, |_| None, |i| Some(i.buffer()))
+    }
+
+    /// Take the buffer of a 16 bit image. (`None` if this is a 8 bit image)
+    pub fn take_buffer16(self) -> Option<T::Wide> {
+        e!(self, |_| None, |i| Some(i.take_buffer()))
     }
 }
 
@@ -121,13 +213,13 @@ impl DynImage<&[u8]> {
     }
 }
 
-impl<T: AsRef<[u8]>> DynImage<T> {
+impl<T: AsRef<[u8]> + Buffer> DynImage<T> {
     /// Reference this image.
     pub fn as_ref(&self) -> DynImage<&[u8]> {
         e!(self => |i| i.as_ref())
     }
 
-    /// Get a pixel, of a type.
+    /// Get a pixel, of a type. 16 bit pixels are reduced to 8 bit.
     /// ```
     /// # use fimg::{Image, DynImage};
     /// let i = DynImage::Rgb(Image::alloc(50, 50));
@@ -143,59 +235,127 @@ impl<T: AsRef<[u8]>> DynImage<T> {
         [u8; P]: PFrom<3>,
         [u8; P]: PFrom<4>,
     {
-        e!(self, |i| PFrom::pfrom(unsafe { *i.pixel(x, y) }))
+        e!(self, |i| PFrom::pfrom(unsafe { *i.pixel(x, y) }), |i| {
+            PFrom::pfrom(unsafe { *i.pixel(x, y) }.map(crate::convert::u16_to_u8))
+        })
     }
 
-    /// Bytes of this image.
+    /// Bytes of this image. (native endian, for 16 bit images)
     pub fn bytes(&self) -> &[u8] {
-        e!(self, |i| i.bytes())
+        e!(self, |i| i.bytes(), |i| {
+            let b = i.buffer().as_ref();
+            // SAFETY: u16 has no padding, and is aligned stricter than u8
+            unsafe { std::slice::from_raw_parts(b.as_ptr().cast(), size_of_val(b)) }
+        })
+    }
+}
+
+#[cfg(feature = "save")]
+impl crate::ReadPng for DynImage<Box<[u8]>> {
+    /// Open a PNG image. 16 bit images are reduced to 8 bit; see [`DynImage::read16`] to keep them.
+    fn read_meta<T: std::io::BufRead + std::io::Seek>(
+        f: &mut T,
+    ) -> Result<(Self, crate::meta::Metadata), crate::Error> {
+        use png::Transformations as T;
+        crate::read_png(f, T::STRIP_16 | T::EXPAND)
+    }
+}
+
+#[cfg(feature = "save")]
+impl<T: AsRef<[u8]> + Buffer> crate::WritePng for DynImage<T> {
+    /// Write this image to a PNG.
+    fn write_with(
+        &self,
+        f: &mut impl std::io::Write,
+        options: &crate::PngOptions,
+    ) -> Result<(), crate::Error> {
+        e!(self, |i| crate::WritePng::write_with(i, f, options), |i| {
+            crate::WritePng::<u16>::write_with(i, f, options)
+        })
     }
 }
 
 impl DynImage<Box<[u8]>> {
+    #[cfg(feature = "save")]
+    /// Open a PNG image, returning a [`Error`](crate::Error) if that fails.
+    pub fn try_open(f: impl AsRef<std::path::Path>) -> Result<Self, crate::Error> {
+        let p = std::fs::File::open(f)?;
+        <Self as crate::ReadPng>::read(&mut std::io::BufReader::new(p))
+    }
+
+    #[cfg(feature = "save")]
+    /// Read a PNG image, keeping 16 bit images 16 bit. (use [`DynImage::buffer16`] to get at their buffer)
+    pub fn read16<T: std::io::BufRead + std::io::Seek>(f: &mut T) -> Result<Self, crate::Error> {
+        crate::read_png(f, png::Transformations::EXPAND).map(|(x, _)| x)
+    }
+
+    #[cfg(feature = "save")]
+    /// Open a PNG image, keeping 16 bit images 16 bit, returning a [`Error`](crate::Error) if that fails.
+    pub fn try_open16(f: impl AsRef<std::path::Path>) -> Result<Self, crate::Error> {
+        let p = std::fs::File::open(f)?;
+        Self::read16(&mut std::io::BufReader::new(p))
+    }
+
     #[cfg(feature = "save")]
     #[track_caller]
     /// Open a PNG image
+    ///
+    /// # Panics
+    ///
+    /// if the file could not be read. See [`DynImage::try_open`] for a fallible version.
     pub fn open(f: impl AsRef<std::path::Path>) -> Self {
-        use png::Transformations as T;
-        let p = std::fs::File::open(f).unwrap();
-        let r = std::io::BufReader::new(p);
-        let mut dec = png::Decoder::new(r);
-        dec.set_transformations(T::STRIP_16 | T::EXPAND);
-        let mut reader = dec.read_info().unwrap();
-        let mut buf = vec![0; reader.output_buffer_size().unwrap()].into_boxed_slice();
-        let info = reader.next_frame(&mut buf).unwrap();
-        use png::ColorType::*;
-        match info.color_type {
-            Indexed | Grayscale => Self::Y(Image::build(info.width, info.height).buf(buf)),
-            Rgb => Self::Rgb(Image::build(info.width, info.height).buf(buf)),
-            Rgba => Self::Rgba(Image::build(info.width, info.height).buf(buf)),
-            GrayscaleAlpha => Self::Ya(Image::build(info.width, info.height).buf(buf)),
-        }
+        Self::try_open(f).unwrap()
+    }
+}
+
+impl<T: AsRef<[u8]> + Buffer> DynImage<T> {
+    #[cfg(feature = "save")]
+    /// Save this image to a PNG, returning a [`Error`](crate::Error) if that fails.
+    pub fn try_save(&self, f: impl AsRef<std::path::Path>) -> Result<(), crate::Error> {
+        self.try_save_with(f, &crate::PngOptions::new())
+    }
+
+    #[cfg(feature = "save")]
+    /// Save this image to a PNG, with these [`PngOptions`](crate::PngOptions), returning a [`Error`](crate::Error) if that fails.
+    pub fn try_save_with(
+        &self,
+        f: impl AsRef<std::path::Path>,
+        options: &crate::PngOptions,
+    ) -> Result<(), crate::Error> {
+        use std::io::Write;
+        let mut w = std::io::BufWriter::new(std::fs::File::create(f)?);
+        crate::WritePng::write_with(self, &mut w, options)?;
+        w.flush()?;
+        Ok(())
     }
 
     #[cfg(feature = "save")]
     #[track_caller]
     /// Save this image to a PNG.
+    ///
+    /// # Panics
+    ///
+    /// if the file could not be written. See [`DynImage::try_save`] for a fallible version.
     pub fn save(&self, f: impl AsRef<std::path::Path>) {
-        let p = std::fs::File::create(f).unwrap();
-        let w = &mut std::io::BufWriter::new(p);
-        let mut enc = png::Encoder::new(w, self.width(), self.height());
-        enc.set_depth(png::BitDepth::Eight);
-        enc.set_color(match self {
-            Self::Y(_) => png::ColorType::Grayscale,
-            Self::Ya(_) => png::ColorType::GrayscaleAlpha,
-            Self::Rgb(_) => png::ColorType::Rgb,
-            Self::Rgba(_) => png::ColorType::Rgba,
-        });
-        enc.set_source_gamma(png::ScaledFloat::new(1.0 / 2.2));
-        enc.set_source_chromaticities(png::SourceChromaticities::new(
-            (0.31270, 0.32900),
-            (0.64000, 0.33000),
-            (0.30000, 0.60000),
-            (0.15000, 0.06000),
-        ));
-        let mut writer = enc.write_header().unwrap();
-        writer.write_image_data(self.bytes()).unwrap();
+        self.try_save(f).unwrap()
     }
 }
+
+#[cfg(feature = "save")]
+#[test]
+fn png16() {
+    use crate::{ReadPng, WritePng};
+    let i = Image::<_, 3>::open("tdata/small_cat.png").to_u16();
+    let mut png = vec![];
+    WritePng::write(&i, &mut png).unwrap();
+    // only when asked for
+    let d = <DynImage<Box<[u8]>> as ReadPng>::read(&mut std::io::Cursor::new(&png)).unwrap();
+    assert!(!d.is_16());
+    assert_eq!(d.buffer(), i.to_u8().buffer());
+    let d = DynImage::read16(&mut std::io::Cursor::new(&png)).unwrap();
+    assert!(d.is_16());
+    assert_eq!(d.rgb().bytes(), i.to_u8().bytes());
+    assert_eq!(d.buffer16().unwrap(), i.buffer());
+    assert_eq!(d.to_u8().buffer(), i.to_u8().buffer());
+    assert_eq!(Image::<Box<[u16]>, 3>::from(d).buffer(), i.buffer());
+}
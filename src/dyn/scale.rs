@@ -1,10 +1,25 @@
 use crate::scale::traits::ScalingAlgorithm;
 
-use super::{e, DynImage};
+use super::{Buffer, DynImage, e};
 
-impl<T: AsMut<[u8]> + AsRef<[u8]>> DynImage<T> {
-    /// Scale this image with a given scaling algorithm.
+impl<T: AsMut<[u8]> + AsRef<[u8]> + Buffer> DynImage<T> {
+    /// Scale this image with a given scaling algorithm, to a 8 bit image.
+    /// 16 bit images are reduced to 8 bit first, as by [`DynImage::to_u8`]: check [`DynImage::is_16`] to avoid that.
     pub fn scale<A: ScalingAlgorithm>(&mut self, width: u32, height: u32) -> DynImage<Box<[u8]>> {
-        e!(self => |i| i.scale::<A>(width, height))
+        e!(self, |i| i.scale::<A>(width, height).into(), |i| {
+            i.to_u8().scale::<A>(width, height).into()
+        })
+    }
+
+    /// Scale this image in linear light with a given scaling algorithm, to a 8 bit image. See [`Image::scale_linear`](crate::Image::scale_linear).
+    /// 16 bit images are reduced to 8 bit first, as by [`DynImage::to_u8`].
+    pub fn scale_linear<A: ScalingAlgorithm>(
+        &self,
+        width: u32,
+        height: u32,
+    ) -> DynImage<Box<[u8]>> {
+        e!(self, |i| i.scale_linear::<A>(width, height).into(), |i| {
+            i.to_u8().scale_linear::<A>(width, height).into()
+        })
     }
 }
@@ -0,0 +1,96 @@
+//! error type for fallible image io.
+use std::fmt::{Display, Formatter};
+
+/// Errors that can occur when loading or saving a image.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The underlying reader or writer failed.
+    Io(std::io::Error),
+    /// The data is not a valid image.
+    Decode(Box<dyn std::error::Error + Send + Sync>),
+    /// The image could not be encoded.
+    Encode(Box<dyn std::error::Error + Send + Sync>),
+    /// The image has a color type (or bit depth) that is not supported.
+    UnsupportedColor(&'static str),
+    /// The data (or file extension) is not of a known image format.
+    UnknownFormat,
+    /// The image format is known, but its feature is not enabled.
+    DisabledFormat(crate::Format),
+    /// The buffer length does not match the dimensions of the image (or the dimensions are zero).
+    DimensionMismatch {
+        /// `width * height * channels`
+        expected: usize,
+        /// length of the buffer
+        got: usize,
+    },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(x) => write!(f, "io error: {x}"),
+            Self::Decode(x) => write!(f, "decoding error: {x}"),
+            Self::Encode(x) => write!(f, "encoding error: {x}"),
+            Self::UnsupportedColor(x) => write!(f, "unsupported color type: {x}"),
+            Self::UnknownFormat => write!(f, "unknown image format"),
+            Self::DisabledFormat(x) => write!(
+                f,
+                "{x:?} support is disabled (enable the `{}` feature)",
+                x.feature()
+            ),
+            Self::DimensionMismatch { expected, got } => {
+                write!(f, "invalid buffer size (expected {expected}, got {got})")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(x) => Some(x),
+            Self::Decode(x) | Self::Encode(x) => Some(&**x),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(x: std::io::Error) -> Self {
+        Self::Io(x)
+    }
+}
+
+#[cfg(feature = "save")]
+impl From<png::DecodingError> for Error {
+    fn from(x: png::DecodingError) -> Self {
+        match x {
+            png::DecodingError::IoError(x) => Self::Io(x),
+            x => Self::Decode(Box::new(x)),
+        }
+    }
+}
+
+#[cfg(feature = "save")]
+impl From<png::EncodingError> for Error {
+    fn from(x: png::EncodingError) -> Self {
+        match x {
+            png::EncodingError::IoError(x) => Self::Io(x),
+            x => Self::Encode(Box::new(x)),
+        }
+    }
+}
+
+#[cfg(feature = "save")]
+#[test]
+fn fallible() {
+    assert!(matches!(
+        crate::Image::<_, 3>::try_open("tdata/missing.png"),
+        Err(Error::Io(_))
+    ));
+    assert!(matches!(
+        crate::DynImage::try_open("tdata/small_cat.six"),
+        Err(Error::Decode(_))
+    ));
+}
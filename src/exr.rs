@@ -0,0 +1,500 @@
+//! [OpenEXR](https://openexr.com) encoding and decoding, for float images.
+//!
+//! Only single part scanline images, uncompressed or with RLE, ZIPS or ZIP compression.
+//! Reads half, float and uint channels named `R`, `G`, `B` and `A` (or `Y`, for gray images). Writes float channels.
+use crate::{Error, Image};
+
+/// Read a openexr image.
+pub trait ReadExr: Sized {
+    /// Read a exr into an image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error>;
+}
+
+/// Write a openexr image.
+pub trait WriteExr {
+    /// Write this exr image, with [`Compression::Zip`].
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error>;
+}
+
+/// How the scanlines of a exr are compressed. All of these are lossless.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Compression {
+    /// No compression.
+    None,
+    /// Run length encoding. Fast, but only good for flat images.
+    Rle,
+    /// Deflate, one scanline at a time.
+    Zips,
+    /// Deflate, 16 scanlines at a time.
+    #[default]
+    Zip,
+}
+
+impl Compression {
+    const fn lines(self) -> usize {
+        match self {
+            Self::Zip => 16,
+            _ => 1,
+        }
+    }
+}
+
+macro_rules! fail {
+    ($x:literal) => {
+        return Err(Error::Decode(concat!("exr: ", $x).into()))
+    };
+}
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+/// same limit as [`qoi`](crate::qoi).
+const MAX_PIXELS: usize = 400_000_000;
+
+/// Sample types.
+const UINT: i32 = 0;
+const HALF: i32 = 1;
+const FLOAT: i32 = 2;
+
+fn half(h: u16) -> f32 {
+    let sign = (h as u32 & 0x8000) << 16;
+    let (exp, man) = ((h >> 10) & 0x1f, (h & 0x3ff) as u32);
+    f32::from_bits(match exp {
+        // subnormal
+        0 => (man as f32 * 2f32.powi(-24)).to_bits() | sign,
+        // inf, nan
+        31 => sign | 0x7f80_0000 | man << 13,
+        _ => sign | (exp as u32 + 127 - 15) << 23 | man << 13,
+    })
+}
+
+/// Split the even and odd bytes, and store the differences, so that deflate (or rle) does better.
+fn predict(data: &[u8]) -> Vec<u8> {
+    let mut out = data.iter().step_by(2).copied().collect::<Vec<_>>();
+    out.extend(data.iter().skip(1).step_by(2));
+    for i in (1..out.len()).rev() {
+        out[i] = out[i].wrapping_sub(out[i - 1]).wrapping_add(128);
+    }
+    out
+}
+
+fn unpredict(mut data: Vec<u8>) -> Vec<u8> {
+    for i in 1..data.len() {
+        data[i] = data[i].wrapping_add(data[i - 1]).wrapping_sub(128);
+    }
+    let (even, odd) = data.split_at(data.len().div_ceil(2));
+    let mut out = Vec::with_capacity(data.len());
+    for (i, &x) in even.iter().enumerate() {
+        out.push(x);
+        out.extend(odd.get(i));
+    }
+    out
+}
+
+fn rle(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    let run = |i: usize| {
+        data[i..]
+            .iter()
+            .take(128)
+            .take_while(|&&x| x == data[i])
+            .count()
+    };
+    let mut i = 0;
+    while i < data.len() {
+        let r = run(i);
+        if r >= 3 {
+            out.extend([(r - 1) as u8, data[i]]);
+            i += r;
+            continue;
+        }
+        let mut j = i;
+        while j < data.len() && j - i < 127 && run(j) < 3 {
+            j += 1;
+        }
+        out.push((i as isize - j as isize) as u8);
+        out.extend(&data[i..j]);
+        i = j;
+    }
+    out
+}
+
+fn unrle(mut data: &[u8], size: usize) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::with_capacity(size);
+    while let [n, rest @ ..] = data {
+        let n = *n as i8;
+        if n < 0 {
+            let n = n.unsigned_abs() as usize;
+            if rest.len() < n {
+                fail!("unexpected end of data")
+            }
+            out.extend(&rest[..n]);
+            data = &rest[n..];
+        } else {
+            let Some((&x, rest)) = rest.split_first() else {
+                fail!("unexpected end of data")
+            };
+            out.extend(std::iter::repeat_n(x, n as usize + 1));
+            data = rest;
+        }
+        if out.len() > size {
+            fail!("too much data")
+        }
+    }
+    Ok(out)
+}
+
+/// Write the header attributes of a scanline image with these (sorted) channels.
+fn header(
+    out: &mut Vec<u8>,
+    (width, height): (u32, u32),
+    channels: &[&str],
+    kind: i32,
+    compression: Compression,
+) {
+    let mut attribute = |name: &str, kind: &str, value: &[u8]| {
+        out.extend(name.as_bytes());
+        out.push(0);
+        out.extend(kind.as_bytes());
+        out.push(0);
+        out.extend((value.len() as u32).to_le_bytes());
+        out.extend(value);
+    };
+    let mut list = vec![];
+    for c in channels {
+        list.extend(c.as_bytes());
+        list.push(0);
+        list.extend(kind.to_le_bytes());
+        // linear, reserved, x and y sampling
+        list.extend([0; 4]);
+        list.extend([1i32, 1].map(i32::to_le_bytes).as_flattened());
+    }
+    list.push(0);
+    attribute("channels", "chlist", &list);
+    attribute(
+        "compression",
+        "compression",
+        &[match compression {
+            Compression::None => 0,
+            Compression::Rle => 1,
+            Compression::Zips => 2,
+            Compression::Zip => 3,
+        }],
+    );
+    let window = [0, 0, width as i32 - 1, height as i32 - 1].map(i32::to_le_bytes);
+    attribute("dataWindow", "box2i", window.as_flattened());
+    attribute("displayWindow", "box2i", window.as_flattened());
+    attribute("lineOrder", "lineOrder", &[0]);
+    attribute("pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute("screenWindowCenter", "v2f", &[0; 8]);
+    attribute("screenWindowWidth", "float", &1f32.to_le_bytes());
+    out.push(0);
+}
+
+/// Encode an image to exr, with float channels.
+pub fn encode<const N: usize>(image: Image<&[f32], N>, compression: Compression) -> Vec<u8>
+where
+    [(); ((N == 3) | (N == 4)) as usize - 1]:,
+{
+    let (w, h) = (image.width() as usize, image.height() as usize);
+    // channels are sorted by name
+    let (names, order): (&[&str], &[usize]) = match N {
+        3 => (&["B", "G", "R"], &[2, 1, 0]),
+        _ => (&["A", "B", "G", "R"], &[3, 2, 1, 0]),
+    };
+    let mut out = MAGIC.to_vec();
+    out.extend([2, 0, 0, 0]);
+    header(&mut out, (w as u32, h as u32), names, FLOAT, compression);
+    let lines = compression.lines();
+    let table = out.len();
+    out.resize(table + h.div_ceil(lines) * 8, 0);
+    for (i, rows) in image.buffer().chunks(w * N * lines).enumerate() {
+        let mut raw = Vec::with_capacity(rows.len() * 4);
+        for row in rows.chunks_exact(w * N) {
+            for &c in order {
+                raw.extend(row.iter().skip(c).step_by(N).flat_map(|x| x.to_le_bytes()));
+            }
+        }
+        let packed = match compression {
+            Compression::None => None,
+            Compression::Rle => Some(rle(&predict(&raw))),
+            Compression::Zips | Compression::Zip => Some(
+                miniz_oxide::deflate::compress_to_vec_zlib(&predict(&raw), 6),
+            ),
+        };
+        // compression that does not help is not used
+        let data = match &packed {
+            Some(x) if x.len() < raw.len() => x,
+            _ => &raw,
+        };
+        let at = out.len() as u64;
+        out[table + i * 8..][..8].copy_from_slice(&at.to_le_bytes());
+        out.extend(((i * lines) as i32).to_le_bytes());
+        out.extend((data.len() as u32).to_le_bytes());
+        out.extend(data);
+    }
+    out
+}
+
+fn cstr<'a>(data: &mut &'a [u8]) -> Result<&'a [u8], Error> {
+    let Some(end) = data.iter().position(|&x| x == 0) else {
+        fail!("unexpected end of header")
+    };
+    let s = &data[..end];
+    *data = &data[end + 1..];
+    Ok(s)
+}
+
+fn i32le(d: &[u8], at: usize) -> i32 {
+    i32::from_le_bytes(d[at..at + 4].try_into().unwrap())
+}
+
+/// Decode a exr image. Missing channels are 0, except alpha, which is 1. Gray images become RGB.
+pub fn decode(data: &[u8]) -> Result<Image<Box<[f32]>, 4>, Error> {
+    let Some((&[m0, m1, m2, m3, version, flags, ..], mut d)) = data.split_first_chunk::<8>() else {
+        fail!("missing header")
+    };
+    if [m0, m1, m2, m3] != MAGIC {
+        fail!("bad magic")
+    }
+    if version != 2 {
+        fail!("unknown version")
+    }
+    if flags & 0x02 != 0 {
+        fail!("tiled images are not supported")
+    }
+    if flags & 0x18 != 0 {
+        fail!("multi part and deep images are not supported")
+    }
+    let mut channels = vec![];
+    let mut compression = None;
+    let mut window = None;
+    loop {
+        let name = cstr(&mut d)?;
+        if name.is_empty() {
+            break;
+        }
+        let kind = cstr(&mut d)?;
+        let Some((size, rest)) = d.split_first_chunk::<4>() else {
+            fail!("unexpected end of header")
+        };
+        let size = u32::from_le_bytes(*size) as usize;
+        if rest.len() < size {
+            fail!("unexpected end of header")
+        }
+        let (value, rest) = rest.split_at(size);
+        d = rest;
+        match (name, kind) {
+            (b"channels", b"chlist") => {
+                let mut v = value;
+                loop {
+                    let name = cstr(&mut v)?;
+                    if name.is_empty() {
+                        break;
+                    }
+                    if v.len() < 16 {
+                        fail!("bad channel list")
+                    }
+                    if i32le(v, 8) != 1 || i32le(v, 12) != 1 {
+                        fail!("subsampled channels are not supported")
+                    }
+                    let size = match i32le(v, 0) {
+                        HALF => 2,
+                        UINT | FLOAT => 4,
+                        _ => fail!("unknown sample type"),
+                    };
+                    channels.push((name, i32le(v, 0), size));
+                    v = &v[16..];
+                }
+            }
+            (b"compression", b"compression") => {
+                compression = Some(match value {
+                    [0] => Compression::None,
+                    [1] => Compression::Rle,
+                    [2] => Compression::Zips,
+                    [3] => Compression::Zip,
+                    _ => fail!("unsupported compression"),
+                })
+            }
+            (b"dataWindow", b"box2i") if size == 16 => {
+                window = Some([0, 4, 8, 12].map(|at| i32le(value, at) as i64))
+            }
+            _ => {}
+        }
+    }
+    let (Some(compression), Some([x0, y0, x1, y1])) = (compression, window) else {
+        fail!("missing required attributes")
+    };
+    let (w, h) = (x1 - x0 + 1, y1 - y0 + 1);
+    if w <= 0 || h <= 0 || w > u32::MAX as i64 || h > u32::MAX as i64 {
+        fail!("bad data window")
+    }
+    let (w, h) = (w as usize, h as usize);
+    if w * h > MAX_PIXELS {
+        fail!("image too large")
+    }
+    // where each channel goes, in rgba
+    let slots = channels
+        .iter()
+        .map(|&(name, ..)| match name {
+            b"R" => &[0][..],
+            b"G" => &[1],
+            b"B" => &[2],
+            b"A" => &[3],
+            b"Y" => &[0, 1, 2],
+            _ => &[],
+        })
+        .collect::<Vec<_>>();
+    if slots.iter().all(|s| s.is_empty() || s == &[3]) {
+        return Err(Error::UnsupportedColor("exr: no color channels"));
+    }
+    let lines = compression.lines();
+    let chunks = h.div_ceil(lines);
+    let line_size = channels.iter().map(|&(_, _, size)| size * w).sum::<usize>();
+    if d.len() < chunks * 8 {
+        fail!("unexpected end of data")
+    }
+    let mut out = [0.0, 0.0, 0.0, 1.0].repeat(w * h);
+    for offset in d[..chunks * 8].chunks_exact(8) {
+        let at = u64::from_le_bytes(offset.try_into().unwrap());
+        let Some(chunk) = usize::try_from(at).ok().and_then(|at| data.get(at..)) else {
+            fail!("bad offset")
+        };
+        if chunk.len() < 8 {
+            fail!("unexpected end of data")
+        }
+        let (y, size) = (i32le(chunk, 0) as i64 - y0, i32le(chunk, 4) as u32 as usize);
+        if y < 0 || y as usize >= h || !(y as usize).is_multiple_of(lines) {
+            fail!("bad chunk position")
+        }
+        let y = y as usize;
+        let Some(packed) = chunk.get(8..8 + size) else {
+            fail!("unexpected end of data")
+        };
+        let expected = lines.min(h - y) * line_size;
+        let raw = if size == expected {
+            packed.to_vec()
+        } else {
+            let predicted = match compression {
+                Compression::None => fail!("bad chunk size"),
+                Compression::Rle => unrle(packed, expected)?,
+                Compression::Zips | Compression::Zip => {
+                    miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(packed, expected)
+                        .map_err(|x| Error::Decode(format!("exr: {x}").into()))?
+                }
+            };
+            if predicted.len() != expected {
+                fail!("bad chunk size")
+            }
+            unpredict(predicted)
+        };
+        for (row, line) in raw.chunks_exact(line_size).enumerate() {
+            let mut line = line;
+            let out = &mut out[(y + row) * w * 4..][..w * 4];
+            for (&(_, kind, size), slots) in channels.iter().zip(&slots) {
+                let (samples, rest) = line.split_at(w * size);
+                line = rest;
+                for (px, x) in out.chunks_exact_mut(4).zip(samples.chunks_exact(size)) {
+                    let x = match kind {
+                        HALF => half(u16::from_le_bytes([x[0], x[1]])),
+                        FLOAT => f32::from_le_bytes([x[0], x[1], x[2], x[3]]),
+                        _ => u32::from_le_bytes([x[0], x[1], x[2], x[3]]) as f32,
+                    };
+                    for &s in *slots {
+                        px[s] = x;
+                    }
+                }
+            }
+        }
+    }
+    Ok(Image::build(w as u32, h as u32).buf(out.into()))
+}
+
+impl ReadExr for Image<Box<[f32]>, 4> {
+    /// Read a exr image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        let mut data = vec![];
+        f.read_to_end(&mut data)?;
+        decode(&data)
+    }
+}
+
+impl ReadExr for Image<Box<[f32]>, 3> {
+    /// Read a exr image, dropping the alpha.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        let i = Image::<Box<[f32]>, 4>::read(f)?;
+        let buf = i.chunked().flat_map(|&[r, g, b, _]| [r, g, b]).collect();
+        Ok(Self::build(i.width(), i.height()).buf(buf))
+    }
+}
+
+impl<T: AsRef<[f32]>> WriteExr for Image<T, 3> {
+    /// Write this image as a exr.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        f.write_all(&encode(self.as_ref(), Compression::Zip))?;
+        Ok(())
+    }
+}
+
+impl<T: AsRef<[f32]>> WriteExr for Image<T, 4> {
+    /// Write this image as a exr.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        f.write_all(&encode(self.as_ref(), Compression::Zip))?;
+        Ok(())
+    }
+}
+
+#[test]
+fn roundtrip() {
+    for (w, h) in [(1, 1), (5, 3), (40, 37)] {
+        let buf: Box<[f32]> = (0..w * h * 4)
+            .map(|i| match i % 7 {
+                0 => -2.5,
+                1 | 2 => 1e6,
+                _ => (i / 4) as f32 / 3.0,
+            })
+            .collect();
+        let rgba = Image::<_, 4>::build(w, h).buf(buf);
+        let rgb = Image::<Box<[f32]>, 3>::build(w, h)
+            .buf(rgba.chunked().flat_map(|&[r, g, b, _]| [r, g, b]).collect());
+        for c in [
+            Compression::None,
+            Compression::Rle,
+            Compression::Zips,
+            Compression::Zip,
+        ] {
+            let exr = encode(rgba.as_ref(), c);
+            assert_eq!(decode(&exr).unwrap().buffer(), rgba.buffer(), "{c:?}");
+            let exr = encode(rgb.as_ref(), c);
+            let back = Image::<Box<[f32]>, 3>::read(&mut &exr[..]).unwrap();
+            assert_eq!(back.buffer(), rgb.buffer(), "{c:?}");
+        }
+    }
+    // every byte the same, so rle works too
+    let flat = Image::<_, 3>::build(64, 64).buf(vec![f32::from_bits(0x3f3f_3f3f); 64 * 64 * 3]);
+    for c in [Compression::Rle, Compression::Zip] {
+        let exr = encode(flat.as_ref(), c);
+        assert!(exr.len() < 64 * 64 * 12 / 2, "{c:?}");
+        assert!(matches!(
+            decode(&exr[..exr.len() - 4]),
+            Err(Error::Decode(_))
+        ));
+    }
+}
+
+#[test]
+fn halves() {
+    assert_eq!(half(0x3c00), 1.0);
+    assert_eq!(half(0xc000), -2.0);
+    assert_eq!(half(0x7bff), 65504.0);
+    assert_eq!(half(0x0001), 2f32.powi(-24));
+    assert!(half(0x7e00).is_nan());
+    assert_eq!(half(0xfc00), f32::NEG_INFINITY);
+
+    // a gray half image
+    let mut exr = MAGIC.to_vec();
+    exr.extend([2, 0, 0, 0]);
+    header(&mut exr, (2, 1), &["Y"], HALF, Compression::None);
+    exr.extend((exr.len() as u64 + 8).to_le_bytes());
+    exr.extend([0, 0, 0, 0, 4, 0, 0, 0, 0x00, 0x38, 0x00, 0x40]);
+    let i = decode(&exr).unwrap();
+    assert_eq!(&*i.take_buffer(), [0.5, 0.5, 0.5, 1.0, 2.0, 2.0, 2.0, 1.0]);
+}
@@ -0,0 +1,172 @@
+//! [farbfeld](https://tools.suckless.org/farbfeld/) encoding and decoding.
+//!
+//! A 16 byte header, then 16 bit big endian RGBA.
+use crate::{DynImage, Error, Image, pixels::convert::PFrom};
+
+/// Read a farbfeld image.
+pub trait ReadFarbfeld: Sized {
+    /// Read a farbfeld into an image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error>;
+}
+
+/// Write a farbfeld image.
+///
+/// `C` is the component type: `u8` for 8 bit images, `u16` for 16 bit images.
+pub trait WriteFarbfeld<C = u8> {
+    /// Write this farbfeld image.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error>;
+}
+
+const MAGIC: &[u8; 8] = b"farbfeld";
+
+fn header(out: &mut Vec<u8>, (w, h): (u32, u32)) {
+    out.extend(MAGIC);
+    out.extend(w.to_be_bytes());
+    out.extend(h.to_be_bytes());
+}
+
+/// Encode an image to farbfeld, expanding it to 16 bits.
+pub fn encode<const N: usize>(image: Image<&[u8], N>) -> Vec<u8>
+where
+    [u8; 4]: PFrom<N>,
+{
+    let mut out = Vec::with_capacity(16 + image.buffer().len() / N * 8);
+    header(&mut out, (image.width(), image.height()));
+    for &px in image.flatten() {
+        for x in <[u8; 4] as PFrom<N>>::pfrom(px) {
+            out.extend([x, x]);
+        }
+    }
+    out
+}
+
+/// Encode a 16 bit image to farbfeld.
+pub fn encode16<const N: usize>(image: Image<&[u16], N>) -> Vec<u8>
+where
+    [u16; 4]: PFrom<N, u16>,
+{
+    let mut out = Vec::with_capacity(16 + image.buffer().len() / N * 8);
+    header(&mut out, (image.width(), image.height()));
+    for &px in image.flatten() {
+        for x in <[u16; 4] as PFrom<N, u16>>::pfrom(px) {
+            out.extend(x.to_be_bytes());
+        }
+    }
+    out
+}
+
+/// Decode a farbfeld image. Produces a [`DynImage::Rgba16`].
+pub fn decode(data: &[u8]) -> Result<DynImage<Box<[u8]>>, Error> {
+    let Some((header, data)) = data.split_first_chunk::<16>() else {
+        return Err(Error::Decode("farbfeld: missing header".into()));
+    };
+    if header[..8] != *MAGIC {
+        return Err(Error::Decode("farbfeld: bad magic".into()));
+    }
+    let w = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+    let h = u32::from_be_bytes([header[12], header[13], header[14], header[15]]);
+    let Some(expected) = (w as usize)
+        .checked_mul(h as usize)
+        .and_then(|x| x.checked_mul(8))
+    else {
+        return Err(Error::Decode("farbfeld: image too large".into()));
+    };
+    if expected == 0 || data.len() < expected {
+        return Err(Error::DimensionMismatch {
+            expected,
+            got: data.len(),
+        });
+    }
+    let buf = data[..expected]
+        .as_chunks::<2>()
+        .0
+        .iter()
+        .map(|&x| u16::from_be_bytes(x))
+        .collect();
+    Ok(DynImage::Rgba16(Image::build(w, h).buf(buf)))
+}
+
+impl ReadFarbfeld for DynImage<Box<[u8]>> {
+    /// Read a farbfeld image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        let mut data = vec![];
+        f.read_to_end(&mut data)?;
+        decode(&data)
+    }
+}
+
+impl<const N: usize> ReadFarbfeld for Image<Box<[u8]>, N>
+where
+    Self: From<DynImage<Box<[u8]>>>,
+{
+    /// Read a farbfeld image, converting it to this many channels, and 8 bits.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        DynImage::read(f).map(Into::into)
+    }
+}
+
+impl<const N: usize> ReadFarbfeld for Image<Box<[u16]>, N>
+where
+    Self: From<DynImage<Box<[u8]>>>,
+{
+    /// Read a farbfeld image, converting it to this many channels.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        DynImage::read(f).map(Into::into)
+    }
+}
+
+impl<T: AsRef<[u8]>, const N: usize> WriteFarbfeld for Image<T, N>
+where
+    [u8; 4]: PFrom<N>,
+{
+    /// Write this image as a farbfeld.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        f.write_all(&encode(self.as_ref()))?;
+        Ok(())
+    }
+}
+
+impl<T: AsRef<[u16]>, const N: usize> WriteFarbfeld<u16> for Image<T, N>
+where
+    [u16; 4]: PFrom<N, u16>,
+{
+    /// Write this 16 bit image as a farbfeld.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        f.write_all(&encode16(self.as_ref()))?;
+        Ok(())
+    }
+}
+
+impl<T: AsRef<[u8]> + crate::Buffer> WriteFarbfeld for DynImage<T> {
+    /// Write this image as a farbfeld.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        crate::r#dyn::e!(self, |i| WriteFarbfeld::write(i, f), |i| {
+            WriteFarbfeld::<u16>::write(i, f)
+        })
+    }
+}
+
+#[cfg(feature = "save")]
+#[test]
+fn roundtrip() {
+    let cat = Image::<_, 3>::open("tdata/small_cat.png");
+    let ff = encode(cat.as_ref());
+    assert_eq!(&ff[..8], MAGIC);
+    assert_eq!(ff.len(), 16 + cat.bytes().len() / 3 * 8);
+    let back = Image::<Box<[u8]>, 3>::read(&mut &ff[..]).unwrap();
+    assert_eq!(back.bytes(), cat.bytes());
+
+    let wide = Image::<Box<[u16]>, 4>::build(3, 1).buf((0..12).map(|x| x * 5000).collect());
+    let mut ff = vec![];
+    WriteFarbfeld::<u16>::write(&wide, &mut ff).unwrap();
+    assert_eq!(
+        Image::<Box<[u16]>, 4>::from(decode(&ff).unwrap()).buffer(),
+        wide.buffer()
+    );
+    assert!(matches!(
+        decode(&ff[..ff.len() - 1]),
+        Err(Error::DimensionMismatch { .. })
+    ));
+    ff[8..16].fill(0xff);
+    assert!(matches!(decode(&ff), Err(Error::Decode(_))));
+}
@@ -0,0 +1,399 @@
+//! picking a codec by magic number or file extension.
+use crate::{DynImage, Error};
+
+/// An image format fimg knows about. Whether it can be read or written depends on the enabled features.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Format {
+    /// PNG (and APNG), via the `save` feature.
+    Png,
+    /// JPEG, via the `jpeg` feature.
+    Jpeg,
+    /// GIF, via the `gif` feature.
+    Gif,
+    /// BMP, via the `bmp` feature.
+    Bmp,
+    /// TGA, via the `tga` feature.
+    Tga,
+    /// QOI, via the `qoi` feature.
+    Qoi,
+    /// Netpbm (PBM, PGM, PPM, PAM), via the `pnm` feature.
+    Pnm,
+    /// Radiance HDR, via the `hdr` feature.
+    Hdr,
+    /// OpenEXR, via the `exr` feature.
+    Exr,
+    /// farbfeld, via the `farbfeld` feature.
+    Farbfeld,
+    /// ICO and CUR (the largest image), via the `ico` feature.
+    Ico,
+    /// TIFF (the first image), via the `tiff` feature.
+    Tiff,
+    /// WebP (lossless, the first frame), via the `webp` feature.
+    Webp,
+    /// DDS (the largest image), via the `dds` feature. Written with block compression, which is lossy.
+    Dds,
+}
+
+impl Format {
+    /// Guess the format of some image data from its first bytes.
+    ///
+    /// TGA has no magic number, so it is only guessed when the header looks plausible.
+    #[must_use]
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        Some(match data {
+            [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', ..] => Self::Png,
+            [0xff, 0xd8, 0xff, ..] => Self::Jpeg,
+            [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Self::Gif,
+            [b'B', b'M', ..] => Self::Bmp,
+            [b'q', b'o', b'i', b'f', ..] => Self::Qoi,
+            [b'P', b'1'..=b'7', b' ' | b'\t' | b'\r' | b'\n', ..] => Self::Pnm,
+            [b'#', b'?', b'A'..=b'Z', ..] => Self::Hdr,
+            [0x76, 0x2f, 0x31, 0x01, ..] => Self::Exr,
+            [b'f', b'a', b'r', b'b', b'f', b'e', b'l', b'd', ..] => Self::Farbfeld,
+            [0, 0, 1 | 2, 0, n, m, ..] if (*n, *m) != (0, 0) => Self::Ico,
+            [b'I', b'I', 42, 0, ..] | [b'M', b'M', 0, 42, ..] => Self::Tiff,
+            [b'D', b'D', b'S', b' ', ..] => Self::Dds,
+            [b'R', b'I', b'F', b'F', _, _, _, _, webp @ ..] if webp.starts_with(b"WEBP") => {
+                Self::Webp
+            }
+            [_, map @ (0 | 1), kind @ (1..=3 | 9..=11), .., _]
+                if data.len() >= 18
+                    && (*map == 1) == matches!(*kind, 1 | 9)
+                    && matches!(data[16], 8 | 15 | 16 | 24 | 32) =>
+            {
+                Self::Tga
+            }
+            _ => return None,
+        })
+    }
+
+    /// The format for this file extension (case insensitive, without the dot).
+    #[must_use]
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        Some(match &*ext.to_ascii_lowercase() {
+            "png" | "apng" => Self::Png,
+            "jpg" | "jpeg" | "jpe" | "jfif" => Self::Jpeg,
+            "gif" => Self::Gif,
+            "bmp" | "dib" => Self::Bmp,
+            "tga" | "icb" | "vda" | "vst" => Self::Tga,
+            "qoi" => Self::Qoi,
+            "pbm" | "pgm" | "ppm" | "pnm" | "pam" => Self::Pnm,
+            "hdr" | "rgbe" => Self::Hdr,
+            "exr" => Self::Exr,
+            "ff" => Self::Farbfeld,
+            "ico" | "cur" => Self::Ico,
+            "tif" | "tiff" => Self::Tiff,
+            "webp" => Self::Webp,
+            "dds" => Self::Dds,
+            _ => return None,
+        })
+    }
+
+    /// The format for the extension of this path.
+    #[must_use]
+    pub fn from_path(path: impl AsRef<std::path::Path>) -> Option<Self> {
+        Self::from_extension(path.as_ref().extension()?.to_str()?)
+    }
+
+    /// The feature that enables this format.
+    #[must_use]
+    pub const fn feature(self) -> &'static str {
+        match self {
+            Self::Png => "save",
+            Self::Jpeg => "jpeg",
+            Self::Gif => "gif",
+            Self::Bmp => "bmp",
+            Self::Tga => "tga",
+            Self::Qoi => "qoi",
+            Self::Pnm => "pnm",
+            Self::Hdr => "hdr",
+            Self::Exr => "exr",
+            Self::Farbfeld => "farbfeld",
+            Self::Ico => "ico",
+            Self::Tiff => "tiff",
+            Self::Webp => "webp",
+            Self::Dds => "dds",
+        }
+    }
+
+    /// Whether this format was compiled in.
+    #[must_use]
+    pub const fn enabled(self) -> bool {
+        match self {
+            Self::Png => cfg!(feature = "save"),
+            Self::Jpeg => cfg!(feature = "jpeg"),
+            Self::Gif => cfg!(feature = "gif"),
+            Self::Bmp => cfg!(feature = "bmp"),
+            Self::Tga => cfg!(feature = "tga"),
+            Self::Qoi => cfg!(feature = "qoi"),
+            Self::Pnm => cfg!(feature = "pnm"),
+            Self::Hdr => cfg!(feature = "hdr"),
+            Self::Exr => cfg!(feature = "exr"),
+            Self::Farbfeld => cfg!(feature = "farbfeld"),
+            Self::Ico => cfg!(feature = "ico"),
+            Self::Tiff => cfg!(feature = "tiff"),
+            Self::Webp => cfg!(feature = "webp"),
+            Self::Dds => cfg!(feature = "dds"),
+        }
+    }
+}
+
+impl DynImage<Box<[u8]>> {
+    /// Read an image of any (enabled) [`Format`], sniffing the format from its contents.
+    pub fn load(mut f: impl std::io::Read) -> Result<Self, Error> {
+        let mut data = vec![];
+        f.read_to_end(&mut data)?;
+        Self::from_bytes(&data)
+    }
+
+    /// Decode an image of any (enabled) [`Format`], sniffing the format from its contents.
+    /// Animations become their first frame.
+    ///
+    /// Returns [`Error::UnknownFormat`] if the format is not recognized, and [`Error::DisabledFormat`] if it is not compiled in.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        Self::decode(data, Format::sniff(data).ok_or(Error::UnknownFormat)?)
+    }
+
+    /// Decode an image of this format.
+    /// Float images ([`Format::Hdr`], [`Format::Exr`]) are clamped to 0..=1, without any tonemapping.
+    /// 16 bit images (of [`Format::Png`], [`Format::Tiff`], [`Format::Pnm`], and [`Format::Farbfeld`]) stay 16 bit.
+    #[allow(unused_variables)] // without any codecs
+    pub fn decode(data: &[u8], format: Format) -> Result<Self, Error> {
+        match format {
+            #[cfg(feature = "save")]
+            Format::Png => Self::read16(&mut std::io::Cursor::new(data)),
+            #[cfg(feature = "jpeg")]
+            Format::Jpeg => crate::jpeg::decode(data),
+            #[cfg(feature = "gif")]
+            Format::Gif => {
+                let mut a = crate::gif::decode(data)?;
+                a.frames.truncate(1);
+                let Some(frame) = a.composite().pop() else {
+                    return Err(Error::Decode("gif: no frames".into()));
+                };
+                Ok(frame.into())
+            }
+            #[cfg(feature = "bmp")]
+            Format::Bmp => crate::bmp::decode(data),
+            #[cfg(feature = "tga")]
+            Format::Tga => crate::tga::decode(data),
+            #[cfg(feature = "qoi")]
+            Format::Qoi => crate::qoi::decode(data),
+            #[cfg(feature = "pnm")]
+            Format::Pnm => crate::pnm::decode(data),
+            #[cfg(feature = "hdr")]
+            Format::Hdr => Ok(clamped(crate::hdr::decode(data)?).into()),
+            #[cfg(feature = "exr")]
+            Format::Exr => Ok(clamped(crate::exr::decode(data)?).into()),
+            #[cfg(feature = "farbfeld")]
+            Format::Farbfeld => crate::farbfeld::decode(data),
+            #[cfg(feature = "ico")]
+            Format::Ico => crate::ico::ReadIco::read(&mut &data[..]),
+            #[cfg(feature = "tiff")]
+            Format::Tiff => crate::tiff::decode(data),
+            #[cfg(feature = "webp")]
+            Format::Webp => crate::webp::decode(data),
+            #[cfg(feature = "dds")]
+            Format::Dds => crate::dds::ReadDds::read(&mut &data[..]),
+            #[allow(unreachable_patterns)]
+            x => Err(Error::DisabledFormat(x)),
+        }
+    }
+}
+
+/// A float image, clamped to 0..=1, as bytes.
+#[cfg(any(feature = "hdr", feature = "exr"))]
+fn clamped<const N: usize>(i: crate::Image<Box<[f32]>, N>) -> crate::Image<Box<[u8]>, N> {
+    let (w, h) = (i.width(), i.height());
+    let mut buf = i.take_buffer();
+    buf.iter_mut()
+        .for_each(|x| *x = if x.is_nan() { 0.0 } else { x.clamp(0.0, 1.0) });
+    crate::Image::<_, N>::build(w, h).buf(&buf[..]).into()
+}
+
+impl<T: AsRef<[u8]> + crate::Buffer> DynImage<T> {
+    /// Encode this image in this format, with the default options, converting it to what the format supports.
+    ///
+    /// Gifs can only be written if the image has at most 256 colors (with fully transparent pixels as one color).
+    #[allow(unused_variables)] // without any codecs
+    pub fn encode(&self, f: &mut impl std::io::Write, format: Format) -> Result<(), Error> {
+        match format {
+            #[cfg(feature = "save")]
+            Format::Png => crate::WritePng::write(self, f),
+            #[cfg(feature = "jpeg")]
+            Format::Jpeg => crate::jpeg::WriteJpeg::write(self, f),
+            #[cfg(feature = "gif")]
+            Format::Gif => {
+                use std::collections::HashMap;
+                let rgba = self.rgba();
+                let mut index = HashMap::new();
+                let mut palette = vec![];
+                let mut transparent = None;
+                let mut indices = Vec::with_capacity(rgba.len());
+                for &[r, g, b, a] in rgba.chunked() {
+                    let px = if a == 0 { None } else { Some([r, g, b]) };
+                    let i = *index.entry(px).or_insert_with(|| {
+                        palette.push(px.unwrap_or_default());
+                        palette.len() - 1
+                    });
+                    if px.is_none() {
+                        transparent = Some(i as u8);
+                    }
+                    indices.push(i as u8);
+                }
+                if palette.len() > 256 {
+                    return Err(Error::Encode("gif: more than 256 colors".into()));
+                }
+                let (w, h) = (rgba.width(), rgba.height());
+                if w > u16::MAX as u32 || h > u16::MAX as u32 {
+                    return Err(Error::Encode("gif: image too large".into()));
+                }
+                let image = crate::indexed::IndexedImage::from_raw_parts(
+                    crate::Image::build(w, h).buf(indices.into()),
+                    palette.into(),
+                )
+                .map_err(|x| Error::Encode(x.into()))?;
+                let mut a = crate::gif::Animation::new(w as u16, h as u16);
+                a.repeat = None;
+                a.frames.push(crate::gif::Frame {
+                    transparent,
+                    ..crate::gif::Frame::new(image, 0)
+                });
+                crate::gif::WriteGif::write(&a, f)
+            }
+            #[cfg(feature = "bmp")]
+            Format::Bmp => crate::bmp::WriteBmp::write(self, f),
+            #[cfg(feature = "tga")]
+            Format::Tga => crate::tga::WriteTga::write(self, f),
+            #[cfg(feature = "qoi")]
+            Format::Qoi => crate::qoi::WriteQoi::write(self, f),
+            #[cfg(feature = "pnm")]
+            Format::Pnm => crate::pnm::WritePnm::write(self, f),
+            #[cfg(feature = "hdr")]
+            Format::Hdr => crate::hdr::WriteHdr::write(&self.rgb().to_f32::<u8>(), f),
+            #[cfg(feature = "exr")]
+            Format::Exr => match self {
+                Self::Y(_) | Self::Rgb(_) | Self::Y16(_) | Self::Rgb16(_) => {
+                    crate::exr::WriteExr::write(&self.rgb().to_f32::<u8>(), f)
+                }
+                _ => crate::exr::WriteExr::write(&self.rgba().to_f32::<u8>(), f),
+            },
+            #[cfg(feature = "farbfeld")]
+            Format::Farbfeld => crate::farbfeld::WriteFarbfeld::write(self, f),
+            #[cfg(feature = "ico")]
+            Format::Ico => crate::ico::WriteIco::write(self, f),
+            #[cfg(feature = "tiff")]
+            Format::Tiff => crate::tiff::WriteTiff::write(self, f),
+            #[cfg(feature = "webp")]
+            Format::Webp => crate::webp::WriteWebp::write(self, f),
+            #[cfg(feature = "dds")]
+            Format::Dds => crate::dds::WriteDds::write(self, f),
+            #[allow(unreachable_patterns)]
+            x => Err(Error::DisabledFormat(x)),
+        }
+    }
+
+    /// Save this image, picking the [`Format`] from the extension of the path, returning a [`Error`] if that fails.
+    ///
+    /// Returns [`Error::UnknownFormat`] if the extension is not recognized, and [`Error::DisabledFormat`] if it is not compiled in.
+    pub fn try_save_as(&self, f: impl AsRef<std::path::Path>) -> Result<(), Error> {
+        let format = Format::from_path(&f).ok_or(Error::UnknownFormat)?;
+        // encode first, so that a failure leaves no file behind
+        let mut out = vec![];
+        self.encode(&mut out, format)?;
+        std::fs::write(f, out)?;
+        Ok(())
+    }
+
+    #[track_caller]
+    /// Save this image, picking the [`Format`] from the extension of the path.
+    ///
+    /// # Panics
+    ///
+    /// if the file could not be written. See [`DynImage::try_save_as`] for a fallible version.
+    pub fn save_as(&self, f: impl AsRef<std::path::Path>) {
+        self.try_save_as(f).unwrap()
+    }
+}
+
+#[test]
+fn sniff() {
+    assert_eq!(Format::sniff(b"\x89PNG\r\n\x1a\n...."), Some(Format::Png));
+    assert_eq!(Format::sniff(b"P6\n1 1\n255\n..."), Some(Format::Pnm));
+    assert_eq!(Format::sniff(&[0, 0, 1, 0, 1, 0]), Some(Format::Ico));
+    assert_eq!(Format::sniff(b"DDS |\0\0\0"), Some(Format::Dds));
+    assert_eq!(Format::sniff(b"PK\x03\x04"), None);
+    assert_eq!(Format::sniff(b""), None);
+    assert_eq!(Format::from_path("a/b.JPG"), Some(Format::Jpeg));
+    assert_eq!(Format::from_path("a/b"), None);
+    assert!(matches!(
+        DynImage::from_bytes(b"hello"),
+        Err(Error::UnknownFormat)
+    ));
+}
+
+#[cfg(all(feature = "save", feature = "qoi", feature = "tga", feature = "bmp"))]
+#[test]
+fn roundtrip() {
+    let cat = DynImage::open("tdata/small_cat.png");
+    let dir = std::env::temp_dir();
+    let mut formats = vec![Format::Png, Format::Qoi, Format::Tga, Format::Bmp];
+    if cfg!(feature = "pnm") {
+        formats.push(Format::Pnm);
+    }
+    if cfg!(feature = "exr") {
+        formats.push(Format::Exr);
+    }
+    if cfg!(feature = "farbfeld") {
+        formats.push(Format::Farbfeld);
+    }
+    if cfg!(feature = "tiff") {
+        formats.push(Format::Tiff);
+    }
+    if cfg!(feature = "webp") {
+        formats.push(Format::Webp);
+    }
+    for format in formats {
+        let mut out = vec![];
+        cat.encode(&mut out, format).unwrap();
+        assert_eq!(Format::sniff(&out), Some(format));
+        let d = DynImage::from_bytes(&out).unwrap();
+        assert_eq!(d.rgb().bytes(), cat.rgb().bytes(), "{format:?}");
+    }
+    assert!(matches!(
+        cat.try_save_as(dir.join("cat.unknown")),
+        Err(Error::UnknownFormat)
+    ));
+    let path = dir.join("fimg_sniff_cat.qoi");
+    cat.save_as(&path);
+    let d = DynImage::load(std::fs::File::open(&path).unwrap()).unwrap();
+    assert_eq!(d.rgb().bytes(), cat.rgb().bytes());
+    // nothing is written if encoding fails
+    let path = dir.join("fimg_too_wide.tga");
+    _ = std::fs::remove_file(&path);
+    let wide = DynImage::Y(crate::Image::<Box<[u8]>, 1>::build(70_000, 1).fill([0]));
+    assert!(wide.try_save_as(&path).is_err());
+    assert!(!path.exists());
+}
+
+#[cfg(all(feature = "gif", feature = "save"))]
+#[test]
+fn gif() {
+    let px = [[255, 0, 0, 255], [0, 0, 0, 0], [0, 255, 0, 255]];
+    let i =
+        crate::Image::<Box<[u8]>, 4>::build(5, 3).buf((0..15).flat_map(|i| px[i % 3]).collect());
+    let mut out = vec![];
+    DynImage::Rgba(i.as_ref())
+        .encode(&mut out, Format::Gif)
+        .unwrap();
+    assert_eq!(
+        DynImage::from_bytes(&out).unwrap().rgba().bytes(),
+        i.bytes()
+    );
+    let cat = DynImage::Rgb(crate::Image::<Vec<u8>, 3>::open("tdata/small_cat.png"));
+    assert!(matches!(
+        cat.encode(&mut out, Format::Gif),
+        Err(Error::Encode(_))
+    ));
+}
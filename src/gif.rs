@@ -0,0 +1,619 @@
+//! [GIF](https://www.w3.org/Graphics/GIF/spec-gif89a.txt) reading and writing.
+//!
+//! Frames are [`IndexedImage`]s, with their palette.
+//! Writing uses a global palette for the frames that share the first frame's palette, and local palettes for the rest.
+use std::collections::HashMap;
+
+use crate::{Error, Image, indexed::IndexedImage};
+
+macro_rules! fail {
+    ($x:literal) => {
+        return Err(Error::Decode(concat!("gif: ", $x).into()))
+    };
+}
+
+/// An indexed image with a RGB palette, as gif frames are stored.
+pub type Indexed = IndexedImage<Box<[u8]>, Box<[[u8; 3]]>>;
+
+/// The most pixels of a decoded frame or canvas.
+const MAX_PIXELS: usize = 400_000_000;
+
+/// What happens to the area of a frame, once it has been shown.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Dispose {
+    /// Leave it as it is.
+    #[default]
+    None,
+    /// Clear it (to transparent).
+    Background,
+    /// Restore it to what it was before this frame was drawn.
+    Previous,
+}
+
+/// A frame of an [`Animation`]: an indexed image (which may be smaller than the canvas), placed on the canvas.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    /// The image, with its palette (the local palette, or the global palette if the frame has none).
+    pub image: Indexed,
+    /// The position of the image on the canvas.
+    pub offset: (u16, u16),
+    /// How long this frame is shown, in hundredths of a second.
+    pub delay: u16,
+    /// What happens to this frame, once it has been shown.
+    pub dispose: Dispose,
+    /// The index that is transparent, if any.
+    pub transparent: Option<u8>,
+}
+
+impl Frame {
+    /// Create a frame at the top left of the canvas, shown for `delay` hundredths of a second.
+    #[must_use]
+    pub const fn new(image: Indexed, delay: u16) -> Self {
+        Self {
+            image,
+            offset: (0, 0),
+            delay,
+            dispose: Dispose::None,
+            transparent: None,
+        }
+    }
+}
+
+/// A gif: a sequence of frames.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Animation {
+    /// Width of the canvas.
+    pub width: u16,
+    /// Height of the canvas.
+    pub height: u16,
+    /// How many times the animation repeats, 0 for forever. [`None`] plays it once.
+    pub repeat: Option<u16>,
+    /// The frames.
+    pub frames: Vec<Frame>,
+}
+
+impl Animation {
+    /// Create an empty animation, that repeats forever.
+    #[must_use]
+    pub const fn new(width: u16, height: u16) -> Self {
+        Self {
+            width,
+            height,
+            repeat: Some(0),
+            frames: vec![],
+        }
+    }
+
+    /// Draw each frame onto a (initially transparent) canvas, producing a full RGBA image for every frame.
+    /// Parts of frames outside the canvas are ignored, and frames entirely outside it draw nothing.
+    #[must_use]
+    pub fn composite(&self) -> Vec<Image<Box<[u8]>, 4>> {
+        let (w, h) = (self.width as usize, self.height as usize);
+        let mut canvas = vec![[0u8; 4]; w * h];
+        let mut out = Vec::with_capacity(self.frames.len());
+        for f in &self.frames {
+            let (x, y) = (f.offset.0 as usize, f.offset.1 as usize);
+            if x >= w || y >= h {
+                out.push(
+                    Image::build(self.width as u32, self.height as u32)
+                        .buf(canvas.as_flattened().into()),
+                );
+                continue;
+            }
+            let (i, p) = (f.image.buffer(), f.image.palette());
+            let fw = i.width() as usize;
+            let (cw, ch) = (
+                fw.min(w.saturating_sub(x)),
+                (i.height() as usize).min(h.saturating_sub(y)),
+            );
+            let previous = (f.dispose == Dispose::Previous).then(|| canvas.clone());
+            for (row, from) in i.buffer().chunks_exact(fw).take(ch).enumerate() {
+                let to = &mut canvas[(y + row) * w + x..][..cw];
+                for (to, &from) in to.iter_mut().zip(from) {
+                    if Some(from) != f.transparent {
+                        let [r, g, b] = p[from as usize];
+                        *to = [r, g, b, 255];
+                    }
+                }
+            }
+            out.push(
+                Image::build(self.width as u32, self.height as u32)
+                    .buf(canvas.as_flattened().into()),
+            );
+            for row in y..y + ch {
+                let area = row * w + x..row * w + x + cw;
+                match &previous {
+                    Some(p) => canvas[area.clone()].copy_from_slice(&p[area]),
+                    None if f.dispose == Dispose::Background => canvas[area].fill([0; 4]),
+                    None => {}
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Read a gif.
+pub trait ReadGif: Sized {
+    /// Read a gif.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error>;
+}
+
+/// Write a gif.
+pub trait WriteGif {
+    /// Write this as a gif.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error>;
+}
+
+impl ReadGif for Animation {
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        let mut data = vec![];
+        f.read_to_end(&mut data)?;
+        decode(&data)
+    }
+}
+
+impl WriteGif for Animation {
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        for x in &self.frames {
+            let i = x.image.buffer();
+            if i.width() > u16::MAX as u32 || i.height() > u16::MAX as u32 {
+                return Err(Error::Encode("gif: frame too large".into()));
+            }
+            if x.image.palette().len() > 256 {
+                return Err(Error::Encode(
+                    "gif: palette has more than 256 colors".into(),
+                ));
+            }
+        }
+        f.write_all(&encode(self))?;
+        Ok(())
+    }
+}
+
+impl<I: AsRef<[u8]>, P: AsRef<[[u8; 3]]>> WriteGif for IndexedImage<I, P> {
+    /// Write this image as a single frame gif.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        let (i, p) = (self.buffer(), self.palette().as_ref());
+        if i.width() > u16::MAX as u32 || i.height() > u16::MAX as u32 {
+            return Err(Error::Encode("gif: image too large".into()));
+        }
+        let image = IndexedImage::from_raw_parts(
+            Image::build(i.width(), i.height()).buf(i.buffer().as_ref().into()),
+            p.into(),
+        )
+        .map_err(|x| Error::Encode(x.into()))?;
+        let mut a = Animation::new(i.width() as u16, i.height() as u16);
+        a.repeat = None;
+        a.frames.push(Frame::new(image, 0));
+        a.write(f)
+    }
+}
+
+/// Reads the sub blocks of a gif.
+struct Blocks<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Blocks<'a> {
+    fn u8(&mut self) -> Result<u8, Error> {
+        let Some(&x) = self.data.get(self.pos) else {
+            fail!("unexpected end of data")
+        };
+        self.pos += 1;
+        Ok(x)
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes([self.u8()?, self.u8()?]))
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        let Some(x) = self.data.get(self.pos..self.pos + n) else {
+            fail!("unexpected end of data")
+        };
+        self.pos += n;
+        Ok(x)
+    }
+
+    fn palette(&mut self, size: u8) -> Result<Box<[[u8; 3]]>, Error> {
+        let n = 2 << (size & 7);
+        Ok(self.take(n * 3)?.as_chunks::<3>().0.into())
+    }
+
+    /// Concatenate a sequence of sub blocks.
+    fn sub_blocks(&mut self) -> Result<Vec<u8>, Error> {
+        let mut out = vec![];
+        loop {
+            let n = self.u8()? as usize;
+            if n == 0 {
+                return Ok(out);
+            }
+            out.extend(self.take(n)?);
+        }
+    }
+}
+
+/// Decompress LZW codes into `out`, stopping when it is full.
+/// Missing data leaves the rest of `out` as 0.
+fn lzw_decode(data: &[u8], min: u8, out: &mut [u8]) -> Result<(), Error> {
+    if !(2..=11).contains(&min) {
+        fail!("bad lzw code size")
+    }
+    let clear = 1u16 << min;
+    let end = clear + 1;
+    // each code is a previous code and a byte
+    let mut prefix = [0u16; 4096];
+    let mut suffix = [0u8; 4096];
+    let mut first = [0u8; 4096];
+    let mut len = [0u16; 4096];
+    for c in 0..clear {
+        (suffix[c as usize], first[c as usize], len[c as usize]) = (c as u8, c as u8, 1);
+    }
+    let (mut size, mut next, mut previous) = (min as u32 + 1, end + 1, None::<u16>);
+    let (mut acc, mut n, mut pos, mut at) = (0u32, 0u32, 0, 0);
+    while at < out.len() {
+        while n < size {
+            let Some(&b) = data.get(pos) else {
+                return Ok(());
+            };
+            acc |= (b as u32) << n;
+            n += 8;
+            pos += 1;
+        }
+        let code = (acc & ((1 << size) - 1)) as u16;
+        acc >>= size;
+        n -= size;
+        if code == clear {
+            (size, next, previous) = (min as u32 + 1, end + 1, None);
+            continue;
+        }
+        if code == end {
+            break;
+        }
+        let Some(p) = previous else {
+            if code > clear {
+                fail!("bad lzw code")
+            }
+            out[at] = code as u8;
+            at += 1;
+            previous = Some(code);
+            continue;
+        };
+        // the first byte of this code's string
+        let k = match code {
+            _ if code < next => first[code as usize],
+            _ if code == next => first[p as usize],
+            _ => fail!("bad lzw code"),
+        };
+        if next < 4096 {
+            let i = next as usize;
+            (prefix[i], suffix[i], first[i], len[i]) =
+                (p, k, first[p as usize], len[p as usize] + 1);
+            next += 1;
+            if next == 1 << size && size < 12 {
+                size += 1;
+            }
+        }
+        // write the string of `code` backwards
+        let l = len[code as usize] as usize;
+        let mut c = code;
+        for j in (0..l).rev() {
+            if let Some(o) = out.get_mut(at + j) {
+                *o = suffix[c as usize];
+            }
+            c = prefix[c as usize];
+        }
+        at += l;
+        previous = Some(code);
+    }
+    Ok(())
+}
+
+/// Compress `data` with LZW, with this minimum code size.
+fn lzw_encode(data: &[u8], min: u8) -> Vec<u8> {
+    let clear = 1u16 << min;
+    let mut out = vec![];
+    let (mut acc, mut n) = (0u32, 0u32);
+    let mut put = |code: u16, size: u32| {
+        acc |= (code as u32) << n;
+        n += size;
+        while n >= 8 {
+            out.push(acc as u8);
+            acc >>= 8;
+            n -= 8;
+        }
+    };
+    let mut dict = HashMap::<(u16, u8), u16>::new();
+    let (mut size, mut next) = (min as u32 + 1, clear + 2);
+    put(clear, size);
+    let mut data = data.iter();
+    if let Some(&x) = data.next() {
+        let mut prefix = x as u16;
+        for &x in data {
+            if let Some(&c) = dict.get(&(prefix, x)) {
+                prefix = c;
+                continue;
+            }
+            put(prefix, size);
+            dict.insert((prefix, x), next);
+            next += 1;
+            if next > 1 << size {
+                size += 1;
+            }
+            if next == 4096 {
+                put(clear, size);
+                dict.clear();
+                (size, next) = (min as u32 + 1, clear + 2);
+            }
+            prefix = x as u16;
+        }
+        put(prefix, size);
+        // the decoder adds a code after reading that one
+        if next == 1 << size && size < 12 {
+            size += 1;
+        }
+    }
+    put(clear + 1, size);
+    put(0, 7);
+    out
+}
+
+fn sub_blocks(out: &mut Vec<u8>, data: &[u8]) {
+    for x in data.chunks(255) {
+        out.push(x.len() as u8);
+        out.extend(x);
+    }
+    out.push(0);
+}
+
+/// Decode a gif.
+pub fn decode(data: &[u8]) -> Result<Animation, Error> {
+    if !data.starts_with(b"GIF87a") && !data.starts_with(b"GIF89a") {
+        fail!("bad magic")
+    }
+    let mut b = Blocks { data, pos: 6 };
+    let mut a = Animation::new(b.u16()?, b.u16()?);
+    if a.width == 0 || a.height == 0 {
+        fail!("zero width or height")
+    }
+    if a.width as usize * a.height as usize > MAX_PIXELS {
+        fail!("image too large")
+    }
+    a.repeat = None;
+    let flags = b.u8()?;
+    b.take(2)?; // background, aspect ratio
+    let global = if flags & 0x80 != 0 {
+        Some(b.palette(flags)?)
+    } else {
+        None
+    };
+    // the graphic control extension, which applies to the next image
+    let mut control = None;
+    loop {
+        let Ok(block) = b.u8() else {
+            if a.frames.is_empty() {
+                fail!("unexpected end of data")
+            }
+            // missing trailer
+            break;
+        };
+        match block {
+            // extension
+            0x21 => match b.u8()? {
+                0xf9 => {
+                    let x = b.sub_blocks()?;
+                    let Some(&[flags, d0, d1, t]) = x.get(..4) else {
+                        fail!("bad graphic control extension")
+                    };
+                    control = Some((flags, u16::from_le_bytes([d0, d1]), t));
+                }
+                0xff => {
+                    let x = b.sub_blocks()?;
+                    if let Some(&[1, l0, l1]) = x
+                        .strip_prefix(b"NETSCAPE2.0")
+                        .or(x.strip_prefix(b"ANIMEXTS1.0"))
+                        .and_then(|x| x.get(..3))
+                    {
+                        a.repeat = Some(u16::from_le_bytes([l0, l1]));
+                    }
+                }
+                _ => _ = b.sub_blocks()?,
+            },
+            // image
+            0x2c => {
+                let (x, y, w, h) = (b.u16()?, b.u16()?, b.u16()?, b.u16()?);
+                let flags = b.u8()?;
+                let palette = match flags & 0x80 {
+                    0 => global.clone(),
+                    _ => Some(b.palette(flags)?),
+                };
+                let Some(palette) = palette else {
+                    fail!("no palette")
+                };
+                if w == 0 || h == 0 {
+                    fail!("zero width or height")
+                }
+                if w as usize * h as usize > MAX_PIXELS {
+                    fail!("image too large")
+                }
+                let min = b.u8()?;
+                let mut i = vec![0; w as usize * h as usize];
+                lzw_decode(&b.sub_blocks()?, min, &mut i)?;
+                if flags & 0x40 != 0 {
+                    // interlaced
+                    let w = w as usize;
+                    let mut out = vec![0; i.len()];
+                    let rows = [(0, 8), (4, 8), (2, 4), (1, 2)]
+                        .into_iter()
+                        .flat_map(|(start, step)| (start..h as usize).step_by(step));
+                    for (from, to) in i.chunks_exact(w).zip(rows) {
+                        out[to * w..][..w].copy_from_slice(from);
+                    }
+                    i = out;
+                }
+                let image = IndexedImage::from_raw_parts(
+                    Image::build(w as u32, h as u32).try_buf(i.into())?,
+                    palette,
+                )
+                .map_err(|x| Error::Decode(x.into()))?;
+                let (flags, delay, t) = control.take().unwrap_or_default();
+                a.frames.push(Frame {
+                    image,
+                    offset: (x, y),
+                    delay,
+                    dispose: match (flags >> 2) & 7 {
+                        2 => Dispose::Background,
+                        3 => Dispose::Previous,
+                        _ => Dispose::None,
+                    },
+                    transparent: (flags & 1 != 0).then_some(t),
+                });
+            }
+            0x3b => break,
+            _ => fail!("bad block"),
+        }
+    }
+    Ok(a)
+}
+
+/// The size field of a palette: it has `2 << size` entries.
+const fn palette_size(n: usize) -> u8 {
+    (usize::BITS - (n.max(2) - 1).leading_zeros()) as u8 - 1
+}
+
+fn palette(out: &mut Vec<u8>, p: &[[u8; 3]]) {
+    let n = 2 << palette_size(p.len());
+    out.extend(p.as_flattened());
+    out.extend(std::iter::repeat_n(0, (n - p.len()) * 3));
+}
+
+/// Encode a gif. Frames whose palette is the same as the first frame's use the global palette.
+///
+/// # Panics
+///
+/// if a palette has more than 256 colors, or if a frame is larger than 65535x65535.
+pub fn encode(a: &Animation) -> Vec<u8> {
+    let mut out = b"GIF89a".to_vec();
+    out.extend(a.width.to_le_bytes());
+    out.extend(a.height.to_le_bytes());
+    let global = a.frames.first().map(|x| &**x.image.palette());
+    match global {
+        Some(p) => {
+            out.extend([0xf0 | palette_size(p.len()), 0, 0]);
+            palette(&mut out, p);
+        }
+        None => out.extend([0, 0, 0]),
+    }
+    if let Some(n) = a.repeat {
+        out.extend(b"\x21\xff\x0bNETSCAPE2.0\x03\x01");
+        out.extend(n.to_le_bytes());
+        out.push(0);
+    }
+    for f in &a.frames {
+        let dispose = match f.dispose {
+            Dispose::None => 1,
+            Dispose::Background => 2,
+            Dispose::Previous => 3,
+        };
+        out.extend([0x21, 0xf9, 4, dispose << 2 | f.transparent.is_some() as u8]);
+        out.extend(f.delay.to_le_bytes());
+        out.extend([f.transparent.unwrap_or(0), 0]);
+
+        let (i, p) = (f.image.buffer(), &**f.image.palette());
+        assert!(p.len() <= 256, "gif palettes have at most 256 colors");
+        let (w, h) = (u16::try_from(i.width()), u16::try_from(i.height()));
+        let (w, h) = (w.expect("frame too large"), h.expect("frame too large"));
+        out.push(0x2c);
+        for x in [f.offset.0, f.offset.1, w, h] {
+            out.extend(x.to_le_bytes());
+        }
+        let size = palette_size(p.len());
+        if Some(p) == global {
+            out.push(0);
+        } else {
+            out.push(0x80 | size);
+            palette(&mut out, p);
+        }
+        let min = (size + 1).max(2);
+        out.push(min);
+        sub_blocks(&mut out, &lzw_encode(i.buffer(), min));
+    }
+    out.push(0x3b);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn indexed(w: u32, h: u32, i: Vec<u8>, p: &[[u8; 3]]) -> Indexed {
+        IndexedImage::from_raw_parts(Image::build(w, h).buf(i.into()), p.into()).unwrap()
+    }
+
+    #[test]
+    fn lzw() {
+        // long enough to fill the table, a few times
+        let mut seed = 7u32;
+        for (min, n) in [(2, 50_000), (8, 100_000), (4, 1), (3, 0)] {
+            let data = (0..n)
+                .map(|i| {
+                    seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                    // some runs, some noise
+                    ((if i % 300 < 150 { i / 40 } else { seed >> 16 }) % (1 << min)) as u8
+                })
+                .collect::<Vec<_>>();
+            let mut out = vec![0; data.len()];
+            lzw_decode(&lzw_encode(&data, min), min, &mut out).unwrap();
+            assert_eq!(out, data);
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let p = [[0, 0, 0], [255, 0, 0], [0, 0, 255], [0, 255, 0]];
+        let mut a = Animation::new(3, 2);
+        a.frames
+            .push(Frame::new(indexed(3, 2, vec![0, 1, 2, 2, 1, 0], &p), 10));
+        a.frames.push(Frame {
+            image: indexed(2, 1, vec![0, 1], &[[1, 2, 3], [4, 5, 6]]),
+            offset: (1, 1),
+            delay: 20,
+            dispose: Dispose::Background,
+            transparent: Some(0),
+        });
+        let b = decode(&encode(&a)).unwrap();
+        assert_eq!(a, b);
+
+        let c = a.composite();
+        assert_eq!(c[0].bytes()[..8], [0, 0, 0, 255, 255, 0, 0, 255]);
+        // transparent, then a local palette
+        assert_eq!(c[1].get_pixel(1, 1), Some(&[255, 0, 0, 255]));
+        assert_eq!(c[1].get_pixel(2, 1), Some(&[4, 5, 6, 255]));
+    }
+
+    #[test]
+    fn outside() {
+        let p = [[0, 0, 0], [255, 0, 0]];
+        let mut a = Animation::new(3, 2);
+        a.frames.push(Frame::new(indexed(3, 2, vec![1; 6], &p), 10));
+        for offset in [(9, 0), (0, 9), (9, 9)] {
+            a.frames.push(Frame {
+                image: indexed(1, 2, vec![0; 2], &p),
+                offset,
+                delay: 10,
+                dispose: Dispose::Background,
+                transparent: None,
+            });
+        }
+        let c = a.composite();
+        assert!(c.iter().all(|x| x.bytes() == c[0].bytes()));
+
+        let mut data = encode(&a);
+        data[6..10].fill(0xff);
+        assert!(decode(&data).is_err());
+        data[6..10].fill(0);
+        assert!(decode(&data).is_err());
+    }
+}
@@ -0,0 +1,297 @@
+//! [Radiance](https://radiance-online.org) `.hdr` (RGBE) encoding and decoding, for float images.
+//!
+//! Every pixel is three 8 bit mantissas sharing a exponent, giving about 1% precision over a huge range.
+//! Negative (and NaN) values become 0.
+use crate::{Error, Image};
+
+/// Read a radiance hdr image.
+pub trait ReadHdr: Sized {
+    /// Read a hdr into an image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error>;
+}
+
+/// Write a radiance hdr image.
+pub trait WriteHdr {
+    /// Write this hdr image.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error>;
+}
+
+macro_rules! fail {
+    ($x:literal) => {
+        return Err(Error::Decode(concat!("hdr: ", $x).into()))
+    };
+}
+
+/// same limit as [`qoi`](crate::qoi).
+const MAX_PIXELS: usize = 400_000_000;
+/// Run length encoding is only allowed for scanlines this wide.
+const RLE: std::ops::RangeInclusive<u32> = 8..=0x7fff;
+
+fn rgbe(px: [f32; 3]) -> [u8; 4] {
+    let [r, g, b] = px.map(|x| x.max(0.0));
+    let v = r.max(g).max(b);
+    if v < 1e-32 {
+        return [0; 4];
+    }
+    if v >= 2f32.powi(127) {
+        return [255; 4];
+    }
+    // v = m * 2^e, 0.5 <= m < 1
+    let e = ((v.to_bits() >> 23) & 0xff) as i32 - 126;
+    let scale = 2f32.powi(8 - e);
+    let [r, g, b] = [r, g, b].map(|x| (x * scale).round().min(255.0) as u8);
+    [r, g, b, (e + 128) as u8]
+}
+
+fn rgb([r, g, b, e]: [u8; 4]) -> [f32; 3] {
+    if e == 0 {
+        return [0.0; 3];
+    }
+    let f = 2f32.powi(e as i32 - (128 + 8));
+    [r, g, b].map(|x| x as f32 * f)
+}
+
+/// Encode an image to hdr. The alpha of RGBA images is dropped.
+pub fn encode<const N: usize>(image: Image<&[f32], N>) -> Vec<u8>
+where
+    [(); ((N == 3) | (N == 4)) as usize - 1]:,
+{
+    let (w, h) = (image.width(), image.height());
+    let mut out = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {h} +X {w}\n").into_bytes();
+    let mut line = vec![[0; 4]; w as usize];
+    for row in image.buffer().chunks_exact(w as usize * N) {
+        for (to, px) in line.iter_mut().zip(row.chunks_exact(N)) {
+            *to = rgbe([px[0], px[1], px[2]]);
+        }
+        if !RLE.contains(&w) {
+            out.extend(line.as_flattened());
+            continue;
+        }
+        out.extend([2, 2, (w >> 8) as u8, w as u8]);
+        for c in 0..4 {
+            let x = line.iter().map(|px| px[c]).collect::<Vec<_>>();
+            let run = |i: usize| x[i..].iter().take(127).take_while(|&&v| v == x[i]).count();
+            let mut i = 0;
+            while i < x.len() {
+                let r = run(i);
+                if r >= 4 {
+                    out.extend([128 + r as u8, x[i]]);
+                    i += r;
+                    continue;
+                }
+                let mut j = i;
+                while j < x.len() && j - i < 128 && run(j) < 4 {
+                    j += 1;
+                }
+                out.push((j - i) as u8);
+                out.extend(&x[i..j]);
+                i = j;
+            }
+        }
+    }
+    out
+}
+
+fn scanline(data: &mut &[u8], line: &mut [[u8; 4]]) -> Result<(), Error> {
+    let w = line.len();
+    if let &[2, 2, hi, lo, ref rest @ ..] = *data
+        && RLE.contains(&(w as u32))
+        && ((hi as usize) << 8 | lo as usize) == w
+    {
+        *data = rest;
+        for c in 0..4 {
+            let mut x = 0;
+            while x < w {
+                let Some((&n, rest)) = data.split_first() else {
+                    fail!("unexpected end of data")
+                };
+                *data = rest;
+                if n > 128 {
+                    let n = (n - 128) as usize;
+                    let Some((&v, rest)) = data.split_first() else {
+                        fail!("unexpected end of data")
+                    };
+                    *data = rest;
+                    let Some(to) = line.get_mut(x..x + n) else {
+                        fail!("run past the end of the scanline")
+                    };
+                    to.iter_mut().for_each(|px| px[c] = v);
+                    x += n;
+                } else {
+                    let n = n as usize;
+                    if n == 0 || n > data.len() {
+                        fail!("bad literal run")
+                    }
+                    let Some(to) = line.get_mut(x..x + n) else {
+                        fail!("run past the end of the scanline")
+                    };
+                    for (px, &v) in to.iter_mut().zip(&data[..n]) {
+                        px[c] = v;
+                    }
+                    *data = &data[n..];
+                    x += n;
+                }
+            }
+        }
+        return Ok(());
+    }
+    // flat, or the old run length encoding
+    let (mut x, mut shift) = (0, 0);
+    while x < w {
+        let Some((&px, rest)) = data.split_first_chunk::<4>() else {
+            fail!("unexpected end of data")
+        };
+        *data = rest;
+        if let [1, 1, 1, n] = px {
+            if x == 0 || shift > 16 {
+                fail!("bad run")
+            }
+            let (n, prev) = ((n as usize) << shift, line[x - 1]);
+            let Some(to) = line.get_mut(x..x + n) else {
+                fail!("run past the end of the scanline")
+            };
+            to.fill(prev);
+            x += n;
+            shift += 8;
+        } else {
+            line[x] = px;
+            x += 1;
+            shift = 0;
+        }
+    }
+    Ok(())
+}
+
+/// Decode a hdr image.
+pub fn decode(mut data: &[u8]) -> Result<Image<Box<[f32]>, 3>, Error> {
+    let mut line = || {
+        let end = data.iter().position(|&x| x == b'\n')?;
+        let l = &data[..end];
+        data = &data[end + 1..];
+        std::str::from_utf8(l).ok()
+    };
+    if !line().is_some_and(|x| x.starts_with("#?")) {
+        fail!("bad magic")
+    }
+    loop {
+        match line() {
+            None => fail!("unexpected end of header"),
+            Some("") => break,
+            Some(l) => match l.strip_prefix("FORMAT=") {
+                Some("32-bit_rle_rgbe") | None => {}
+                Some("32-bit_rle_xyze") => return Err(Error::UnsupportedColor("hdr: xyze")),
+                Some(_) => fail!("unknown format"),
+            },
+        }
+    }
+    let Some(size) = line() else {
+        fail!("missing resolution")
+    };
+    let (flip, h, w) = match *size.split_ascii_whitespace().collect::<Vec<_>>() {
+        [y @ ("-Y" | "+Y"), h, "+X", w] => (y == "+Y", h, w),
+        [_, _, _, _] => fail!("unsupported orientation"),
+        _ => fail!("bad resolution"),
+    };
+    let (Ok(w), Ok(h)) = (w.parse::<u32>(), h.parse::<u32>()) else {
+        fail!("bad resolution")
+    };
+    // every scanline takes at least 4 bytes
+    if w == 0 || h == 0 || h as usize > data.len() / 4 {
+        fail!("bad resolution")
+    }
+    if w as usize * h as usize > MAX_PIXELS {
+        fail!("image too large")
+    }
+    let mut out = vec![0.0; w as usize * h as usize * 3];
+    let mut l = vec![[0; 4]; w as usize];
+    let stride = w as usize * 3;
+    for y in 0..h as usize {
+        scanline(&mut data, &mut l)?;
+        let y = if flip { h as usize - 1 - y } else { y };
+        for (to, &px) in out[y * stride..][..stride].chunks_exact_mut(3).zip(&l) {
+            to.copy_from_slice(&rgb(px));
+        }
+    }
+    Ok(Image::build(w, h).buf(out.into()))
+}
+
+impl ReadHdr for Image<Box<[f32]>, 3> {
+    /// Read a hdr image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        let mut data = vec![];
+        f.read_to_end(&mut data)?;
+        decode(&data)
+    }
+}
+
+impl ReadHdr for Image<Box<[f32]>, 4> {
+    /// Read a hdr image, with a alpha of 1.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        let i = Image::<Box<[f32]>, 3>::read(f)?;
+        let buf = i.chunked().flat_map(|&[r, g, b]| [r, g, b, 1.0]).collect();
+        Ok(Self::build(i.width(), i.height()).buf(buf))
+    }
+}
+
+impl<T: AsRef<[f32]>> WriteHdr for Image<T, 3> {
+    /// Write this image as a hdr.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        f.write_all(&encode(self.as_ref()))?;
+        Ok(())
+    }
+}
+
+impl<T: AsRef<[f32]>> WriteHdr for Image<T, 4> {
+    /// Write this image as a hdr, dropping the alpha.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        f.write_all(&encode(self.as_ref()))?;
+        Ok(())
+    }
+}
+
+#[test]
+fn roundtrip() {
+    let px = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.5, 0.25],
+        [1000.0, 20.0, 0.001],
+        [1e-20, 3e-20, 0.0],
+        [-1.0, f32::NAN, 0.75],
+    ];
+    for w in [1, 7, 8, 200] {
+        let buf: Box<[f32]> = (0..w * 3).flat_map(|i| px[i % px.len()]).collect();
+        let i = Image::<_, 3>::build(w as u32, 3).buf(buf);
+        let hdr = encode(i.as_ref());
+        assert_eq!(&hdr[..10], b"#?RADIANCE");
+        let back = decode(&hdr).unwrap();
+        assert_eq!((back.width(), back.height()), (w as u32, 3));
+        for (&[r, g, b], &[r2, g2, b2]) in i.chunked().zip(back.chunked()) {
+            let max = r.max(g).max(b);
+            for (a, b) in [(r, r2), (g, g2), (b, b2)] {
+                let a = if a.is_nan() { 0.0 } else { a.max(0.0) };
+                assert!((a - b).abs() <= max / 128.0, "{a} {b}");
+            }
+        }
+    }
+    // runs, in both encodings
+    let flat = Image::<_, 3>::build(300, 2).buf(vec![4.0; 300 * 6]);
+    let hdr = encode(flat.as_ref());
+    assert!(hdr.len() < 150);
+    assert_eq!(*decode(&hdr).unwrap().take_buffer(), *flat.buffer());
+    let mut old = b"#?RGBE\n\n+Y 2 +X 3\n".to_vec();
+    old.extend([128, 64, 32, 129, 1, 1, 1, 2]);
+    old.extend([0, 0, 0, 0, 128, 128, 128, 130, 1, 1, 1, 1]);
+    let i = decode(&old).unwrap();
+    assert_eq!(
+        &*i.take_buffer(),
+        [[0.0; 3], [2.0; 3], [2.0; 3]]
+            .into_iter()
+            .chain([[1.0, 0.5, 0.25]; 3])
+            .flatten()
+            .collect::<Vec<_>>()
+    );
+    assert!(matches!(
+        decode(&hdr[..hdr.len() - 2]),
+        Err(Error::Decode(_))
+    ));
+}
@@ -0,0 +1,429 @@
+//! [ICO and CUR](https://en.wikipedia.org/wiki/ICO_(file_format)) (icon and cursor) reading and writing.
+//!
+//! Icons hold several images, each at most 256×256, stored as a png or a bmp.
+//! Cursors are icons with a hotspot for every image.
+//!
+//! ```
+//! # use fimg::{Image, ico::{Entry, Encoding, WriteIco}};
+//! let big = Image::<_, 4>::build(64, 64).buf(vec![255; 64 * 64 * 4]);
+//! let small = Image::<_, 4>::build(16, 16).buf(vec![255; 16 * 16 * 4]);
+//! let mut ico = vec![];
+//! [Entry::new(big), Entry::new(small).encoding(Encoding::Bmp)].write(&mut ico)?;
+//! let images = fimg::ico::decode(&ico)?;
+//! assert_eq!(images[1].width(), 16);
+//! # Ok::<(), fimg::Error>(())
+//! ```
+use crate::{DynImage, Error, Image};
+
+/// Read a icon (or cursor).
+pub trait ReadIco: Sized {
+    /// Read a icon into an image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error>;
+}
+
+/// Write a icon (or cursor).
+pub trait WriteIco {
+    /// Write this icon.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error>;
+
+    /// Write this cursor, with the hotspots of its entries.
+    fn write_cursor(&self, f: &mut impl std::io::Write) -> Result<(), Error>;
+}
+
+macro_rules! fail {
+    ($x:literal) => {
+        return Err(Error::Decode(concat!("ico: ", $x).into()))
+    };
+}
+
+/// Whether a file is a icon or a cursor.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// A icon (`.ico`).
+    Icon,
+    /// A cursor (`.cur`), where every image has a hotspot.
+    Cursor,
+}
+
+/// How a image is stored in the icon.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// A png. Smallest, but not understood by windows xp and older.
+    #[default]
+    Png,
+    /// A 32 bit bmp, with a 1 bit mask for old programs.
+    Bmp,
+}
+
+/// A image of a icon, to be written.
+#[derive(Clone, Debug)]
+pub struct Entry<T> {
+    /// The image. At most 256×256.
+    pub image: Image<T, 4>,
+    /// How it is stored.
+    pub encoding: Encoding,
+    /// The hotspot (the pixel that clicks) of a cursor. Unused in icons.
+    pub hotspot: (u16, u16),
+}
+
+impl<T> Entry<T> {
+    /// Make a png entry, with a hotspot of (0, 0).
+    pub const fn new(image: Image<T, 4>) -> Self {
+        Self {
+            image,
+            encoding: Encoding::Png,
+            hotspot: (0, 0),
+        }
+    }
+
+    /// Store this image as a png or a bmp.
+    #[must_use]
+    pub fn encoding(self, encoding: Encoding) -> Self {
+        Self { encoding, ..self }
+    }
+
+    /// Set the hotspot of a cursor.
+    #[must_use]
+    pub fn hotspot(self, x: u16, y: u16) -> Self {
+        Self {
+            hotspot: (x, y),
+            ..self
+        }
+    }
+}
+
+fn u16le(d: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([d[at], d[at + 1]])
+}
+
+fn u32le(d: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([d[at], d[at + 1], d[at + 2], d[at + 3]])
+}
+
+/// Encode a 32 bit bmp, without the file header, and with its mask.
+fn bmp(image: Image<&[u8], 4>) -> Vec<u8> {
+    let (w, h) = (image.width() as usize, image.height() as usize);
+    let mask = w.div_ceil(32) * 4;
+    let mut out = Vec::with_capacity(40 + (w * 4 + mask) * h);
+    out.extend(40u32.to_le_bytes());
+    out.extend(image.width().to_le_bytes());
+    // the image and the mask
+    out.extend((image.height() * 2).to_le_bytes());
+    out.extend(1u16.to_le_bytes());
+    out.extend(32u16.to_le_bytes());
+    out.extend(0u32.to_le_bytes());
+    out.extend((((w * 4 + mask) * h) as u32).to_le_bytes());
+    out.extend([0; 16]);
+    for row in image.buffer().chunks_exact(w * 4).rev() {
+        for &[r, g, b, a] in row.as_chunks::<4>().0 {
+            out.extend([b, g, r, a]);
+        }
+    }
+    for row in image.buffer().chunks_exact(w * 4).rev() {
+        let at = out.len();
+        out.resize(at + mask, 0);
+        for (x, px) in row.chunks_exact(4).enumerate() {
+            if px[3] == 0 {
+                out[at + x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+    }
+    out
+}
+
+/// Encode images to a icon or cursor.
+///
+/// Returns a [`Error::Encode`] if there are no images, or a image is larger than 256×256.
+pub fn encode<T: AsRef<[u8]>>(entries: &[Entry<T>], kind: Kind) -> Result<Vec<u8>, Error> {
+    if entries.is_empty() || entries.len() > u16::MAX as usize {
+        return Err(Error::Encode("ico: bad number of images".into()));
+    }
+    let mut images = Vec::with_capacity(entries.len());
+    for e in entries {
+        let i = e.image.as_ref();
+        if i.width() > 256 || i.height() > 256 {
+            return Err(Error::Encode("ico: images are at most 256×256".into()));
+        }
+        images.push(match e.encoding {
+            Encoding::Png => {
+                let mut out = vec![];
+                crate::WritePng::write(&i, &mut out)?;
+                out
+            }
+            Encoding::Bmp => bmp(i),
+        });
+    }
+    let mut out = vec![];
+    out.extend(0u16.to_le_bytes());
+    out.extend((kind as u16 + 1).to_le_bytes());
+    out.extend((entries.len() as u16).to_le_bytes());
+    let mut offset = 6 + 16 * entries.len();
+    for (e, data) in entries.iter().zip(&images) {
+        // 256 is written as 0
+        out.extend([e.image.width() as u8, e.image.height() as u8, 0, 0]);
+        let (a, b) = match kind {
+            // color planes and bits per pixel
+            Kind::Icon => (1, 32),
+            Kind::Cursor => e.hotspot,
+        };
+        out.extend(a.to_le_bytes());
+        out.extend(b.to_le_bytes());
+        out.extend((data.len() as u32).to_le_bytes());
+        out.extend((offset as u32).to_le_bytes());
+        offset += data.len();
+    }
+    out.extend(images.concat());
+    Ok(out)
+}
+
+/// The kind and entries (of the data, and the hotspot) of a icon.
+#[allow(clippy::type_complexity)]
+fn directory(data: &[u8]) -> Result<(Kind, Vec<(&[u8], (u16, u16))>), Error> {
+    if data.len() < 6 || u16le(data, 0) != 0 {
+        fail!("bad magic")
+    }
+    let kind = match u16le(data, 2) {
+        1 => Kind::Icon,
+        2 => Kind::Cursor,
+        _ => fail!("bad magic"),
+    };
+    let n = u16le(data, 4) as usize;
+    if n == 0 {
+        fail!("no images")
+    }
+    let Some(dir) = data.get(6..6 + n * 16) else {
+        fail!("unexpected end of data")
+    };
+    let mut entries = Vec::with_capacity(n);
+    for e in dir.chunks_exact(16) {
+        let (size, offset) = (u32le(e, 8) as usize, u32le(e, 12) as usize);
+        let Some(image) = data.get(offset..offset.saturating_add(size)) else {
+            fail!("image past the end of the data")
+        };
+        entries.push((image, (u16le(e, 4), u16le(e, 6))));
+    }
+    Ok((kind, entries))
+}
+
+/// Decode a bmp entry: a bmp without the file header, twice as tall, followed by a mask.
+fn decode_bmp(d: &[u8]) -> Result<DynImage<Box<[u8]>>, Error> {
+    if d.len() < 40 || u32le(d, 0) < 40 {
+        fail!("unsupported bitmap header")
+    }
+    let header = u32le(d, 0) as usize;
+    let (w, h) = (u32le(d, 4) as i32, (u32le(d, 8) as i32 / 2).unsigned_abs());
+    let (bpp, compression, colors) = (u16le(d, 14) as usize, u32le(d, 16), u32le(d, 32) as usize);
+    let Ok(w) = u32::try_from(w) else {
+        fail!("negative width")
+    };
+    if w == 0 || h == 0 || w > 1 << 16 || h > 1 << 16 {
+        fail!("bad size")
+    }
+    let palette = match bpp {
+        1 | 2 | 4 | 8 if colors == 0 => 4 << bpp,
+        1 | 2 | 4 | 8 => 4 * colors.min(256),
+        _ => 0,
+    };
+    let masks = if compression == 3 && header == 40 {
+        12
+    } else {
+        0
+    };
+    let start = header + masks + palette;
+    let size = match compression {
+        1 | 2 => u32le(d, 20) as usize,
+        _ => (w as usize * bpp).div_ceil(32) * 4 * h as usize,
+    };
+    let mut image = if bpp == 32 && compression == 0 {
+        // the fourth byte is alpha, unlike in a bmp
+        let Some(px) = d.get(start..start + size) else {
+            fail!("unexpected end of data")
+        };
+        let mut out = Vec::with_capacity(size);
+        for row in px.chunks_exact(w as usize * 4).rev() {
+            for &[b, g, r, a] in row.as_chunks::<4>().0 {
+                out.extend([r, g, b, a]);
+            }
+        }
+        let i = Image::<_, 4>::build(w, h).buf(out.into_boxed_slice());
+        if i.chunked().all(|px| px[3] == 0) {
+            // a old icon, that only uses the mask
+            DynImage::Rgb(i.as_ref().into())
+        } else {
+            return Ok(i.into());
+        }
+    } else {
+        let mut bmp = Vec::with_capacity(14 + d.len());
+        bmp.extend(b"BM");
+        bmp.extend(((14 + d.len()) as u32).to_le_bytes());
+        bmp.extend([0; 4]);
+        bmp.extend(((14 + start) as u32).to_le_bytes());
+        bmp.extend(&d[..8]);
+        bmp.extend((h as i32).to_le_bytes());
+        bmp.extend(&d[12..]);
+        crate::bmp::decode(&bmp)?
+    };
+    let stride = (w as usize).div_ceil(32) * 4;
+    if let Some(mask) = d.get(start + size..start + size + stride * h as usize)
+        && mask.iter().any(|&x| x != 0)
+    {
+        let mut buf = image.rgba().take_buffer();
+        for (row, mask) in buf
+            .chunks_exact_mut(w as usize * 4)
+            .zip(mask.chunks_exact(stride).rev())
+        {
+            for (x, px) in row.chunks_exact_mut(4).enumerate() {
+                if mask[x / 8] & (0x80 >> (x % 8)) != 0 {
+                    px[3] = 0;
+                }
+            }
+        }
+        image = Image::<_, 4>::build(w, h).buf(buf).into();
+    }
+    Ok(image)
+}
+
+/// Decode every image of a icon (or cursor), in order.
+pub fn decode(data: &[u8]) -> Result<Vec<DynImage<Box<[u8]>>>, Error> {
+    let (_, entries) = directory(data)?;
+    entries
+        .into_iter()
+        .map(|(d, _)| match d {
+            [0x89, b'P', b'N', b'G', ..] => {
+                <DynImage<Box<[u8]>> as crate::ReadPng>::read(&mut std::io::Cursor::new(d))
+            }
+            _ => decode_bmp(d),
+        })
+        .collect()
+}
+
+/// The hotspots of every image of a cursor, in order. Returns a [`Error::Decode`] for icons.
+pub fn hotspots(data: &[u8]) -> Result<Vec<(u16, u16)>, Error> {
+    match directory(data)? {
+        (Kind::Cursor, entries) => Ok(entries.into_iter().map(|(_, h)| h).collect()),
+        (Kind::Icon, _) => fail!("not a cursor"),
+    }
+}
+
+impl ReadIco for Vec<DynImage<Box<[u8]>>> {
+    /// Read every image of a icon.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        let mut data = vec![];
+        f.read_to_end(&mut data)?;
+        decode(&data)
+    }
+}
+
+impl ReadIco for DynImage<Box<[u8]>> {
+    /// Read the largest image of a icon.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        let images = Vec::<Self>::read(f)?;
+        let mut largest = None::<Self>;
+        for i in images {
+            if largest
+                .as_ref()
+                .is_none_or(|l| i.width() * i.height() > l.width() * l.height())
+            {
+                largest = Some(i);
+            }
+        }
+        // directory() makes sure there is a image
+        Ok(largest.unwrap())
+    }
+}
+
+impl<const N: usize> ReadIco for Image<Box<[u8]>, N>
+where
+    Self: From<DynImage<Box<[u8]>>>,
+{
+    /// Read the largest image of a icon, converting it to this many channels.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        DynImage::read(f).map(Into::into)
+    }
+}
+
+impl<T: AsRef<[u8]>> WriteIco for [Entry<T>] {
+    /// Write these images as a icon.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        f.write_all(&encode(self, Kind::Icon)?)?;
+        Ok(())
+    }
+
+    /// Write these images as a cursor.
+    fn write_cursor(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        f.write_all(&encode(self, Kind::Cursor)?)?;
+        Ok(())
+    }
+}
+
+impl<T: AsRef<[u8]>, const N: usize> WriteIco for [Entry<T>; N] {
+    /// Write these images as a icon.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        self[..].write(f)
+    }
+
+    /// Write these images as a cursor.
+    fn write_cursor(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        self[..].write_cursor(f)
+    }
+}
+
+impl<T: AsRef<[u8]>> WriteIco for Image<T, 4> {
+    /// Write this image as a icon with one png.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        [Entry::new(self.as_ref())].write(f)
+    }
+
+    /// Write this image as a cursor with one png, with a hotspot at the top left.
+    fn write_cursor(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        [Entry::new(self.as_ref())].write_cursor(f)
+    }
+}
+
+impl<T: AsRef<[u8]> + crate::Buffer> WriteIco for DynImage<T> {
+    /// Write this image as a icon with one png. It is converted to RGBA.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        self.rgba().write(f)
+    }
+
+    /// Write this image as a cursor with one png. It is converted to RGBA.
+    fn write_cursor(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        self.rgba().write_cursor(f)
+    }
+}
+
+#[test]
+fn roundtrip() {
+    use crate::Cropper;
+    let cat = Image::<_, 4>::open("tdata/small_cat.png");
+    let cat = cat.crop(256, cat.height()).from(0, 0).own();
+    let small = Image::<_, 4>::build(3, 2).buf(vec![
+        255, 0, 0, 255, 0, 255, 0, 0, 0, 0, 255, 128, //
+        0, 0, 0, 0, 9, 9, 9, 255, 255, 255, 255, 255,
+    ]);
+    for encoding in [Encoding::Png, Encoding::Bmp] {
+        let entries = [
+            Entry::new(cat.as_ref()).encoding(encoding),
+            Entry::new(small.as_ref()).encoding(encoding).hotspot(1, 2),
+        ];
+        let mut ico = vec![];
+        entries.write(&mut ico).unwrap();
+        let images = decode(&ico).unwrap();
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].rgba().bytes(), cat.bytes());
+        assert_eq!(images[1].rgba().bytes(), small.bytes());
+        assert!(hotspots(&ico).is_err());
+        let mut cur = vec![];
+        entries.write_cursor(&mut cur).unwrap();
+        assert_eq!(hotspots(&cur).unwrap(), [(0, 0), (1, 2)]);
+        let largest = DynImage::read(&mut &cur[..]).unwrap();
+        assert_eq!(largest.width(), 256);
+    }
+    assert!(matches!(
+        Image::<_, 4>::build(257, 1)
+            .buf(&[0; 257 * 4][..])
+            .write(&mut vec![]),
+        Err(Error::Encode(_))
+    ));
+    assert!(decode(&[0, 0, 1, 0, 1, 0]).is_err());
+}
@@ -20,8 +20,11 @@ macro_rules! int {
 }
 int!(u8 u16 u32 u64 usize u128);
 
+/// A image with 8 bit indices into a RGBA palette, as most paletted formats store it.
+pub type Paletted = IndexedImage<Box<[u8]>, Box<[[u8; 4]]>>;
+
 /// An image with a palette.
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct IndexedImage<INDEX, PALETTE> {
     // likely Box<[u8]>, …
     // safety invariant: when INDEX<impl uint>, and PALETTE: Buffer, U must be < len(PALETTE)
@@ -88,6 +91,16 @@ impl<I, P> IndexedImage<I, P> {
         self.buffer.as_mut()
     }
 
+    /// The indices of this image.
+    pub const fn buffer(&self) -> &Image<I, 1> {
+        &self.buffer
+    }
+
+    /// The palette of this image.
+    pub const fn palette(&self) -> &P {
+        &self.palette
+    }
+
     /// Provides the buffer and palette of this image.
     pub fn into_raw_parts(self) -> (Image<I, 1>, P) {
         (self.buffer, self.palette)
@@ -118,3 +131,172 @@ impl<P, I: uint> IndexedImage<Box<[MaybeUninit<I>]>, P> {
         }
     }
 }
+
+/// Read an indexed png: its indices, palette, tRNS alpha and metadata.
+#[cfg(feature = "save")]
+#[allow(clippy::type_complexity)]
+fn read_png(
+    f: impl std::io::BufRead + std::io::Seek,
+) -> Result<
+    (
+        Image<Box<[u8]>, 1>,
+        Vec<[u8; 3]>,
+        Vec<u8>,
+        crate::meta::Metadata,
+    ),
+    crate::Error,
+> {
+    let mut reader = png::Decoder::new(f).read_info()?;
+    if reader.info().color_type != png::ColorType::Indexed {
+        return Err(crate::Error::UnsupportedColor("not indexed"));
+    }
+    let size = reader
+        .output_buffer_size()
+        .ok_or(crate::Error::Decode("image too large".into()))?;
+    let mut buf = vec![0; size];
+    let out = reader.next_frame(&mut buf)?;
+    // text may come after the image data; a bad trailing chunk only loses that
+    _ = reader.finish();
+    let info = reader.info();
+    let palette = info
+        .palette
+        .as_deref()
+        .unwrap_or_default()
+        .as_chunks::<3>()
+        .0
+        .to_vec();
+    let alpha = info.trns.as_deref().unwrap_or_default().to_vec();
+    let depth = out.bit_depth as usize;
+    let indices = buf[..out.buffer_size()]
+        .chunks_exact(out.line_size)
+        .flat_map(|row| {
+            (0..out.width as usize).map(move |x| {
+                let bit = x * depth;
+                row[bit / 8] >> (8 - depth - bit % 8) & ((1 << depth) - 1) as u8
+            })
+        })
+        .collect();
+    let image = Image::build(out.width, out.height).try_buf(indices)?;
+    Ok((image, palette, alpha, crate::png_meta(info)?))
+}
+
+#[cfg(feature = "save")]
+impl crate::ReadPng for IndexedImage<Box<[u8]>, Box<[[u8; 3]]>> {
+    /// Read a paletted png, ignoring its transparency.
+    fn read_meta<T: std::io::BufRead + std::io::Seek>(
+        f: &mut T,
+    ) -> Result<(Self, crate::meta::Metadata), crate::Error> {
+        let (image, palette, _, meta) = read_png(f)?;
+        Self::from_raw_parts(image, palette.into())
+            .map(|x| (x, meta))
+            .map_err(|x| crate::Error::Decode(x.into()))
+    }
+}
+
+#[cfg(feature = "save")]
+impl crate::ReadPng for IndexedImage<Box<[u8]>, Box<[[u8; 4]]>> {
+    /// Read a paletted png, with its transparency.
+    fn read_meta<T: std::io::BufRead + std::io::Seek>(
+        f: &mut T,
+    ) -> Result<(Self, crate::meta::Metadata), crate::Error> {
+        let (image, palette, alpha, meta) = read_png(f)?;
+        let palette = palette
+            .iter()
+            .enumerate()
+            .map(|(i, &[r, g, b])| [r, g, b, alpha.get(i).copied().unwrap_or(255)])
+            .collect();
+        Self::from_raw_parts(image, palette)
+            .map(|x| (x, meta))
+            .map_err(|x| crate::Error::Decode(x.into()))
+    }
+}
+
+/// Write a paletted png, with the smallest index bit depth that fits the palette.
+#[cfg(feature = "save")]
+fn write_png<const N: usize>(
+    f: &mut impl std::io::Write,
+    buffer: Image<&[u8], 1>,
+    palette: &[[u8; N]],
+    options: &crate::PngOptions,
+) -> Result<(), crate::Error> {
+    if palette.len() > 256 {
+        return Err(crate::Error::Encode("png: palette too large".into()));
+    }
+    // only the entries up to the last transparent one need an alpha
+    let alpha = match N {
+        4 => {
+            let end = palette
+                .iter()
+                .rposition(|x| x[3] != 255)
+                .map_or(0, |x| x + 1);
+            palette[..end].iter().map(|x| x[3]).collect()
+        }
+        _ => vec![],
+    };
+    crate::png_options::write_indexed(
+        f,
+        (buffer.width(), buffer.height()),
+        buffer.buffer(),
+        (
+            palette.iter().flat_map(|x| &x[..3]).copied().collect(),
+            alpha,
+        ),
+        options,
+    )
+}
+
+macro_rules! png_writer {
+    ($($p:ty),+) => {
+        $(
+            #[cfg(feature = "save")]
+            impl<I: AsRef<[u8]>> crate::WritePng for IndexedImage<I, $p> {
+                /// Write this image as a paletted png, with the smallest index bit depth that fits the palette, which must have at most 256 entries.
+                /// [`reduce`](crate::PngOptions::reduce) is ignored.
+                fn write_with(
+                    &self,
+                    f: &mut impl std::io::Write,
+                    options: &crate::PngOptions,
+                ) -> Result<(), crate::Error> {
+                    write_png(f, self.buffer.as_ref(), self.palette.as_ref(), options)
+                }
+            }
+        )+
+    };
+}
+png_writer!(Box<[[u8; 3]]>, Vec<[u8; 3]>, &[[u8; 3]]);
+png_writer!(Box<[[u8; 4]]>, Vec<[u8; 4]>, &[[u8; 4]]);
+
+#[cfg(feature = "save")]
+#[test]
+fn png() {
+    use crate::{ReadPng, WritePng};
+    for n in [2, 3, 16, 17, 256] {
+        let palette = (0..n)
+            .map(|i| {
+                [
+                    i as u8,
+                    255 - i as u8,
+                    7,
+                    if i % 3 == 0 { 255 } else { i as u8 },
+                ]
+            })
+            .collect::<Box<[_]>>();
+        let i = Paletted::from_raw_parts(
+            Image::build(13, 7).buf((0..91).map(|x| (x * 7 % n) as u8).collect()),
+            palette,
+        )
+        .unwrap();
+        for interlace in [false, true] {
+            let mut out = vec![];
+            i.write_with(&mut out, &crate::PngOptions::new().interlace(interlace))
+                .unwrap();
+            let r = Paletted::read(&mut std::io::Cursor::new(&out)).unwrap();
+            assert_eq!(r, i);
+            let rgb = IndexedImage::<_, Box<[[u8; 3]]>>::read(&mut std::io::Cursor::new(&out));
+            assert_eq!(rgb.unwrap().palette()[1], [1, 254, 7]);
+            // and it is still a png everyone can read
+            let d = crate::DynImage::read(&mut std::io::Cursor::new(&out)).unwrap();
+            assert_eq!(d.rgba().bytes(), i.to::<u8, u8, 4>().bytes());
+        }
+    }
+}
@@ -0,0 +1,762 @@
+use super::{ZIGZAG, dct_matrix};
+use crate::{DynImage, Error, Image};
+
+macro_rules! fail {
+    ($x:literal) => {
+        return Err(Error::Decode(concat!("jpeg: ", $x).into()))
+    };
+}
+
+/// The most pixels of a decoded image.
+const MAX_PIXELS: usize = 400_000_000;
+
+fn u16be(d: &[u8], at: usize) -> usize {
+    u16::from_be_bytes([d[at], d[at + 1]]) as usize
+}
+
+#[derive(Clone)]
+struct Huffman {
+    /// `(length, value)` for codes of up to [`Huffman::FAST`] bits, indexed by the next bits. length 0 if the code is longer.
+    fast: Box<[(u8, u8); 1 << Self::FAST]>,
+    /// largest code of each length, -1 if there are none
+    max: [i32; 17],
+    /// `values[offset[l] + code]` is the value of a code of length `l`
+    offset: [i32; 17],
+    values: [u8; 256],
+}
+
+impl Huffman {
+    const FAST: usize = 9;
+
+    fn new(counts: &[u8; 16], symbols: &[u8]) -> Result<Self, Error> {
+        let mut me = Self {
+            fast: Box::new([(0, 0); 1 << Self::FAST]),
+            max: [-1; 17],
+            offset: [0; 17],
+            values: [0; 256],
+        };
+        me.values[..symbols.len()].copy_from_slice(symbols);
+        let (mut code, mut k) = (0, 0);
+        for len in 1..=16 {
+            let n = counts[len - 1] as i32;
+            me.offset[len] = k - code;
+            for _ in 0..n {
+                if code >= 1 << len {
+                    fail!("bad huffman table")
+                }
+                if len <= Self::FAST {
+                    let shift = Self::FAST - len;
+                    for x in 0..1 << shift {
+                        me.fast[(code as usize) << shift | x] = (len as u8, symbols[k as usize]);
+                    }
+                }
+                code += 1;
+                k += 1;
+            }
+            me.max[len] = code - 1;
+            code <<= 1;
+        }
+        Ok(me)
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u8, Error> {
+        let (len, value) = self.fast[bits.peek(Self::FAST as u32) as usize];
+        if len != 0 {
+            bits.consume(len as u32);
+            return Ok(value);
+        }
+        let mut code = bits.peek(16) as i32;
+        for len in 1..=16 {
+            let c = code >> (16 - len);
+            if c <= self.max[len] {
+                bits.consume(len as u32);
+                code = self.offset[len] + c;
+                return Ok(self.values[code as usize]);
+            }
+        }
+        fail!("bad huffman code")
+    }
+}
+
+/// Reads entropy coded data. Feeds zeroes on reaching a marker.
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buf: u64,
+    n: u32,
+}
+
+impl Bits<'_> {
+    fn fill(&mut self) {
+        while self.n <= 56 {
+            let byte = match self.data.get(self.pos..self.pos + 2) {
+                Some(&[0xff, 0x00]) => {
+                    self.pos += 2;
+                    0xff
+                }
+                Some(&[0xff, _]) => 0,
+                _ => match self.data.get(self.pos) {
+                    Some(&x) if x != 0xff => {
+                        self.pos += 1;
+                        x
+                    }
+                    _ => 0,
+                },
+            };
+            self.buf |= (byte as u64) << (56 - self.n);
+            self.n += 8;
+        }
+    }
+
+    fn peek(&mut self, n: u32) -> u32 {
+        self.fill();
+        (self.buf >> (64 - n)) as u32
+    }
+
+    const fn consume(&mut self, n: u32) {
+        self.buf <<= n;
+        self.n -= n;
+    }
+
+    fn bits(&mut self, n: u32) -> u32 {
+        if n == 0 {
+            return 0;
+        }
+        let x = self.peek(n);
+        self.consume(n);
+        x
+    }
+
+    fn bit(&mut self) -> bool {
+        self.bits(1) == 1
+    }
+
+    /// read a `s` bit value, and sign extend it
+    fn extend(&mut self, s: u8) -> i32 {
+        let s = (s & 15) as u32;
+        let v = self.bits(s) as i32;
+        if s != 0 && v < 1 << (s - 1) {
+            v - (1 << s) + 1
+        } else {
+            v
+        }
+    }
+
+    /// skip the restart marker
+    fn restart(&mut self) {
+        (self.buf, self.n) = (0, 0);
+        while let Some(&[0xff, m]) = self.data.get(self.pos..self.pos + 2) {
+            self.pos += if m == 0xff { 1 } else { 2 };
+            if (0xd0..=0xd7).contains(&m) {
+                break;
+            }
+        }
+    }
+}
+
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+    quant: usize,
+    /// blocks per line, padded to whole MCUs
+    bw: usize,
+    bh: usize,
+    /// in zigzag order
+    coefficients: Vec<[i16; 64]>,
+    dc_prediction: i32,
+}
+
+#[derive(Default)]
+struct Decoder {
+    width: usize,
+    height: usize,
+    progressive: bool,
+    components: Vec<Component>,
+    /// zigzag order
+    quant: [Option<[u16; 64]>; 4],
+    dc: [Option<Huffman>; 4],
+    ac: [Option<Huffman>; 4],
+    restart_interval: usize,
+    eobrun: u32,
+    /// the adobe transform flag
+    adobe: Option<u8>,
+    scans: usize,
+}
+
+impl Decoder {
+    fn hmax(&self) -> usize {
+        self.components.iter().map(|c| c.h).max().unwrap_or(1)
+    }
+
+    fn vmax(&self) -> usize {
+        self.components.iter().map(|c| c.v).max().unwrap_or(1)
+    }
+
+    fn frame(&mut self, seg: &[u8], progressive: bool) -> Result<(), Error> {
+        if !self.components.is_empty() {
+            fail!("multiple frames")
+        }
+        if seg.len() < 6 {
+            fail!("unexpected end of data")
+        }
+        if seg[0] != 8 {
+            fail!("only 8 bit jpegs are supported")
+        }
+        self.progressive = progressive;
+        (self.height, self.width) = (u16be(seg, 1), u16be(seg, 3));
+        if self.width == 0 || self.height == 0 {
+            fail!("zero sized image")
+        }
+        if self.width * self.height > MAX_PIXELS {
+            fail!("image too large")
+        }
+        let n = seg[5] as usize;
+        if !matches!(n, 1 | 3) {
+            return Err(Error::UnsupportedColor(
+                "jpeg with neither 1 or 3 components",
+            ));
+        }
+        let Some(c) = seg.get(6..6 + n * 3) else {
+            fail!("unexpected end of data")
+        };
+        for c in c.chunks_exact(3) {
+            let (h, v) = ((c[1] >> 4) as usize, (c[1] & 15) as usize);
+            if !(1..=4).contains(&h) || !(1..=4).contains(&v) || c[2] > 3 {
+                fail!("bad component")
+            }
+            self.components.push(Component {
+                id: c[0],
+                h,
+                v,
+                quant: c[2] as usize,
+                bw: 0,
+                bh: 0,
+                coefficients: vec![],
+                dc_prediction: 0,
+            });
+        }
+        let (hmax, vmax) = (self.hmax(), self.vmax());
+        let mcux = self.width.div_ceil(8 * hmax);
+        let mcuy = self.height.div_ceil(8 * vmax);
+        for c in &mut self.components {
+            (c.bw, c.bh) = (mcux * c.h, mcuy * c.v);
+            let n = c.bw * c.bh;
+            if c.coefficients.try_reserve_exact(n).is_err() {
+                fail!("image too large")
+            }
+            c.coefficients.resize(n, [0; 64]);
+        }
+        Ok(())
+    }
+
+    fn huffman(&mut self, mut seg: &[u8]) -> Result<(), Error> {
+        while let Some((&[tc, ref counts @ ..], rest)) = seg.split_first_chunk::<17>() {
+            let n = counts.iter().map(|&x| x as usize).sum::<usize>();
+            let (Some(symbols), true) = (rest.get(..n), n <= 256) else {
+                fail!("bad huffman table")
+            };
+            let table = Some(Huffman::new(counts, symbols)?);
+            match tc {
+                0x00..=0x03 => self.dc[tc as usize] = table,
+                0x10..=0x13 => self.ac[(tc & 3) as usize] = table,
+                _ => fail!("bad huffman table"),
+            }
+            seg = &rest[n..];
+        }
+        Ok(())
+    }
+
+    fn quant(&mut self, mut seg: &[u8]) -> Result<(), Error> {
+        while let Some((&pt, rest)) = seg.split_first() {
+            let (precision, t) = (pt >> 4, (pt & 15) as usize);
+            let size = if precision == 0 { 64 } else { 128 };
+            let (Some(q), true) = (rest.get(..size), t < 4) else {
+                fail!("bad quantization table")
+            };
+            self.quant[t] = Some(std::array::from_fn(|i| match precision {
+                0 => q[i] as u16,
+                _ => u16::from_be_bytes([q[i * 2], q[i * 2 + 1]]),
+            }));
+            seg = &rest[size..];
+        }
+        Ok(())
+    }
+
+    /// Decode a scan. Returns the position after the entropy coded data.
+    fn scan(&mut self, seg: &[u8], data: &[u8], pos: usize) -> Result<usize, Error> {
+        if self.components.is_empty() {
+            fail!("scan before frame")
+        }
+        let Some(&n) = seg.first() else {
+            fail!("unexpected end of data")
+        };
+        let n = n as usize;
+        let Some(&[ss, se, a]) = seg.get(1 + n * 2..4 + n * 2) else {
+            fail!("unexpected end of data")
+        };
+        let (ss, se, ah, al) = (ss as usize, se as usize, a >> 4, a & 15);
+        if ss > se || se > 63 || (self.progressive && ss == 0 && se != 0) || al > 13 {
+            fail!("bad scan")
+        }
+        let mut scan = Vec::with_capacity(n);
+        for c in seg[1..1 + n * 2].chunks_exact(2) {
+            let Some(i) = self.components.iter().position(|x| x.id == c[0]) else {
+                fail!("scan of unknown component")
+            };
+            let (dc, ac) = ((c[1] >> 4) as usize, (c[1] & 15) as usize);
+            let need_dc = !self.progressive || (ss == 0 && ah == 0);
+            let need_ac = !self.progressive || ss != 0;
+            if dc > 3 || ac > 3 {
+                fail!("bad huffman table")
+            }
+            // tables that this kind of scan does not use may be absent
+            let table = |t: &Option<Huffman>, needed: bool| match t {
+                Some(t) => Ok(t.clone()),
+                None if !needed => Huffman::new(&[0; 16], &[]),
+                None => fail!("missing huffman table"),
+            };
+            let (dc, ac) = (table(&self.dc[dc], need_dc)?, table(&self.ac[ac], need_ac)?);
+            scan.push((i, dc, ac));
+        }
+        if scan.is_empty() || (self.progressive && ss != 0 && scan.len() != 1) {
+            fail!("bad scan")
+        }
+        for c in &mut self.components {
+            c.dc_prediction = 0;
+        }
+        self.eobrun = 0;
+
+        let mut bits = Bits {
+            data,
+            pos,
+            buf: 0,
+            n: 0,
+        };
+        let (hmax, vmax) = (self.hmax(), self.vmax());
+        let kind = match (self.progressive, ss, ah) {
+            (false, ..) => Kind::Baseline,
+            (true, 0, 0) => Kind::DcFirst,
+            (true, 0, _) => Kind::DcRefine,
+            (true, _, 0) => Kind::AcFirst,
+            (true, ..) => Kind::AcRefine,
+        };
+        let (ss, se, al) = (ss, se, al as u32);
+        let block = |d: &mut Self, bits: &mut Bits, s: usize, b: usize| -> Result<(), Error> {
+            let (c, dc, ac) = &scan[s];
+            let c = &mut d.components[*c];
+            let coef = &mut c.coefficients[b];
+            match kind {
+                Kind::Baseline => baseline(bits, dc, ac, &mut c.dc_prediction, coef),
+                Kind::DcFirst => {
+                    let t = dc.decode(bits)?;
+                    c.dc_prediction = c.dc_prediction.wrapping_add(bits.extend(t));
+                    coef[0] = (c.dc_prediction << al) as i16;
+                    Ok(())
+                }
+                Kind::DcRefine => {
+                    if bits.bit() {
+                        coef[0] |= 1 << al;
+                    }
+                    Ok(())
+                }
+                Kind::AcFirst => ac_first(bits, ac, coef, (ss, se, al), &mut d.eobrun),
+                Kind::AcRefine => ac_refine(bits, ac, coef, (ss, se, al), &mut d.eobrun),
+            }
+        };
+
+        let mut mcu = 0;
+        let mut restart = |d: &mut Self, bits: &mut Bits| {
+            if d.restart_interval != 0 && mcu != 0 && mcu % d.restart_interval == 0 {
+                bits.restart();
+                for c in &mut d.components {
+                    c.dc_prediction = 0;
+                }
+                d.eobrun = 0;
+            }
+            mcu += 1;
+        };
+        if scan.len() == 1 {
+            // non interleaved: only the blocks covering the component
+            let c = &self.components[scan[0].0];
+            let (w, h) = (
+                (self.width * c.h).div_ceil(hmax).div_ceil(8),
+                (self.height * c.v).div_ceil(vmax).div_ceil(8),
+            );
+            let bw = c.bw;
+            for y in 0..h {
+                for x in 0..w {
+                    restart(self, &mut bits);
+                    block(self, &mut bits, 0, y * bw + x)?;
+                }
+            }
+        } else {
+            let mcux = self.width.div_ceil(8 * hmax);
+            let mcuy = self.height.div_ceil(8 * vmax);
+            for my in 0..mcuy {
+                for mx in 0..mcux {
+                    restart(self, &mut bits);
+                    for (s, &(c, ..)) in scan.iter().enumerate() {
+                        let c = &self.components[c];
+                        let (h, v, bw) = (c.h, c.v, c.bw);
+                        for y in 0..v {
+                            for x in 0..h {
+                                block(self, &mut bits, s, (my * v + y) * bw + mx * h + x)?;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        self.scans += 1;
+
+        // find the next marker
+        let mut pos = bits.pos;
+        while let Some(&[a, b]) = data.get(pos..pos + 2) {
+            if a == 0xff && b != 0 && !(0xd0..=0xd7).contains(&b) {
+                break;
+            }
+            pos += 1;
+        }
+        Ok(pos)
+    }
+
+    /// Dequantize, idct, upsample, and color convert.
+    fn finish(self) -> Result<DynImage<Box<[u8]>>, Error> {
+        if self.scans == 0 {
+            fail!("no image data")
+        }
+        let m = dct_matrix();
+        let (hmax, vmax) = (self.hmax(), self.vmax());
+        let (w, h) = (self.width, self.height);
+        let mut planes = Vec::with_capacity(self.components.len());
+        for c in &self.components {
+            let Some(q) = self.quant[c.quant] else {
+                fail!("missing quantization table")
+            };
+            let stride = c.bw * 8;
+            let mut plane = vec![0u8; stride * c.bh * 8];
+            for (i, coef) in c.coefficients.iter().enumerate() {
+                let (bx, by) = (i % c.bw, i / c.bw);
+                let out = &mut plane[by * 8 * stride + bx * 8..];
+                idct(&m, coef, &q, out, stride);
+            }
+            let (cw, ch) = ((w * c.h).div_ceil(hmax), (h * c.v).div_ceil(vmax));
+            planes.push(upsample(
+                &plane,
+                stride,
+                (cw, ch),
+                (w, h),
+                (c.h, hmax),
+                (c.v, vmax),
+            ));
+        }
+        if planes.len() == 1 {
+            let y = planes.pop().unwrap();
+            return Ok(Image::<_, 1>::build(w as u32, h as u32)
+                .try_buf(y.into())?
+                .into());
+        }
+        let ids = self.components.iter().map(|x| x.id).collect::<Vec<_>>();
+        let rgb = self.adobe == Some(0) || ids == b"RGB";
+        let out = (0..w * h)
+            .flat_map(|i| {
+                let [a, b, c] = [planes[0][i], planes[1][i], planes[2][i]];
+                if rgb { [a, b, c] } else { ycbcr(a, b, c) }
+            })
+            .collect();
+        Ok(Image::<_, 3>::build(w as u32, h as u32)
+            .try_buf(out)?
+            .into())
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Kind {
+    Baseline,
+    DcFirst,
+    DcRefine,
+    AcFirst,
+    AcRefine,
+}
+
+fn baseline(
+    bits: &mut Bits,
+    dc: &Huffman,
+    ac: &Huffman,
+    prediction: &mut i32,
+    coef: &mut [i16; 64],
+) -> Result<(), Error> {
+    let t = dc.decode(bits)?;
+    *prediction = prediction.wrapping_add(bits.extend(t));
+    coef[0] = *prediction as i16;
+    let mut k = 1;
+    while k < 64 {
+        let rs = ac.decode(bits)?;
+        let (r, s) = ((rs >> 4) as usize, rs & 15);
+        if s == 0 {
+            if r != 15 {
+                break;
+            }
+            k += 16;
+            continue;
+        }
+        k += r;
+        if k > 63 {
+            fail!("coefficient out of range")
+        }
+        coef[k] = bits.extend(s) as i16;
+        k += 1;
+    }
+    Ok(())
+}
+
+fn ac_first(
+    bits: &mut Bits,
+    ac: &Huffman,
+    coef: &mut [i16; 64],
+    (ss, se, al): (usize, usize, u32),
+    eobrun: &mut u32,
+) -> Result<(), Error> {
+    if *eobrun > 0 {
+        *eobrun -= 1;
+        return Ok(());
+    }
+    let mut k = ss;
+    while k <= se {
+        let rs = ac.decode(bits)?;
+        let (r, s) = ((rs >> 4) as u32, rs & 15);
+        if s == 0 {
+            if r < 15 {
+                *eobrun = (1 << r) - 1 + bits.bits(r);
+                break;
+            }
+            k += 16;
+            continue;
+        }
+        k += r as usize;
+        if k > 63 {
+            fail!("coefficient out of range")
+        }
+        coef[k] = (bits.extend(s) << al) as i16;
+        k += 1;
+    }
+    Ok(())
+}
+
+fn ac_refine(
+    bits: &mut Bits,
+    ac: &Huffman,
+    coef: &mut [i16; 64],
+    (ss, se, al): (usize, usize, u32),
+    eobrun: &mut u32,
+) -> Result<(), Error> {
+    let (p1, m1) = (1i16 << al, -1i16 << al);
+    let refine = |bits: &mut Bits, c: &mut i16| {
+        if bits.bit() && *c & p1 == 0 {
+            *c = c.wrapping_add(if *c >= 0 { p1 } else { m1 });
+        }
+    };
+    let mut k = ss;
+    if *eobrun == 0 {
+        while k <= se {
+            let rs = ac.decode(bits)?;
+            let (mut r, s) = ((rs >> 4) as i32, rs & 15);
+            let value = match s {
+                0 if r < 15 => {
+                    *eobrun = (1 << r) + bits.bits(r as u32);
+                    break;
+                }
+                0 => 0,
+                _ if bits.bit() => p1,
+                _ => m1,
+            };
+            // skip r zero coefficients, refining the nonzero ones on the way
+            while k <= se {
+                if coef[k] != 0 {
+                    refine(bits, &mut coef[k]);
+                } else {
+                    if r == 0 {
+                        break;
+                    }
+                    r -= 1;
+                }
+                k += 1;
+            }
+            if value != 0 {
+                if k > 63 {
+                    fail!("coefficient out of range")
+                }
+                coef[k] = value;
+            }
+            k += 1;
+        }
+    }
+    if *eobrun > 0 {
+        while k <= se {
+            if coef[k] != 0 {
+                refine(bits, &mut coef[k]);
+            }
+            k += 1;
+        }
+        *eobrun -= 1;
+    }
+    Ok(())
+}
+
+/// Dequantize and inverse dct a block, writing 8x8 samples.
+fn idct(m: &[[f32; 8]; 8], coef: &[i16; 64], q: &[u16; 64], out: &mut [u8], stride: usize) {
+    if coef[1..].iter().all(|&x| x == 0) {
+        let dc = (coef[0] as f32 * q[0] as f32 / 8.0 + 128.0)
+            .round()
+            .clamp(0.0, 255.0) as u8;
+        for row in out.chunks_mut(stride).take(8) {
+            row[..8].fill(dc);
+        }
+        return;
+    }
+    let mut f = [0.0f32; 64];
+    for k in 0..64 {
+        f[ZIGZAG[k]] = coef[k] as f32 * q[k] as f32;
+    }
+    // rows, then columns
+    let mut tmp = [0.0f32; 64];
+    for v in 0..8 {
+        for x in 0..8 {
+            tmp[v * 8 + x] = (0..8).map(|u| m[x][u] * f[v * 8 + u]).sum();
+        }
+    }
+    for (y, row) in out.chunks_mut(stride).take(8).enumerate() {
+        for x in 0..8 {
+            let s: f32 = (0..8).map(|v| m[y][v] * tmp[v * 8 + x]).sum();
+            row[x] = (s + 128.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+/// Scale a component plane to the size of the image, with bilinear (centered) sampling.
+fn upsample(
+    plane: &[u8],
+    stride: usize,
+    (cw, ch): (usize, usize),
+    (w, h): (usize, usize),
+    (sh, hmax): (usize, usize),
+    (sv, vmax): (usize, usize),
+) -> Vec<u8> {
+    if sh == hmax && sv == vmax {
+        return plane
+            .chunks_exact(stride)
+            .take(h)
+            .flat_map(|x| &x[..w])
+            .copied()
+            .collect();
+    }
+    // (first, second, weight of second) for each output coordinate
+    let taps = |n: usize, size: usize, s: usize, max: usize| {
+        (0..n)
+            .map(|x| {
+                let c = ((x as f32 + 0.5) * s as f32 / max as f32 - 0.5).max(0.0);
+                let a = (c as usize).min(size - 1);
+                (a, (a + 1).min(size - 1), c - a as f32)
+            })
+            .collect::<Vec<_>>()
+    };
+    let xs = taps(w, cw, sh, hmax);
+    let ys = taps(h, ch, sv, vmax);
+    let mut out = Vec::with_capacity(w * h);
+    for &(y0, y1, wy) in &ys {
+        let (r0, r1) = (&plane[y0 * stride..], &plane[y1 * stride..]);
+        out.extend(xs.iter().map(|&(x0, x1, wx)| {
+            let top = (r0[x1] as f32 - r0[x0] as f32).mul_add(wx, r0[x0] as f32);
+            let bottom = (r1[x1] as f32 - r1[x0] as f32).mul_add(wx, r1[x0] as f32);
+            (bottom - top).mul_add(wy, top).round() as u8
+        }));
+    }
+    out
+}
+
+fn ycbcr(y: u8, cb: u8, cr: u8) -> [u8; 3] {
+    let (y, cb, cr) = (y as f32, cb as f32 - 128.0, cr as f32 - 128.0);
+    [
+        1.402f32.mul_add(cr, y),
+        (-0.714_136f32).mul_add(cr, (-0.344_136f32).mul_add(cb, y)),
+        1.772f32.mul_add(cb, y),
+    ]
+    .map(|x| x.round().clamp(0.0, 255.0) as u8)
+}
+
+/// Decode a jpeg image. Produces a [`DynImage::Y`] or a [`DynImage::Rgb`].
+pub fn decode(data: &[u8]) -> Result<DynImage<Box<[u8]>>, Error> {
+    if !data.starts_with(&[0xff, 0xd8]) {
+        fail!("bad magic")
+    }
+    let mut d = Decoder::default();
+    let mut pos = 2;
+    loop {
+        while data.get(pos..pos + 2) == Some(&[0xff, 0xff]) {
+            pos += 1;
+        }
+        let Some(&[0xff, marker]) = data.get(pos..pos + 2) else {
+            if d.scans > 0 {
+                // missing end of image
+                break;
+            }
+            fail!("unexpected end of data")
+        };
+        pos += 2;
+        match marker {
+            // end of image
+            0xd9 => break,
+            0xd0..=0xd7 | 0x01 => continue,
+            _ => {}
+        }
+        let Some(len) = data.get(pos..pos + 2).map(|x| u16be(x, 0)) else {
+            fail!("unexpected end of data")
+        };
+        let Some(seg) = data.get(pos + 2..pos + len.max(2)) else {
+            fail!("unexpected end of data")
+        };
+        pos += len;
+        match marker {
+            0xc0 | 0xc1 => d.frame(seg, false)?,
+            0xc2 => d.frame(seg, true)?,
+            0xc3 | 0xc5..=0xc7 | 0xc9..=0xcb | 0xcd..=0xcf => {
+                fail!("lossless, hierarchical, and arithmetic coded jpegs are not supported")
+            }
+            0xc4 => d.huffman(seg)?,
+            0xdb => d.quant(seg)?,
+            0xdd if seg.len() >= 2 => d.restart_interval = u16be(seg, 0),
+            0xda => pos = d.scan(seg, data, pos)?,
+            0xee if seg.starts_with(b"Adobe") && seg.len() >= 12 => d.adobe = Some(seg[11]),
+            _ => {}
+        }
+    }
+    d.finish()
+}
+
+#[test]
+fn handcrafted() {
+    // 16x8 gray, two blocks: dc +8 with one horizontal ac coefficient, then a flat block (dc diff 0)
+    let mut data = vec![0xff, 0xd8, 0xff, 0xdb, 0, 0x43, 0, 1, 16];
+    data.extend([1; 62]);
+    data.extend([0xff, 0xc0, 0, 11, 8, 0, 8, 0, 16, 1, 1, 0x11, 0]);
+    for (class, counts, symbols) in [(0x00, [2, 0], [0, 4]), (0x10, [1, 1], [0, 1])] {
+        data.extend([0xff, 0xc4, 0, 21, class]);
+        data.extend(counts.iter().chain(&[0; 14]).chain(&symbols));
+    }
+    data.extend([0xff, 0xda, 0, 8, 1, 1, 0, 0, 63, 0, 0xc5, 0x1f, 0xff, 0xd9]);
+    let img = decode(&data).unwrap();
+    let row = [
+        132, 131, 131, 130, 128, 127, 127, 126, 129, 129, 129, 129, 129, 129, 129, 129,
+    ];
+    assert_eq!(img.bytes(), row.repeat(8));
+}
+
+#[test]
+fn huge() {
+    let mut data = vec![
+        0xff, 0xd8, 0xff, 0xc0, 0, 11, 8, 0xff, 0xff, 0xff, 0xff, 1, 1, 0x11, 0,
+    ];
+    data.extend([0xff, 0xd9]);
+    assert!(matches!(decode(&data), Err(Error::Decode(_))));
+}
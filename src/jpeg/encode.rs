@@ -0,0 +1,537 @@
+use super::{ZIGZAG, dct_matrix};
+use crate::Image;
+
+/// Chroma subsampling of color jpegs.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Subsampling {
+    /// Full resolution chroma (4:4:4).
+    Yuv444,
+    /// Half resolution chroma, horizontally and vertically (4:2:0).
+    #[default]
+    Yuv420,
+}
+
+/// Jpeg encoder options.
+///
+/// ```
+/// # use fimg::jpeg::{Options, Subsampling};
+/// let o = Options { quality: 90, subsampling: Subsampling::Yuv444, ..Options::default() };
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Options {
+    /// Quality, from 1 (smallest) to 100 (best). Defaults to 75.
+    pub quality: u8,
+    /// Chroma subsampling. Ignored for gray images.
+    pub subsampling: Subsampling,
+    /// Write a progressive jpeg, which can be shown at a lower detail before it has fully loaded.
+    pub progressive: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            quality: 75,
+            subsampling: Subsampling::default(),
+            progressive: false,
+        }
+    }
+}
+
+/// The standard (Annex K) luminance quantization table.
+const LUMA: [u16; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, //
+    12, 12, 14, 19, 26, 58, 60, 55, //
+    14, 13, 16, 24, 40, 57, 69, 56, //
+    14, 17, 22, 29, 51, 87, 80, 62, //
+    18, 22, 37, 56, 68, 109, 103, 77, //
+    24, 35, 55, 64, 81, 104, 113, 92, //
+    49, 64, 78, 87, 103, 121, 120, 101, //
+    72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+/// The standard (Annex K) chrominance quantization table.
+const CHROMA: [u16; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, //
+    18, 21, 26, 66, 99, 99, 99, 99, //
+    24, 26, 56, 99, 99, 99, 99, 99, //
+    47, 66, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+/// Scale a base table by the quality (like libjpeg), in zigzag order.
+fn quant(base: &[u16; 64], quality: u8) -> [u16; 64] {
+    let q = quality.clamp(1, 100) as u32;
+    let scale = if q < 50 { 5000 / q } else { 200 - q * 2 };
+    std::array::from_fn(|k| ((base[ZIGZAG[k]] as u32 * scale + 50) / 100).clamp(1, 255) as u16)
+}
+
+struct Component {
+    h: usize,
+    v: usize,
+    /// blocks per line, padded to whole MCUs
+    bw: usize,
+    /// blocks covering the component
+    cover: (usize, usize),
+    /// quantized, in zigzag order
+    blocks: Vec<[i16; 64]>,
+}
+
+impl Component {
+    /// Level shift, dct and quantize a plane of `w`x`h` samples, padding by repeating the edges.
+    fn new(
+        plane: &[f32],
+        (w, h): (usize, usize),
+        (mh, mv): (usize, usize),
+        (mcux, mcuy): (usize, usize),
+        q: &[u16; 64],
+        m: &[[f32; 8]; 8],
+    ) -> Self {
+        let (bw, bh) = (mcux * mh, mcuy * mv);
+        let mut blocks = Vec::with_capacity(bw * bh);
+        for by in 0..bh {
+            for bx in 0..bw {
+                let f: [f32; 64] = std::array::from_fn(|i| {
+                    let (x, y) = ((bx * 8 + i % 8).min(w - 1), (by * 8 + i / 8).min(h - 1));
+                    plane[y * w + x] - 128.0
+                });
+                // rows, then columns
+                let mut tmp = [0.0f32; 64];
+                for y in 0..8 {
+                    for u in 0..8 {
+                        tmp[y * 8 + u] = (0..8).map(|x| m[x][u] * f[y * 8 + x]).sum();
+                    }
+                }
+                blocks.push(std::array::from_fn(|k| {
+                    let (v, u) = (ZIGZAG[k] / 8, ZIGZAG[k] % 8);
+                    let c: f32 = (0..8).map(|y| m[y][v] * tmp[y * 8 + u]).sum();
+                    (c / q[k] as f32).round() as i16
+                }));
+            }
+        }
+        Self {
+            h: mh,
+            v: mv,
+            bw,
+            cover: (w.div_ceil(8), h.div_ceil(8)),
+            blocks,
+        }
+    }
+}
+
+/// A huffman table, built from symbol frequencies.
+struct Huffman {
+    counts: [u8; 16],
+    values: Vec<u8>,
+    /// `(code, length)` of each symbol
+    codes: [(u16, u8); 256],
+}
+
+impl Huffman {
+    /// Build an optimal length limited code (Annex K.2).
+    fn new(freq: &[u32; 256]) -> Self {
+        let mut freq = freq.map(|x| x as u64).to_vec();
+        // reserve one code, so no code is all ones
+        freq.push(1);
+        let mut size = [0usize; 257];
+        let mut others = [usize::MAX; 257];
+        loop {
+            // the least frequent symbol, and the next least frequent
+            let least = |skip: usize| {
+                (0..257)
+                    .filter(|&i| freq[i] > 0 && i != skip)
+                    .min_by_key(|&i| (freq[i], std::cmp::Reverse(i)))
+            };
+            let Some(mut c1) = least(usize::MAX) else {
+                break;
+            };
+            let Some(mut c2) = least(c1) else { break };
+            freq[c1] += freq[c2];
+            freq[c2] = 0;
+            size[c1] += 1;
+            while others[c1] != usize::MAX {
+                c1 = others[c1];
+                size[c1] += 1;
+            }
+            others[c1] = c2;
+            size[c2] += 1;
+            while others[c2] != usize::MAX {
+                c2 = others[c2];
+                size[c2] += 1;
+            }
+        }
+        let mut bits = [0u32; 33];
+        for &s in size.iter().filter(|&&s| s > 0) {
+            bits[s] += 1;
+        }
+        // limit the code lengths to 16
+        for i in (17..=32).rev() {
+            while bits[i] > 0 {
+                let mut j = i - 2;
+                while bits[j] == 0 {
+                    j -= 1;
+                }
+                bits[i] -= 2;
+                bits[i - 1] += 1;
+                bits[j + 1] += 2;
+                bits[j] -= 1;
+            }
+        }
+        // drop the reserved code
+        if let Some(i) = (1..=16).rev().find(|&i| bits[i] > 0) {
+            bits[i] -= 1;
+        }
+
+        let mut values = (0..256).filter(|&s| size[s] > 0).collect::<Vec<_>>();
+        values.sort_by_key(|&s| size[s]);
+        let values = values.into_iter().map(|s| s as u8).collect::<Vec<_>>();
+        let counts = std::array::from_fn(|i| bits[i + 1] as u8);
+        let mut codes = [(0, 0); 256];
+        let (mut code, mut k) = (0u16, 0);
+        for (len, &n) in (1..).zip(&counts) {
+            for _ in 0..n {
+                codes[values[k] as usize] = (code, len);
+                code += 1;
+                k += 1;
+            }
+            code <<= 1;
+        }
+        Self {
+            counts,
+            values,
+            codes,
+        }
+    }
+}
+
+/// Counts symbols (for building tables), or writes them.
+struct Entropy {
+    /// dc 0, dc 1, ac 0, ac 1
+    freq: [[u32; 256]; 4],
+    tables: Option<[Huffman; 4]>,
+    out: Vec<u8>,
+    acc: u32,
+    n: u32,
+}
+
+impl Entropy {
+    fn symbol(&mut self, table: usize, symbol: u8) {
+        match &self.tables {
+            None => self.freq[table][symbol as usize] += 1,
+            Some(t) => {
+                let (code, len) = t[table].codes[symbol as usize];
+                self.bits(code as u32, len as u32);
+            }
+        }
+    }
+
+    fn bits(&mut self, bits: u32, n: u32) {
+        if self.tables.is_none() || n == 0 {
+            return;
+        }
+        self.acc = (self.acc << n) | (bits & ((1 << n) - 1));
+        self.n += n;
+        while self.n >= 8 {
+            self.n -= 8;
+            let byte = (self.acc >> self.n) as u8;
+            self.out.push(byte);
+            if byte == 0xff {
+                self.out.push(0);
+            }
+        }
+    }
+
+    /// Write a value with its size (category) symbol.
+    fn value(&mut self, table: usize, run: u8, v: i32) {
+        let size = 32 - v.unsigned_abs().leading_zeros();
+        self.symbol(table, run << 4 | size as u8);
+        self.bits(if v < 0 { v - 1 } else { v } as u32, size);
+    }
+
+    fn eob(&mut self, table: usize, eobrun: &mut u32) {
+        if *eobrun > 0 {
+            let r = 31 - eobrun.leading_zeros();
+            self.symbol(table, (r << 4) as u8);
+            self.bits(*eobrun, r);
+            *eobrun = 0;
+        }
+    }
+}
+
+/// Encode a scan of these components, over coefficients `ss..=se`.
+fn scan(
+    e: &mut Entropy,
+    components: &[Component],
+    scan: &[usize],
+    (mcux, mcuy): (usize, usize),
+    (ss, se): (usize, usize),
+    progressive: bool,
+) {
+    let mut order = vec![];
+    if let [c] = scan {
+        let x = &components[*c];
+        for y in 0..x.cover.1 {
+            order.extend((0..x.cover.0).map(|b| (*c, y * x.bw + b)));
+        }
+    } else {
+        for my in 0..mcuy {
+            for mx in 0..mcux {
+                for &c in scan {
+                    let x = &components[c];
+                    for y in 0..x.v {
+                        order.extend((0..x.h).map(|b| (c, (my * x.v + y) * x.bw + mx * x.h + b)));
+                    }
+                }
+            }
+        }
+    }
+    let mut prediction = [0; 3];
+    let mut eobrun = 0;
+    for (c, b) in order {
+        let coef = &components[c].blocks[b];
+        let (dc, ac) = ((c != 0) as usize, 2 + (c != 0) as usize);
+        if ss == 0 {
+            e.value(dc, 0, coef[0] as i32 - prediction[c]);
+            prediction[c] = coef[0] as i32;
+        }
+        if se == 0 {
+            continue;
+        }
+        let mut run = 0;
+        for &v in &coef[ss.max(1)..=se] {
+            if v == 0 {
+                run += 1;
+                continue;
+            }
+            e.eob(ac, &mut eobrun);
+            while run > 15 {
+                e.symbol(ac, 0xf0);
+                run -= 16;
+            }
+            e.value(ac, run, v as i32);
+            run = 0;
+        }
+        if run > 0 {
+            if progressive {
+                eobrun += 1;
+                if eobrun == 0x7fff {
+                    e.eob(ac, &mut eobrun);
+                }
+            } else {
+                e.symbol(ac, 0x00);
+            }
+        }
+    }
+    e.eob(2 + (scan[0] != 0) as usize, &mut eobrun);
+}
+
+fn segment(out: &mut Vec<u8>, marker: u8, data: &[u8]) {
+    out.extend([0xff, marker]);
+    out.extend(((data.len() + 2) as u16).to_be_bytes());
+    out.extend(data);
+}
+
+/// Encode a gray or RGB image to jpeg.
+///
+/// # Panics
+///
+/// if the image is larger than 65535x65535.
+pub fn encode<const N: usize>(image: Image<&[u8], N>, options: Options) -> Vec<u8>
+where
+    [(); ((N == 1) | (N == 3)) as usize - 1]:,
+{
+    let (w, h) = (image.width() as usize, image.height() as usize);
+    assert!(
+        w <= 0xffff && h <= 0xffff,
+        "jpeg images are at most 65535x65535"
+    );
+    let m = dct_matrix();
+    let tables = [
+        quant(&LUMA, options.quality),
+        quant(&CHROMA, options.quality),
+    ];
+    let sub = N == 3 && options.subsampling == Subsampling::Yuv420;
+    let s = if sub { 2 } else { 1 };
+    let mcu = (w.div_ceil(8 * s), h.div_ceil(8 * s));
+
+    let buf = image.buffer();
+    let mut components = vec![];
+    if N == 1 {
+        let y = buf.iter().map(|&x| x as f32).collect::<Vec<_>>();
+        components.push(Component::new(&y, (w, h), (1, 1), mcu, &tables[0], &m));
+    } else {
+        let mut planes = [(); 3].map(|()| Vec::with_capacity(w * h));
+        for p in buf.chunks_exact(3) {
+            let [r, g, b] = [p[0], p[1], p[2]].map(|x| x as f32);
+            planes[0].push(0.114f32.mul_add(b, 0.299f32.mul_add(r, 0.587 * g)));
+            planes[1].push(0.5f32.mul_add(b, (-0.168_736f32).mul_add(r, -0.331_264 * g)) + 128.0);
+            planes[2].push((-0.081_312f32).mul_add(b, 0.5f32.mul_add(r, -0.418_688 * g)) + 128.0);
+        }
+        let [y, cb, cr] = planes;
+        components.push(Component::new(&y, (w, h), (s, s), mcu, &tables[0], &m));
+        for c in [cb, cr] {
+            let c = if sub {
+                // average each 2x2
+                let (cw, ch) = (w.div_ceil(2), h.div_ceil(2));
+                let at = |x: usize, y: usize| c[y.min(h - 1) * w + x.min(w - 1)];
+                (0..cw * ch)
+                    .map(|i| {
+                        let (x, y) = (i % cw * 2, i / cw * 2);
+                        (at(x, y) + at(x + 1, y) + at(x, y + 1) + at(x + 1, y + 1)) / 4.0
+                    })
+                    .collect()
+            } else {
+                c
+            };
+            let size = (w.div_ceil(s), h.div_ceil(s));
+            components.push(Component::new(&c, size, (1, 1), mcu, &tables[1], &m));
+        }
+    }
+
+    let mut out = vec![0xff, 0xd8];
+    segment(&mut out, 0xe0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0");
+    let mut dqt = vec![];
+    for (i, t) in tables.iter().enumerate().take(if N == 1 { 1 } else { 2 }) {
+        dqt.push(i as u8);
+        dqt.extend(t.iter().map(|&x| x as u8));
+    }
+    segment(&mut out, 0xdb, &dqt);
+    let mut sof = vec![8];
+    sof.extend((h as u16).to_be_bytes());
+    sof.extend((w as u16).to_be_bytes());
+    sof.push(N as u8);
+    for (i, c) in components.iter().enumerate() {
+        sof.extend([i as u8 + 1, (c.h << 4 | c.v) as u8, (i != 0) as u8]);
+    }
+    segment(
+        &mut out,
+        if options.progressive { 0xc2 } else { 0xc0 },
+        &sof,
+    );
+
+    let all = [0, 1, 2];
+    let all = &all[..N];
+    let scans: &[(&[usize], usize, usize)] = match (options.progressive, N) {
+        (false, _) => &[(all, 0, 63)],
+        // spectral selection: the dc, a rough luma, the chroma, then the remaining luma
+        (true, 1) => &[(all, 0, 0), (&[0], 1, 5), (&[0], 6, 63)],
+        (true, _) => &[
+            (all, 0, 0),
+            (&[0], 1, 5),
+            (&[1], 1, 63),
+            (&[2], 1, 63),
+            (&[0], 6, 63),
+        ],
+    };
+    for &(which, ss, se) in scans {
+        let mut e = Entropy {
+            freq: [[0; 256]; 4],
+            tables: None,
+            out: vec![],
+            acc: 0,
+            n: 0,
+        };
+        scan(
+            &mut e,
+            &components,
+            which,
+            mcu,
+            (ss, se),
+            options.progressive,
+        );
+        let tables = e.freq.each_ref().map(Huffman::new);
+        let mut dht = vec![];
+        for (i, t) in tables.iter().enumerate() {
+            if e.freq[i].iter().any(|&x| x > 0) {
+                dht.push(((i & 2) << 3 | (i & 1)) as u8);
+                dht.extend(t.counts);
+                dht.extend(&t.values);
+            }
+        }
+        segment(&mut out, 0xc4, &dht);
+
+        let mut sos = vec![which.len() as u8];
+        for &c in which {
+            let t = (c != 0) as u8;
+            sos.extend([c as u8 + 1, t << 4 | t]);
+        }
+        sos.extend([ss as u8, se as u8, 0]);
+        segment(&mut out, 0xda, &sos);
+
+        e.tables = Some(tables);
+        scan(
+            &mut e,
+            &components,
+            which,
+            mcu,
+            (ss, se),
+            options.progressive,
+        );
+        // pad with ones
+        let pad = (8 - e.n % 8) % 8;
+        e.bits(0xff, pad);
+        out.extend(e.out);
+    }
+    out.extend([0xff, 0xd9]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// mean absolute error
+    fn error(a: &[u8], b: &[u8]) -> f32 {
+        assert_eq!(a.len(), b.len());
+        let sum = a
+            .iter()
+            .zip(b)
+            .map(|(&a, &b)| a.abs_diff(b) as u32)
+            .sum::<u32>();
+        sum as f32 / a.len() as f32
+    }
+
+    #[cfg(feature = "save")]
+    #[test]
+    fn roundtrip() {
+        let cat = Image::<_, 4>::open("tdata/small_cat.png");
+        let rgb = Image::<Box<[u8]>, 3>::from(cat.as_ref());
+        let y = Image::<Box<[u8]>, 1>::from(cat.as_ref());
+        for progressive in [false, true] {
+            for subsampling in [Subsampling::Yuv444, Subsampling::Yuv420] {
+                let o = Options {
+                    quality: 90,
+                    subsampling,
+                    progressive,
+                };
+                let d = super::super::decode(&encode(rgb.as_ref(), o)).unwrap();
+                assert!(error(d.bytes(), rgb.bytes()) < 4.0);
+                let d = super::super::decode(&encode(y.as_ref(), o)).unwrap();
+                assert!(error(d.bytes(), y.bytes()) < 2.0);
+            }
+        }
+    }
+
+    #[test]
+    fn best() {
+        let img = Image::<_, 3>::build(13, 7).buf(
+            (0..13 * 7 * 3)
+                .map(|x| (x * 7 % 256) as u8)
+                .collect::<Vec<_>>(),
+        );
+        let o = Options {
+            quality: 100,
+            subsampling: Subsampling::Yuv444,
+            progressive: false,
+        };
+        let d = super::super::decode(&encode(img.as_ref(), o)).unwrap();
+        assert!(
+            d.bytes()
+                .iter()
+                .zip(img.buffer())
+                .all(|(&a, &b)| a.abs_diff(b) <= 6)
+        );
+    }
+}
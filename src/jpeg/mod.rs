@@ -0,0 +1,104 @@
+//! [JPEG](https://jpeg.org/jpeg/) decoding and encoding.
+//!
+//! Reads baseline and progressive (huffman coded) jpegs, with any chroma subsampling.
+//! Writes baseline or progressive jpegs, with optimized huffman tables.
+use crate::{DynImage, Error, Image};
+mod decode;
+mod encode;
+pub use decode::decode;
+pub use encode::{Options, Subsampling, encode};
+
+/// Read a jpeg image.
+pub trait ReadJpeg: Sized {
+    /// Read a jpeg into an image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error>;
+}
+
+impl ReadJpeg for DynImage<Box<[u8]>> {
+    /// Read a jpeg image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        let mut data = vec![];
+        f.read_to_end(&mut data)?;
+        decode(&data)
+    }
+}
+
+impl<const N: usize> ReadJpeg for Image<Box<[u8]>, N>
+where
+    Self: From<DynImage<Box<[u8]>>>,
+{
+    /// Read a jpeg image, converting it to this many channels.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        DynImage::read(f).map(Into::into)
+    }
+}
+
+/// Write a jpeg image.
+pub trait WriteJpeg {
+    /// Write this image as a jpeg, with the default [`Options`].
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        self.write_with(f, Options::default())
+    }
+
+    /// Write this image as a jpeg.
+    fn write_with(&self, f: &mut impl std::io::Write, options: Options) -> Result<(), Error>;
+}
+
+macro_rules! writer {
+    ($n:literal) => {
+        impl<T: AsRef<[u8]>> WriteJpeg for Image<T, $n> {
+            /// Write this image as a jpeg.
+            fn write_with(
+                &self,
+                f: &mut impl std::io::Write,
+                options: Options,
+            ) -> Result<(), Error> {
+                if self.width() > u16::MAX as u32 || self.height() > u16::MAX as u32 {
+                    return Err(Error::Encode("jpeg: image too large".into()));
+                }
+                f.write_all(&encode(self.as_ref(), options))?;
+                Ok(())
+            }
+        }
+    };
+}
+writer!(1);
+writer!(3);
+
+impl<T: AsRef<[u8]> + crate::Buffer> WriteJpeg for DynImage<T> {
+    /// Write this image as a jpeg. Alpha is dropped, and 16 bit images are reduced to 8 bit.
+    fn write_with(&self, f: &mut impl std::io::Write, options: Options) -> Result<(), Error> {
+        match self {
+            Self::Y(x) => x.write_with(f, options),
+            Self::Rgb(x) => x.write_with(f, options),
+            Self::Ya(_) | Self::Y16(_) | Self::Ya16(_) => self.y().write_with(f, options),
+            Self::Rgba(_) | Self::Rgb16(_) | Self::Rgba16(_) => self.rgb().write_with(f, options),
+        }
+    }
+}
+
+/// The natural (row major) index of the nth coefficient in zigzag order.
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, //
+    17, 24, 32, 25, 18, 11, 4, 5, //
+    12, 19, 26, 33, 40, 48, 41, 34, //
+    27, 20, 13, 6, 7, 14, 21, 28, //
+    35, 42, 49, 56, 57, 50, 43, 36, //
+    29, 22, 15, 23, 30, 37, 44, 51, //
+    58, 59, 52, 45, 38, 31, 39, 46, //
+    53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+/// `m[x][u]`: the contribution of frequency `u` to sample `x`, for the separable (i)dct.
+fn dct_matrix() -> [[f32; 8]; 8] {
+    std::array::from_fn(|x| {
+        std::array::from_fn(|u| {
+            let c = if u == 0 {
+                std::f32::consts::FRAC_1_SQRT_2
+            } else {
+                1.0
+            };
+            0.5 * c * ((2 * x + 1) as f32 * u as f32 * std::f32::consts::PI / 16.0).cos()
+        })
+    })
+}
@@ -41,14 +41,30 @@
 //! ## feature flags
 //!
 //! - `scale`: enables the [`scale`] module.
-//! - `save`: enables [`Image::save`], via the [`png`](https://crates.io/crates/png) crate.
+//! - `save`: enables [`Image::save`] (and [`Image::try_save`], [`Image::try_save_with`], [`Image::save16`] for 16 bit images, and the row streaming [`PngRowReader`] and [`PngRowWriter`]), via the [`png`](https://crates.io/crates/png) crate.
+//! - `pnm`: enables the [`pnm`] module, for reading and writing [Netpbm](https://netpbm.sourceforge.net/doc/#formats) images.
+//! - `qoi`: enables the [`qoi`] module, for reading and writing [QOI](https://qoiformat.org) images.
+//! - `bmp`: enables the [`bmp`] module, for reading and writing BMP images.
+//! - `tga`: enables the [`tga`] module, for reading and writing TGA images.
+//! - `jpeg`: enables the [`jpeg`] module, for reading and writing JPEG images.
+//! - `apng`: enables the [`apng`] module, for reading and writing animated PNGs.
+//! - `gif`: enables the [`gif`] module, for reading and writing (animated) GIFs.
+//! - `hdr`: enables the [`hdr`] module, for reading and writing Radiance HDR float images.
+//! - `exr`: enables the [`exr`] module, for reading and writing OpenEXR float images.
+//! - `tiff`: enables the [`tiff`] module, for reading and writing TIFF images.
+//! - `webp`: enables the [`webp`] module, for reading and writing lossless WebP images.
+//! - `dds`: enables the [`dds`] module, for reading and writing (block compressed) DDS textures.
+//! - `ico`: enables the [`ico`] module, for reading and writing icons and cursors.
+//! - `farbfeld`: enables the [`farbfeld`] module, for reading and writing farbfeld images.
+//! - `raw`: enables the [`raw`] module, for reading and writing raw pixel buffers of any layout.
 //! - `text`: enables [`Image::text`], via the [`fontdue`](https://crates.io/crates/fontdue) crate.
 //! - `blur`: enables [`Image::blur`], via the [`stackblur`](https://crates.io/crates/stackblur-iter) crate.
 //! - `real-show`: [`Image::show`], if the `save` feature is enabled, will, by default, simply open the appropriate image viewing program.
 //! if, for some reason, this is inadequate/you dont have a good image viewer, enable the `real-show` feature to make [`Image::show`] open up a window of its own.
 //! without the `real-show` feature, [`Image::show`] will save itself to your temp directory, which you may not want.
 //! - `term`: [`term::print`]. this enables printing images directly to the terminal, if you don't want to open a window or something. supports `{iterm2, kitty, sixel, fallback}` graphics.
-//! - `default`: \[`save`, `scale`\].
+//! - `all-formats`: every image format above: \[`save`, `qoi`, `pnm`, `bmp`, `tga`, `jpeg`, `apng`, `gif`, `hdr`, `exr`, `tiff`, `webp`, `dds`, `ico`, `farbfeld`, `raw`\].
+//! - `default`: \[`save`, `scale`, `term`\].
 #![cfg_attr(all(feature = "term", windows), windows_subsystem = "console")]
 #![feature(
     type_changing_struct_update,
@@ -93,38 +109,87 @@ use std::{hint::assert_unchecked, intrinsics::transmute_unchecked, num::NonZeroU
 mod affine;
 #[cfg(feature = "blur")]
 mod blur;
+#[cfg(feature = "bmp")]
+pub mod bmp;
 mod slicing;
 pub use sub::{Cropper, SubImage};
 pub mod builder;
 #[doc(hidden)]
 pub mod cloner;
 mod convert;
+#[cfg(feature = "dds")]
+pub mod dds;
 mod drawing;
 mod r#dyn;
+mod error;
+#[cfg(feature = "exr")]
+pub mod exr;
+#[cfg(feature = "farbfeld")]
+pub mod farbfeld;
+mod format;
 pub mod indexed;
+mod linear;
 pub(crate) mod math;
+pub mod matrix;
+pub mod meta;
 #[doc(hidden)]
 pub mod overlay;
 mod pack;
+pub mod space;
 mod span;
 mod sub;
 pub mod uninit;
 #[cfg(feature = "wgpu-convert")]
 mod wgpu_convert;
+pub use format::Format;
 pub use pack::Pack;
+#[cfg(feature = "apng")]
+pub mod apng;
+#[cfg(feature = "gif")]
+pub mod gif;
+#[cfg(feature = "hdr")]
+pub mod hdr;
+#[cfg(feature = "ico")]
+pub mod ico;
+#[cfg(feature = "jpeg")]
+pub mod jpeg;
 pub mod pixels;
+#[cfg(feature = "save")]
+mod png_options;
+mod premultiplied;
+#[cfg(feature = "save")]
+pub use png_options::{PngCompression, PngFilter, PngOptions};
+#[cfg(feature = "save")]
+mod png_stream;
+#[cfg(feature = "save")]
+pub use png_stream::{PngRowReader, PngRowWriter};
+#[cfg(feature = "pnm")]
+pub mod pnm;
+#[cfg(feature = "qoi")]
+pub mod qoi;
+#[cfg(feature = "raw")]
+pub mod raw;
 #[cfg(feature = "scale")]
 pub mod scale;
 #[cfg(any(feature = "save", feature = "real-show"))]
 mod show;
 #[cfg(feature = "term")]
 pub mod term;
+#[cfg(feature = "tga")]
+pub mod tga;
+#[cfg(feature = "tiff")]
+pub mod tiff;
+#[cfg(feature = "webp")]
+pub mod webp;
 pub use cloner::ImageCloner;
-pub use r#dyn::DynImage;
+pub use r#dyn::{Buffer, DynImage};
+pub use error::Error;
+pub use linear::Linear;
 pub use overlay::{
     BlendingOverlay, BlendingOverlayAt, ClonerOverlay, ClonerOverlayAt, Overlay, OverlayAt,
     OverlayAtClipping,
 };
+pub use premultiplied::Premultiplied;
 
 trait CopyWithinUnchecked {
     /// # Safety
@@ -362,9 +427,9 @@ impl<const CHANNELS: usize, T: Clone> Image<&mut [T], CHANNELS> {
     }
 }
 
-impl<const CHANNELS: usize> Copy for Image<&[u8], CHANNELS> {}
+impl<const CHANNELS: usize, T> Copy for Image<&[T], CHANNELS> {}
 
-impl<const CHANNELS: usize> Image<&[u8], CHANNELS> {
+impl<const CHANNELS: usize, T> Image<&[T], CHANNELS> {
     #[inline]
     #[must_use]
     /// Copy this ref image
@@ -375,7 +440,9 @@ impl<const CHANNELS: usize> Image<&[u8], CHANNELS> {
             buffer: self.buffer,
         }
     }
+}
 
+impl<const CHANNELS: usize> Image<&[u8], CHANNELS> {
     /// Create a new immutable image of width x, y.
     ///
     /// # Panics
@@ -518,6 +585,16 @@ impl<T, const CHANNELS: usize> Image<T, CHANNELS> {
         unsafe { self.buffer().as_ref().as_chunks_unchecked::<CHANNELS>() }
     }
 
+    #[inline]
+    /// Flatten the chunks of this image into a mutable slice of slices.
+    pub fn flatten_mut<U>(&mut self) -> &mut [[U; CHANNELS]]
+    where
+        T: AsMut<[U]>,
+    {
+        // SAFETY: buffer cannot have half pixels
+        unsafe { self.buffer.as_mut().as_chunks_unchecked_mut::<CHANNELS>() }
+    }
+
     /// Create a mutref to this image
     pub fn as_mut<U>(&mut self) -> Image<&mut [U], CHANNELS>
     where
@@ -787,13 +864,6 @@ impl<T: AsMut<[u8]> + AsRef<[u8]>, const CHANNELS: usize> Image<T, CHANNELS> {
         self.buffer.as_mut().array_chunks_mut::<CHANNELS>()
     }
 
-    #[inline]
-    /// Flatten the chunks of this image into a mutable slice of slices.
-    pub fn flatten_mut(&mut self) -> &mut [[u8; CHANNELS]] {
-        // SAFETY: buffer cannot have half pixels
-        unsafe { self.buffer.as_mut().as_chunks_unchecked_mut::<CHANNELS>() }
-    }
-
     /// Set the pixel at x, y
     ///
     /// # Safety
@@ -864,9 +934,21 @@ impl<const CHANNELS: usize, T: ?Sized> Image<Box<T>, CHANNELS> {
 
 #[cfg(feature = "save")]
 /// Write a png image.
-pub trait WritePng {
-    /// Write this png image.
-    fn write(&self, f: &mut impl std::io::Write) -> std::io::Result<()>;
+///
+/// `C` is the component type: `u8` for 8 bit images, `u16` for 16 bit images.
+pub trait WritePng<C = u8> {
+    /// Write this png image, with the gamma and chromaticities of sRGB ([`Metadata::SRGB`](meta::Metadata::SRGB)).
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        self.write_with(f, &PngOptions::new())
+    }
+
+    /// Write this png image, with this metadata.
+    fn write_meta(&self, f: &mut impl std::io::Write, meta: &meta::Metadata) -> Result<(), Error> {
+        self.write_with(f, &PngOptions::new().meta(meta))
+    }
+
+    /// Write this png image, with these [`PngOptions`].
+    fn write_with(&self, f: &mut impl std::io::Write, options: &PngOptions) -> Result<(), Error>;
 }
 
 /// Read png.
@@ -876,104 +958,428 @@ where
     Self: Sized,
 {
     /// Read a png into an image.
-    fn read<T: std::io::BufRead + std::io::Seek>(f: &mut T) -> std::io::Result<Self>;
+    fn read<T: std::io::BufRead + std::io::Seek>(f: &mut T) -> Result<Self, Error> {
+        Self::read_meta(f).map(|(x, _)| x)
+    }
+
+    /// Read a png into an image, along with its metadata.
+    fn read_meta<T: std::io::BufRead + std::io::Seek>(
+        f: &mut T,
+    ) -> Result<(Self, meta::Metadata), Error>;
+}
+
+/// Decode the first frame of a png, applying some transformations.
+/// 16 bit pngs become 16 bit images, unless `STRIP_16` is set.
+#[cfg(feature = "save")]
+#[allow(clippy::type_complexity)]
+fn read_png(
+    f: impl std::io::BufRead + std::io::Seek,
+    transformations: png::Transformations,
+) -> Result<(DynImage<Box<[u8]>>, meta::Metadata), Error> {
+    let mut dec = png::Decoder::new(f);
+    dec.set_transformations(transformations);
+    let mut reader = dec.read_info()?;
+    let size = reader
+        .output_buffer_size()
+        .ok_or(Error::Decode("image too large".into()))?;
+    let mut buf = vec![0; size].into_boxed_slice();
+    let info = reader.next_frame(&mut buf)?;
+    // text may come after the image data; a bad trailing chunk only loses that
+    _ = reader.finish();
+    Ok((png_frame(buf, &info)?, png_meta(reader.info())?))
+}
+
+/// Collect the metadata of a png.
+#[cfg(feature = "save")]
+pub(crate) fn png_meta(info: &png::Info) -> Result<meta::Metadata, Error> {
+    use meta::*;
+    let xy = |(x, y): (png::ScaledFloat, png::ScaledFloat)| (x.into_value(), y.into_value());
+    let mut text = vec![];
+    for t in &info.uncompressed_latin1_text {
+        text.push(Text::new(&*t.keyword, &*t.text));
+    }
+    for t in &info.compressed_latin1_text {
+        text.push(Text {
+            keyword: t.keyword.clone(),
+            text: t.get_text()?,
+            kind: TextKind::CompressedLatin1,
+        });
+    }
+    for t in &info.utf8_text {
+        text.push(Text {
+            keyword: t.keyword.clone(),
+            text: t.get_text()?,
+            kind: TextKind::Utf8 {
+                compressed: t.compressed,
+                language: t.language_tag.clone(),
+                translated_keyword: t.translated_keyword.clone(),
+            },
+        });
+    }
+    Ok(Metadata {
+        text,
+        density: info.pixel_dims.map(|d| Density {
+            x: d.xppu,
+            y: d.yppu,
+            unit: match d.unit {
+                png::Unit::Meter => Unit::Meter,
+                png::Unit::Unspecified => Unit::Unknown,
+            },
+        }),
+        icc: info.icc_profile.as_deref().map(Into::into),
+        gamma: info.gama_chunk.map(png::ScaledFloat::into_value),
+        chromaticities: info.chrm_chunk.map(|c| Chromaticities {
+            white: xy(c.white),
+            red: xy(c.red),
+            green: xy(c.green),
+            blue: xy(c.blue),
+        }),
+    })
+}
+
+/// Make an image out of a decoded png frame.
+#[cfg(feature = "save")]
+pub(crate) fn png_frame(
+    mut buf: Box<[u8]>,
+    info: &png::OutputInfo,
+) -> Result<DynImage<Box<[u8]>>, Error> {
+    if buf.len() != info.buffer_size() {
+        buf = buf[..info.buffer_size()].into();
+    }
+    use png::ColorType::*;
+    macro_rules! n {
+        ($x:literal) => {
+            Image::<_, $x>::build(info.width, info.height)
+                .try_buf(buf)?
+                .into()
+        };
+        ($x:literal, $variant:ident) => {
+            DynImage::$variant(
+                Image::build(info.width, info.height).try_buf(
+                    buf.as_chunks::<2>()
+                        .0
+                        .iter()
+                        .copied()
+                        .map(u16::from_be_bytes)
+                        .collect::<Box<[u16]>>(),
+                )?,
+            )
+        };
+    }
+    Ok(match (info.color_type, info.bit_depth) {
+        (Indexed, _) => return Err(Error::UnsupportedColor("indexed")), // see EXPAND
+        (Grayscale, png::BitDepth::Sixteen) => n![1, Y16],
+        (GrayscaleAlpha, png::BitDepth::Sixteen) => n![2, Ya16],
+        (Rgb, png::BitDepth::Sixteen) => n![3, Rgb16],
+        (Rgba, png::BitDepth::Sixteen) => n![4, Rgba16],
+        (Grayscale, _) => n![1],
+        (GrayscaleAlpha, _) => n![2],
+        (Rgb, _) => n![3],
+        (Rgba, _) => n![4],
+    })
+}
+
+/// A png encoder, that writes with these options.
+#[cfg(feature = "save")]
+pub(crate) fn png_encoder<'a, W: std::io::Write>(
+    f: W,
+    (width, height): (u32, u32),
+    (color, depth): (png::ColorType, png::BitDepth),
+    options: &PngOptions<'a>,
+) -> png::Encoder<'a, W> {
+    let meta = options.meta;
+    use meta::*;
+    // rounded, rather than truncated like ScaledFloat::new, so that read values are written back unchanged
+    let scaled =
+        |x: f32| png::ScaledFloat::from_scaled((x * png::ScaledFloat::SCALING).round() as u32);
+    let xy = |(x, y)| (scaled(x), scaled(y));
+    let mut info = png::Info::with_size(width, height);
+    info.color_type = color;
+    info.bit_depth = depth;
+    info.interlaced = options.interlace;
+    for t in &meta.text {
+        let (k, v) = (&*t.keyword, &*t.text);
+        match &t.kind {
+            TextKind::Latin1 => info
+                .uncompressed_latin1_text
+                .push(png::text_metadata::TEXtChunk::new(k, v)),
+            TextKind::CompressedLatin1 => info
+                .compressed_latin1_text
+                .push(png::text_metadata::ZTXtChunk::new(k, v)),
+            TextKind::Utf8 {
+                compressed,
+                language,
+                translated_keyword,
+            } => {
+                let mut c = png::text_metadata::ITXtChunk::new(k, v);
+                c.compressed = *compressed;
+                c.language_tag.clone_from(language);
+                c.translated_keyword.clone_from(translated_keyword);
+                info.utf8_text.push(c);
+            }
+        }
+    }
+    info.pixel_dims = meta.density.map(|d| png::PixelDimensions {
+        xppu: d.x,
+        yppu: d.y,
+        unit: match d.unit {
+            Unit::Meter => png::Unit::Meter,
+            Unit::Unknown => png::Unit::Unspecified,
+        },
+    });
+    info.icc_profile = meta.icc.as_deref().map(Into::into);
+    info.source_gamma = meta.gamma.map(scaled);
+    info.source_chromaticities = meta.chromaticities.map(|c| png::SourceChromaticities {
+        white: xy(c.white),
+        red: xy(c.red),
+        green: xy(c.green),
+        blue: xy(c.blue),
+    });
+    let mut enc = png::Encoder::with_info(f, info).expect("only fails for animations");
+    enc.set_compression(match options.compression {
+        PngCompression::None => png::Compression::NoCompression,
+        PngCompression::Fast => png::Compression::Fast,
+        PngCompression::Balanced => png::Compression::Balanced,
+        PngCompression::Best => png::Compression::High,
+    });
+    enc.set_filter(match options.filter {
+        PngFilter::None => png::Filter::NoFilter,
+        PngFilter::Sub => png::Filter::Sub,
+        PngFilter::Up => png::Filter::Up,
+        PngFilter::Average => png::Filter::Avg,
+        PngFilter::Paeth => png::Filter::Paeth,
+        PngFilter::Adaptive => png::Filter::Adaptive,
+    });
+    enc
 }
 
 /// helper macro for defining the save() method.
 macro_rules! save {
-    ($channels:literal == $clr:ident ($clrhuman:literal)) => {
+    ($channels:literal == $clrhuman:literal) => {
         #[cfg(feature = "save")]
         impl<T: AsRef<[u8]>> WritePng for Image<T, $channels> {
             #[doc = "Save this "]
             #[doc = $clrhuman]
             #[doc = " image."]
-            fn write(&self, f: &mut impl std::io::Write) -> std::io::Result<()> {
-                let mut enc = png::Encoder::new(f, self.width(), self.height());
-                enc.set_color(png::ColorType::$clr);
-                enc.set_depth(png::BitDepth::Eight);
-                enc.set_source_gamma(png::ScaledFloat::new(1.0 / 2.2));
-                enc.set_source_chromaticities(png::SourceChromaticities::new(
-                    (0.31270, 0.32900),
-                    (0.64000, 0.33000),
-                    (0.30000, 0.60000),
-                    (0.15000, 0.06000),
-                ));
-                let mut writer = enc.write_header()?;
-                writer.write_image_data(self.bytes())?;
-                Ok(())
+            fn write_with(
+                &self,
+                f: &mut impl std::io::Write,
+                options: &PngOptions,
+            ) -> Result<(), Error> {
+                png_options::write(
+                    f,
+                    (self.width(), self.height()),
+                    ($channels, 8),
+                    self.bytes(),
+                    options,
+                )
+            }
+        }
+
+        #[cfg(feature = "save")]
+        impl<T: AsRef<[u16]>> WritePng<u16> for Image<T, $channels> {
+            #[doc = "Save this 16 bit "]
+            #[doc = $clrhuman]
+            #[doc = " image."]
+            fn write_with(
+                &self,
+                f: &mut impl std::io::Write,
+                options: &PngOptions,
+            ) -> Result<(), Error> {
+                png_options::write(
+                    f,
+                    (self.width(), self.height()),
+                    ($channels, 16),
+                    &self
+                        .buffer()
+                        .as_ref()
+                        .iter()
+                        .flat_map(|x| x.to_be_bytes())
+                        .collect::<Vec<_>>(),
+                    options,
+                )
             }
         }
+
+        #[cfg(feature = "save")]
         impl<T: AsRef<[u8]>> Image<T, $channels> {
-            #[cfg(feature = "save")]
+            #[doc = "Save this "]
+            #[doc = $clrhuman]
+            #[doc = " image to a PNG, returning a [`Error`] if that fails."]
+            pub fn try_save(&self, f: impl AsRef<std::path::Path>) -> Result<(), Error> {
+                self.try_save_with(f, &PngOptions::new())
+            }
+
+            #[doc = "Save this "]
+            #[doc = $clrhuman]
+            #[doc = " image to a PNG, with these [`PngOptions`], returning a [`Error`] if that fails."]
+            pub fn try_save_with(
+                &self,
+                f: impl AsRef<std::path::Path>,
+                options: &PngOptions,
+            ) -> Result<(), Error> {
+                save_png::<u8>(self, f, options)
+            }
+
+            #[track_caller]
             #[doc = "Save this "]
             #[doc = $clrhuman]
             #[doc = " image."]
+            ///
+            /// # Panics
+            ///
+            /// if the file could not be written. See [`Image::try_save`] for a fallible version.
             pub fn save(&self, f: impl AsRef<std::path::Path>) {
-                self.write(&mut std::io::BufWriter::new(
-                    std::fs::File::create(f).unwrap(),
-                ))
-                .unwrap();
+                self.try_save(f).unwrap()
+            }
+        }
+
+        #[cfg(feature = "save")]
+        impl<T: AsRef<[u16]>> Image<T, $channels> {
+            #[doc = "Save this 16 bit "]
+            #[doc = $clrhuman]
+            #[doc = " image to a PNG, returning a [`Error`] if that fails."]
+            pub fn try_save16(&self, f: impl AsRef<std::path::Path>) -> Result<(), Error> {
+                self.try_save16_with(f, &PngOptions::new())
+            }
+
+            #[doc = "Save this 16 bit "]
+            #[doc = $clrhuman]
+            #[doc = " image to a PNG, with these [`PngOptions`], returning a [`Error`] if that fails."]
+            pub fn try_save16_with(
+                &self,
+                f: impl AsRef<std::path::Path>,
+                options: &PngOptions,
+            ) -> Result<(), Error> {
+                save_png::<u16>(self, f, options)
+            }
+
+            #[track_caller]
+            #[doc = "Save this 16 bit "]
+            #[doc = $clrhuman]
+            #[doc = " image."]
+            ///
+            /// # Panics
+            ///
+            /// if the file could not be written. See [`Image::try_save16`] for a fallible version.
+            pub fn save16(&self, f: impl AsRef<std::path::Path>) {
+                self.try_save16(f).unwrap()
             }
         }
     };
 }
 
+/// Write a png to this path, flushing it before returning.
+#[cfg(feature = "save")]
+fn save_png<C>(
+    image: &impl WritePng<C>,
+    f: impl AsRef<std::path::Path>,
+    options: &PngOptions,
+) -> Result<(), Error> {
+    use std::io::Write;
+    let mut w = std::io::BufWriter::new(std::fs::File::create(f)?);
+    image.write_with(&mut w, options)?;
+    w.flush()?;
+    Ok(())
+}
+
 macro_rules! read {
     ($n:literal) => {
         #[cfg(feature = "save")]
         impl ReadPng for Image<Box<[u8]>, $n> {
             /// Open a PNG image
-            fn read<T: std::io::BufRead + std::io::Seek>(f: &mut T) -> std::io::Result<Self> {
+            fn read_meta<T: std::io::BufRead + std::io::Seek>(
+                f: &mut T,
+            ) -> Result<(Self, meta::Metadata), Error> {
                 use png::Transformations as T;
-                let mut dec = png::Decoder::new(f);
-                match $n {
-                    1 | 3 => dec.set_transformations(T::STRIP_16 | T::EXPAND),
-                    2 | 4 => dec.set_transformations(T::STRIP_16 | T::ALPHA), // alpha implies expand
-                    _ => (),
-                }
-                let mut reader = dec.read_info()?;
-                let mut buf = vec![0; reader.output_buffer_size().unwrap()].into_boxed_slice();
-                let info = reader.next_frame(&mut buf)?;
-                use png::ColorType::*;
-                macro_rules! n {
-                    ($x:literal) => {
-                        Image::<_, $x>::build(info.width, info.height)
-                            .buf(buf)
-                            .into()
-                    };
-                }
-                Ok(match info.color_type {
-                    Indexed => unreachable!(), // see EXPAND
-                    Grayscale => n![1],
-                    GrayscaleAlpha => n![2],
-                    Rgb => n![3],
-                    Rgba => n![4],
-                })
+                let t = match $n {
+                    2 | 4 => T::STRIP_16 | T::ALPHA, // alpha implies expand
+                    _ => T::STRIP_16 | T::EXPAND,
+                };
+                read_png(f, t).map(|(x, m)| (x.into(), m))
+            }
+        }
+
+        #[cfg(feature = "save")]
+        impl ReadPng for Image<Box<[u16]>, $n> {
+            /// Open a PNG image, as 16 bit.
+            fn read_meta<T: std::io::BufRead + std::io::Seek>(
+                f: &mut T,
+            ) -> Result<(Self, meta::Metadata), Error> {
+                use png::Transformations as T;
+                let t = match $n {
+                    2 | 4 => T::ALPHA,
+                    _ => T::EXPAND,
+                };
+                read_png(f, t).map(|(x, m)| (x.into(), m))
             }
         }
     };
 }
+
+/// `t(Image::<Box<[T]>, N>::read(r)?.unbox())` for the `N` == `CHANNELS`.
+macro_rules! read_n {
+    ($r:expr, $t:ty) => {{
+        use core::intrinsics::transmute_unchecked as t;
+        // SAFETY: ... this is idiotic.
+        unsafe {
+            match CHANNELS {
+                1 => t(Image::<Box<[$t]>, 1>::read($r)?.unbox()),
+                2 => t(Image::<Box<[$t]>, 2>::read($r)?.unbox()),
+                3 => t(Image::<Box<[$t]>, 3>::read($r)?.unbox()),
+                4 => t(Image::<Box<[$t]>, 4>::read($r)?.unbox()),
+                _ => unreachable!(),
+            }
+        }
+    }};
+}
+
 impl<const CHANNELS: usize> Image<Vec<u8>, CHANNELS>
 where
     [(); { (CHANNELS <= 4) as usize } - 1]:,
 {
+    #[cfg(feature = "save")]
+    /// Open a PNG image, returning a [`Error`] if that fails.
+    pub fn try_open(f: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+        let p = std::fs::File::open(f)?;
+        let r = &mut std::io::BufReader::new(p);
+        Ok(read_n!(r, u8))
+    }
+
     #[cfg(feature = "save")]
     #[cfg_attr(debug_assertions, track_caller)]
     /// Open a PNG image
+    ///
+    /// # Panics
+    ///
+    /// if the file could not be read. See [`Image::try_open`] for a fallible version.
     pub fn open(f: impl AsRef<std::path::Path>) -> Self {
-        let p = std::fs::File::open(f).unwrap();
+        Self::try_open(f).unwrap()
+    }
+}
+
+impl<const CHANNELS: usize> Image<Vec<u16>, CHANNELS>
+where
+    [(); { (CHANNELS <= 4) as usize } - 1]:,
+{
+    #[cfg(feature = "save")]
+    /// Open a PNG image as a 16 bit image, returning a [`Error`] if that fails.
+    /// 8 bit PNGs are expanded.
+    pub fn try_open16(f: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+        let p = std::fs::File::open(f)?;
         let r = &mut std::io::BufReader::new(p);
-        use core::intrinsics::transmute_unchecked as t;
-        // SAFETY: ... this is idiotic.
-        unsafe {
-            match CHANNELS {
-                1 => t(Image::<Box<_>, 1>::read(r).unwrap().unbox()),
-                2 => t(Image::<Box<_>, 2>::read(r).unwrap().unbox()),
-                3 => t(Image::<Box<_>, 3>::read(r).unwrap().unbox()),
-                4 => t(Image::<Box<_>, 4>::read(r).unwrap().unbox()),
-                _ => unreachable!(),
-            }
-        }
+        Ok(read_n!(r, u16))
+    }
+
+    #[cfg(feature = "save")]
+    #[cfg_attr(debug_assertions, track_caller)]
+    /// Open a PNG image as a 16 bit image.
+    ///
+    /// # Panics
+    ///
+    /// if the file could not be read. See [`Image::try_open16`] for a fallible version.
+    pub fn open16(f: impl AsRef<std::path::Path>) -> Self {
+        Self::try_open16(f).unwrap()
     }
 }
 read!(1);
@@ -981,10 +1387,10 @@ read!(2);
 read!(3);
 read!(4);
 
-save!(3 == Rgb("RGB"));
-save!(4 == Rgba("RGBA"));
-save!(2 == GrayscaleAlpha("YA"));
-save!(1 == Grayscale("Y"));
+save!(3 == "RGB");
+save!(4 == "RGBA");
+save!(2 == "YA");
+save!(1 == "Y");
 
 #[cfg(test)]
 macro_rules! img {
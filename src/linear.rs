@@ -0,0 +1,228 @@
+//! linear light images, and conversion to and from sRGB.
+//! alpha is never transferred, only the color channels.
+use crate::{
+    Image,
+    convert::{f32_to_u8, f32s_to_u8s, mapping, u8_to_f32},
+};
+use std::{
+    simd::{StdFloat, prelude::*},
+    sync::LazyLock,
+};
+
+/// An image whose color channels hold linear light, as opposed to a plain (sRGB encoded) [`Image`].
+///
+/// Blending, blurring and scaling in linear light is gamma-correct.
+/// Make one with [`Image::to_linear`], and go back with [`Linear::to_srgb`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Linear<T, const N: usize>(Image<T, N>);
+
+impl<T, const N: usize> Linear<T, N> {
+    /// Treat this image as linear light. Does not convert anything.
+    pub const fn new(image: Image<T, N>) -> Self {
+        Self(image)
+    }
+
+    /// Take the image out, still linear.
+    pub fn into_inner(self) -> Image<T, N> {
+        self.0
+    }
+
+    /// The linear image.
+    pub const fn image(&self) -> &Image<T, N> {
+        &self.0
+    }
+
+    /// The linear image, mutably.
+    pub const fn image_mut(&mut self) -> &mut Image<T, N> {
+        &mut self.0
+    }
+
+    /// Reference this image.
+    pub fn as_ref<U>(&self) -> Linear<&[U], N>
+    where
+        T: AsRef<[U]>,
+    {
+        Linear(self.0.as_ref())
+    }
+
+    /// Encode back to sRGB.
+    pub fn to_srgb<U>(&self) -> Image<Box<[U]>, N>
+    where
+        T: AsRef<[f32]>,
+        for<'a> Image<Box<[U]>, N>: From<Linear<&'a [f32], N>>,
+    {
+        self.as_ref().into()
+    }
+}
+
+/// Does this many channels end in alpha?
+const fn alpha(n: usize) -> bool {
+    n == 2 || n == 4
+}
+
+pub(crate) fn linear(x: f32) -> f32 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linears(x: f32x8) -> f32x8 {
+    // x^2.4 = 2^(2.4 * log2(x))
+    let curve = ((x + Simd::splat(0.055)) / Simd::splat(1.055)).log2() * Simd::splat(2.4);
+    x.simd_le(Simd::splat(0.04045))
+        .select(x / Simd::splat(12.92), curve.exp2())
+}
+
+pub(crate) fn srgb(x: f32) -> f32 {
+    if x <= 0.0031308 {
+        x * 12.92
+    } else {
+        x.powf(1.0 / 2.4).mul_add(1.055, -0.055)
+    }
+}
+
+fn srgbs(x: f32x8) -> f32x8 {
+    let curve = (x.log2() * Simd::splat(1.0 / 2.4)).exp2();
+    x.simd_le(Simd::splat(0.0031308)).select(
+        x * Simd::splat(12.92),
+        curve.mul_add(Simd::splat(1.055), Simd::splat(-0.055)),
+    )
+}
+
+static LUT: LazyLock<[f32; 256]> =
+    LazyLock::new(|| std::array::from_fn(|i| linear(i as f32 / 255.0)));
+
+/// Put the alpha of `from` back into `to`.
+fn keep_alpha<T: Copy, U, const N: usize>(to: &mut [U], from: &[T], f: impl Fn(T) -> U) {
+    if alpha(N) {
+        for (to, &from) in to
+            .chunks_exact_mut(N)
+            .zip(from.iter().skip(N - 1).step_by(N))
+        {
+            to[N - 1] = f(from);
+        }
+    }
+}
+
+impl<const N: usize> From<Image<&[u8], N>> for Linear<Box<[f32]>, N> {
+    /// Decode sRGB, from a lookup table.
+    fn from(value: Image<&[u8], N>) -> Self {
+        // SAFETY: length unchanged
+        Self(unsafe {
+            value.mapped(|x| {
+                let mut out = x.iter().map(|&x| LUT[x as usize]).collect::<Box<[f32]>>();
+                keep_alpha::<_, _, N>(&mut out, x, u8_to_f32);
+                out
+            })
+        })
+    }
+}
+
+impl<const N: usize> From<Image<&[f32], N>> for Linear<Box<[f32]>, N> {
+    /// Decode sRGB.
+    fn from(value: Image<&[f32], N>) -> Self {
+        // SAFETY: length unchanged
+        Self(unsafe {
+            value.mapped(|x| {
+                let mut out = mapping(x, linears, linear).into_boxed_slice();
+                keep_alpha::<_, _, N>(&mut out, x, |a| a);
+                out
+            })
+        })
+    }
+}
+
+impl<const N: usize> From<Linear<&[f32], N>> for Image<Box<[f32]>, N> {
+    /// Encode as sRGB.
+    fn from(Linear(value): Linear<&[f32], N>) -> Self {
+        // SAFETY: length unchanged
+        unsafe {
+            value.mapped(|x| {
+                let mut out = mapping(x, srgbs, srgb).into_boxed_slice();
+                keep_alpha::<_, _, N>(&mut out, x, |a| a);
+                out
+            })
+        }
+    }
+}
+
+impl<const N: usize> From<Linear<&[f32], N>> for Image<Box<[u8]>, N> {
+    /// Encode as sRGB, clamping to 0-255.
+    fn from(Linear(value): Linear<&[f32], N>) -> Self {
+        let (zero, one) = (Simd::splat(0.0), Simd::splat(1.0));
+        // SAFETY: length unchanged
+        unsafe {
+            value.mapped(|x| {
+                let mut out = mapping(
+                    x,
+                    |x| f32s_to_u8s(srgbs(x).simd_clamp(zero, one)),
+                    |x| f32_to_u8(srgb(x).clamp(0.0, 1.0)),
+                )
+                .into_boxed_slice();
+                keep_alpha::<_, _, N>(&mut out, x, |a| f32_to_u8(a.clamp(0.0, 1.0)));
+                out
+            })
+        }
+    }
+}
+
+impl<const N: usize, T> Image<T, N> {
+    /// Decode sRGB into a [`Linear`] image. Alpha is left as is.
+    pub fn to_linear<U>(&self) -> Linear<Box<[f32]>, N>
+    where
+        T: AsRef<[U]>,
+        for<'a> Linear<Box<[f32]>, N>: From<Image<&'a [U], N>>,
+    {
+        self.as_ref().into()
+    }
+}
+
+#[test]
+fn transfer() {
+    let img = Image::<_, 1>::build(4, 1).buf(vec![0.0f32, 0.5, 1.0, 0.02]);
+    let lin = img.to_linear();
+    let b = lin.image().buffer();
+    assert_eq!(b[0], 0.0);
+    assert!((b[1] - 0.214_041).abs() < 1e-5);
+    assert!((b[2] - 1.0).abs() < 1e-6);
+    assert!((b[3] - 0.02 / 12.92).abs() < 1e-7);
+    let back = lin.to_srgb::<f32>();
+    assert!(
+        back.buffer()
+            .iter()
+            .zip(img.buffer())
+            .all(|(a, b)| (a - b).abs() < 1e-5)
+    );
+}
+
+#[test]
+fn roundtrip() {
+    let cat = Image::<_, 3>::open("tdata/small_cat.png");
+    let lin = cat.to_linear();
+    assert_eq!(lin.to_srgb::<u8>().bytes(), cat.bytes());
+    // the simd path agrees with the table
+    assert!(
+        cat.to_f32()
+            .to_linear()
+            .image()
+            .buffer()
+            .iter()
+            .zip(lin.image().buffer())
+            .all(|(a, b)| (a - b).abs() < 1e-5)
+    );
+
+    let mut cat = Image::<Box<[u8]>, 4>::from(cat.as_ref());
+    cat.chunked_mut()
+        .enumerate()
+        .for_each(|(i, [.., a])| *a = i as u8);
+    let lin = cat.to_linear();
+    assert!(
+        lin.image()
+            .chunked()
+            .enumerate()
+            .all(|(i, &[.., a])| a == u8_to_f32(i as u8))
+    );
+    assert_eq!(lin.to_srgb::<u8>().bytes(), cat.bytes());
+}
@@ -0,0 +1,138 @@
+//! color matrices, like css `feColorMatrix`.
+//!
+//! a [`Matrix3`] mixes rgb, a [`Matrix4`] rgba: each row is the weights of the input channels (as 0.0-1.0), then an offset.
+//!
+//! ```
+//! # use fimg::{Image, matrix};
+//! let mut i = Image::<_, 3>::build(1, 1).buf(vec![255, 128, 0]);
+//! // swap red and blue
+//! i.mix(&[
+//!     [0.0, 0.0, 1.0, 0.0],
+//!     [0.0, 1.0, 0.0, 0.0],
+//!     [1.0, 0.0, 0.0, 0.0],
+//! ]);
+//! assert_eq!(i.bytes(), [0, 128, 255]);
+//! i.mix(&matrix::saturate(0.0));
+//! ```
+use crate::{
+    Image,
+    pixels::convert::{Luma, mix},
+};
+
+/// A color matrix for rgb: three rows of (r, g, b, offset).
+pub type Matrix3 = [[f32; 4]; 3];
+/// A color matrix for rgba: four rows of (r, g, b, a, offset).
+pub type Matrix4 = [[f32; 5]; 4];
+
+/// Changes nothing.
+pub const IDENTITY: Matrix3 = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+];
+
+/// Extend a rgb matrix to rgba, leaving alpha alone.
+pub const fn rgba([r, g, b]: Matrix3) -> Matrix4 {
+    const fn row([x, y, z, o]: [f32; 4]) -> [f32; 5] {
+        [x, y, z, 0.0, o]
+    }
+    [row(r), row(g), row(b), [0.0, 0.0, 0.0, 1.0, 0.0]]
+}
+
+/// Grey, weighed by `luma`.
+pub fn grey(luma: Luma) -> Matrix3 {
+    let [r, g, b] = luma.weights().map(|x| x as f32 / 10000.0);
+    [[r, g, b, 0.0]; 3]
+}
+
+/// Scale saturation: `0.0` is grey (by [`Luma::Rec709`]), `1.0` changes nothing, and more saturates.
+pub fn saturate(s: f32) -> Matrix3 {
+    let grey = grey(Luma::Rec709);
+    std::array::from_fn(|i| {
+        std::array::from_fn(|j| {
+            let identity = (i == j) as u8 as f32;
+            (identity - grey[i][j]).mul_add(s, grey[i][j])
+        })
+    })
+}
+
+/// The sepia tone.
+pub const SEPIA: Matrix3 = [
+    [0.393, 0.769, 0.189, 0.0],
+    [0.349, 0.686, 0.168, 0.0],
+    [0.272, 0.534, 0.131, 0.0],
+];
+
+impl<T: AsMut<[u8]> + AsRef<[u8]>> Image<T, 3> {
+    /// Mix the channels of this image with a color matrix.
+    pub fn mix(&mut self, m: &Matrix3) {
+        self.chunked_mut().for_each(|p| *p = mix(*p, m));
+    }
+}
+
+impl<T: AsMut<[u8]> + AsRef<[u8]>> Image<T, 4> {
+    /// Mix the channels of this image with a color matrix. See [`rgba`] for rgb matrices.
+    pub fn mix(&mut self, m: &Matrix4) {
+        self.chunked_mut().for_each(|p| *p = mix(*p, m));
+    }
+}
+
+impl<T: AsRef<[u8]>> Image<T, 3> {
+    /// Reduce to grey, weighed by `luma`.
+    pub fn luma(&self, luma: Luma) -> Image<Box<[u8]>, 1> {
+        // SAFETY: a third of the length, for a third of the channels
+        unsafe {
+            self.as_ref()
+                .mapped(|x| x.as_chunks::<3>().0.iter().map(|&p| luma.luma(p)).collect())
+        }
+    }
+}
+
+impl<T: AsRef<[u8]>> Image<T, 4> {
+    /// Reduce to grey, weighed by `luma`, keeping alpha.
+    pub fn luma(&self, luma: Luma) -> Image<Box<[u8]>, 2> {
+        // SAFETY: half the length, for half the channels
+        unsafe {
+            self.as_ref().mapped(|x| {
+                x.as_chunks::<4>()
+                    .0
+                    .iter()
+                    .flat_map(|&[r, g, b, a]| [luma.luma([r, g, b]), a])
+                    .collect()
+            })
+        }
+    }
+}
+
+#[test]
+fn matrices() {
+    let cat = Image::<_, 3>::open("tdata/small_cat.png");
+    let mut x = cat.clone();
+    x.mix(&IDENTITY);
+    assert_eq!(x.bytes(), cat.bytes());
+    x.mix(&saturate(1.0));
+    assert_eq!(x.bytes(), cat.bytes());
+    x.mix(&saturate(0.0));
+    let y = cat.luma(Luma::Rec709);
+    assert!(
+        x.chunked()
+            .zip(y.chunked())
+            .all(|(&[r, g, b], &[y])| r == g && g == b && r.abs_diff(y) <= 1)
+    );
+    // the default is what PFrom uses
+    assert_eq!(y.bytes(), Image::<Box<[u8]>, 1>::from(cat.as_ref()).bytes());
+
+    let mut x = Image::<_, 4>::build(1, 1).buf(vec![10, 20, 30, 40]);
+    x.mix(&rgba(SEPIA));
+    assert_eq!(x.bytes()[3], 40);
+    x.mix(&[
+        [0.0, 0.0, 0.0, 0.0, 1.0],
+        [0.0; 5],
+        [0.0; 5],
+        [0.0, 0.0, 0.0, 2.0, 0.0],
+    ]);
+    assert_eq!(x.bytes(), [255, 0, 0, 80]);
+    assert_eq!(x.luma(Luma::Average).bytes(), [85, 80]);
+    assert_eq!(Luma::Rec601.luma([255; 3]), 255);
+    assert_eq!(Luma::Rec2020.luma([255; 3]), 255);
+}
@@ -0,0 +1,201 @@
+//! image metadata, that travels alongside an [`Image`](crate::Image) or [`DynImage`](crate::DynImage).
+//!
+//! ```no_run
+//! # use fimg::{DynImage, ReadPng, WritePng};
+//! let f = &mut std::io::BufReader::new(std::fs::File::open("scan.png")?);
+//! let (image, mut meta) = DynImage::read_meta(f)?;
+//! meta.text.push(fimg::meta::Text::new("Software", "fimg"));
+//! image.write_meta(&mut std::fs::File::create("out.png")?, &meta)?;
+//! # Ok::<(), fimg::Error>(())
+//! ```
+
+/// Ancillary information about an image. Everything is optional.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metadata {
+    /// Key-value text pairs.
+    pub text: Vec<Text>,
+    /// Physical pixel dimensions.
+    pub density: Option<Density>,
+    /// An embedded ICC profile.
+    pub icc: Option<Box<[u8]>>,
+    /// The gamma of the source, as the exponent that encodes linear light (usually `1 / 2.2`).
+    pub gamma: Option<f32>,
+    /// The chromaticities of the source.
+    pub chromaticities: Option<Chromaticities>,
+}
+
+impl Metadata {
+    /// Nothing but the gamma (1 / 2.2) and chromaticities of sRGB. This is what [`WritePng::write`](crate::WritePng::write) writes.
+    pub const SRGB: Self = Self {
+        text: vec![],
+        density: None,
+        icc: None,
+        gamma: Some(0.45455),
+        chromaticities: Some(Chromaticities::SRGB),
+    };
+
+    /// Get the text for this keyword, if there is any.
+    #[must_use]
+    pub fn get(&self, keyword: &str) -> Option<&str> {
+        self.text
+            .iter()
+            .find(|t| t.keyword == keyword)
+            .map(|t| &*t.text)
+    }
+}
+
+/// A keyword and its text.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Text {
+    /// The keyword, 1-79 latin-1 characters. (e.g. `Title`, `Author`, `Description`, `Comment`)
+    pub keyword: String,
+    /// The text.
+    pub text: String,
+    /// How the text is stored.
+    pub kind: TextKind,
+}
+
+impl Text {
+    /// Create a uncompressed latin-1 text pair.
+    #[must_use]
+    pub fn new(keyword: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            keyword: keyword.into(),
+            text: text.into(),
+            kind: TextKind::Latin1,
+        }
+    }
+}
+
+/// How a [`Text`] is stored.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TextKind {
+    /// Uncompressed latin-1. (`tEXt`)
+    #[default]
+    Latin1,
+    /// Compressed latin-1. (`zTXt`)
+    CompressedLatin1,
+    /// UTF-8. (`iTXt`)
+    Utf8 {
+        /// Whether the text is compressed.
+        compressed: bool,
+        /// The language of the text, e.g. `en-us`. May be empty.
+        language: String,
+        /// The keyword, translated to that language. May be empty.
+        translated_keyword: String,
+    },
+}
+
+/// The physical size of a pixel.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Density {
+    /// Pixels per unit, horizontally.
+    pub x: u32,
+    /// Pixels per unit, vertically.
+    pub y: u32,
+    /// The unit.
+    pub unit: Unit,
+}
+
+/// The unit of a [`Density`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Unit {
+    /// No unit, the density only gives the aspect ratio of a pixel.
+    Unknown,
+    /// Pixels per meter.
+    Meter,
+}
+
+impl Density {
+    /// Create a density from dots per inch.
+    #[must_use]
+    pub const fn dpi(dpi: u32) -> Self {
+        let ppm = (dpi as f64 / 0.0254 + 0.5) as u32;
+        Self {
+            x: ppm,
+            y: ppm,
+            unit: Unit::Meter,
+        }
+    }
+
+    /// Dots per inch, as `(x, y)`, if the unit is known.
+    #[must_use]
+    pub fn to_dpi(self) -> Option<(f32, f32)> {
+        (self.unit == Unit::Meter).then_some((self.x as f32 * 0.0254, self.y as f32 * 0.0254))
+    }
+}
+
+/// The CIE xy chromaticities of the white point and the primaries.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Chromaticities {
+    /// White point.
+    pub white: (f32, f32),
+    /// Red primary.
+    pub red: (f32, f32),
+    /// Green primary.
+    pub green: (f32, f32),
+    /// Blue primary.
+    pub blue: (f32, f32),
+}
+
+impl Chromaticities {
+    /// The chromaticities of sRGB (and rec.709).
+    pub const SRGB: Self = Self {
+        white: (0.31270, 0.32900),
+        red: (0.64000, 0.33000),
+        green: (0.30000, 0.60000),
+        blue: (0.15000, 0.06000),
+    };
+}
+
+#[cfg(feature = "save")]
+#[test]
+fn png() {
+    use crate::{DynImage, Image, ReadPng, WritePng};
+    let i = Image::<_, 3>::open("tdata/small_cat.png");
+    let meta = Metadata {
+        text: vec![
+            Text::new("Title", "cat"),
+            Text {
+                keyword: "Comment".into(),
+                text: "a small cat".repeat(20),
+                kind: TextKind::CompressedLatin1,
+            },
+            Text {
+                keyword: "Description".into(),
+                text: "ねこ".into(),
+                kind: TextKind::Utf8 {
+                    compressed: true,
+                    language: "ja".into(),
+                    translated_keyword: "説明".into(),
+                },
+            },
+        ],
+        density: Some(Density::dpi(300)),
+        icc: Some(Box::new([1, 2, 3, 4])),
+        gamma: Some(1.0),
+        chromaticities: Some(Chromaticities {
+            white: (0.3127, 0.329),
+            red: (0.708, 0.292),
+            green: (0.17, 0.797),
+            blue: (0.131, 0.046),
+        }),
+    };
+    let mut out = vec![];
+    i.write_meta(&mut out, &meta).unwrap();
+    let (d, m) = DynImage::read_meta(&mut std::io::Cursor::new(&out)).unwrap();
+    assert_eq!(d.rgb().bytes(), i.bytes());
+    assert_eq!(m, meta);
+    assert_eq!(m.get("Title"), Some("cat"));
+    assert_eq!(m.density.unwrap().to_dpi().unwrap().0.round(), 300.0);
+
+    let mut out = vec![];
+    i.write(&mut out).unwrap();
+    let (_, m) = Image::<Box<[u8]>, 3>::read_meta(&mut std::io::Cursor::new(&out)).unwrap();
+    assert_eq!(m, Metadata::SRGB);
+
+    // a broken end after the image data still decodes
+    out.truncate(out.len() - 4);
+    let (d, _) = DynImage::read_meta(&mut std::io::Cursor::new(&out)).unwrap();
+    assert_eq!(d.rgb().bytes(), i.bytes());
+}
@@ -1,6 +1,6 @@
 //! Handles image overlay
 // TODO Y/YA
-use crate::{DynImage, cloner::ImageCloner, uninit};
+use crate::{Buffer, DynImage, cloner::ImageCloner, uninit};
 
 use super::{Image, assert_unchecked};
 use crate::pixels::Blend;
@@ -551,41 +551,47 @@ impl ClonerOverlayAt<4, 4> for ImageCloner<'_, 4> {
     }
 }
 
-impl<T: AsMut<[u8]> + AsRef<[u8]>, U: AsRef<[u8]>> OverlayAt<DynImage<U>> for Image<T, 3> {
+impl<T: AsMut<[u8]> + AsRef<[u8]>, U: AsRef<[u8]> + Buffer> OverlayAt<DynImage<U>> for Image<T, 3> {
     unsafe fn overlay_at(&mut self, with: &DynImage<U>, x: u32, y: u32) -> &mut Self {
-        crate::r#dyn::e!(with, |with| unsafe {
+        crate::r#dyn::e!(narrow with, |with| unsafe {
             self.overlay_at(with, x, y);
         });
         self
     }
 }
 
-impl<T: AsMut<[u8]> + AsRef<[u8]>, U: AsRef<[u8]>> OverlayAt<DynImage<U>> for Image<T, 4> {
+impl<T: AsMut<[u8]> + AsRef<[u8]>, U: AsRef<[u8]> + Buffer> OverlayAt<DynImage<U>> for Image<T, 4> {
     unsafe fn overlay_at(&mut self, with: &DynImage<U>, x: u32, y: u32) -> &mut Self {
-        crate::r#dyn::e!(with, |with| unsafe {
+        crate::r#dyn::e!(narrow with, |with| unsafe {
             self.overlay_at(with, x, y);
         });
         self
     }
 }
-impl<T: AsRef<[u8]>, U: AsRef<[u8]> + AsMut<[u8]>> OverlayAtClipping<DynImage<T>> for Image<U, 3> {
+impl<T: AsRef<[u8]> + Buffer, U: AsRef<[u8]> + AsMut<[u8]>> OverlayAtClipping<DynImage<T>>
+    for Image<U, 3>
+{
     fn clipping_overlay_at(&mut self, with: &DynImage<T>, x: u32, y: u32) -> &mut Self {
-        crate::r#dyn::e!(with, |with| { self.clipping_overlay_at(with, x, y) });
+        crate::r#dyn::e!(narrow with, |with| self.clipping_overlay_at(with, x, y));
         self
     }
 }
-impl<T: AsRef<[u8]>, U: AsRef<[u8]> + AsMut<[u8]>> OverlayAtClipping<DynImage<T>> for Image<U, 4> {
+impl<T: AsRef<[u8]> + Buffer, U: AsRef<[u8]> + AsMut<[u8]>> OverlayAtClipping<DynImage<T>>
+    for Image<U, 4>
+{
     fn clipping_overlay_at(&mut self, with: &DynImage<T>, x: u32, y: u32) -> &mut Self {
-        crate::r#dyn::e!(with, |with| { self.clipping_overlay_at(with, x, y) });
+        crate::r#dyn::e!(narrow with, |with| self.clipping_overlay_at(with, x, y));
         self
     }
 }
 
-impl<U: AsRef<[u8]>> OverlayAt<DynImage<U>> for uninit::Image<u8, 3> {
+impl<U: AsRef<[u8]> + Buffer> OverlayAt<DynImage<U>> for uninit::Image<u8, 3> {
     unsafe fn overlay_at(&mut self, with: &DynImage<U>, x: u32, y: u32) -> &mut Self {
         match with {
             DynImage::Rgb(with) => unsafe { self.overlay_at(with, x, y) },
             DynImage::Rgba(with) => unsafe { self.overlay_at(with, x, y) },
+            DynImage::Rgb16(with) => unsafe { self.overlay_at(&with.to_u8(), x, y) },
+            DynImage::Rgba16(with) => unsafe { self.overlay_at(&with.to_u8(), x, y) },
             _ => unimplemented!(),
         };
         self
@@ -1,14 +1,20 @@
 //! provides From's for pixels.
+use crate::{
+    linear::{linear, srgb},
+    math::madd,
+};
 use atools::prelude::*;
 
 /// Converts a pixel to another pixel.
-pub trait PFrom<const N: usize> {
+///
+/// `C` is the component type (`u8`, or `u16` for 16 bit pixels).
+pub trait PFrom<const N: usize, C = u8> {
     /// Convert a pixel to this pixel.
-    fn pfrom(f: [u8; N]) -> Self;
+    fn pfrom(f: [C; N]) -> Self;
 }
 
-impl<const N: usize> PFrom<N> for [u8; N] {
-    fn pfrom(f: [u8; N]) -> Self {
+impl<const N: usize, C> PFrom<N, C> for [C; N] {
+    fn pfrom(f: [C; N]) -> Self {
         f
     }
 }
@@ -22,8 +28,8 @@ impl PFrom<2> for Y {
 }
 
 impl PFrom<3> for Y {
-    fn pfrom([r, g, b]: RGB) -> Self {
-        [((2126 * r as u32 + 7152 * g as u32 + 722 * b as u32) / 10000) as u8]
+    fn pfrom(f: RGB) -> Self {
+        [Luma::Rec709.luma(f)]
     }
 }
 
@@ -33,6 +39,51 @@ impl PFrom<4> for Y {
     }
 }
 
+/// How to weigh red, green and blue when reducing to grey.
+/// [`PFrom`] uses [`Luma::Rec709`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Luma {
+    /// BT.601, for standard definition video (and jpeg).
+    Rec601,
+    /// BT.709, for sRGB and HD video.
+    #[default]
+    Rec709,
+    /// BT.2020, for UHD video.
+    Rec2020,
+    /// The plain average of the channels.
+    Average,
+}
+
+impl Luma {
+    /// The weights, in ten thousandths.
+    pub const fn weights(self) -> [u32; 3] {
+        match self {
+            Self::Rec601 => [2990, 5870, 1140],
+            Self::Rec709 => [2126, 7152, 722],
+            Self::Rec2020 => [2627, 6780, 593],
+            Self::Average => [3333, 3334, 3333],
+        }
+    }
+
+    const fn weigh(self, [r, g, b]: [u32; 3]) -> u32 {
+        let [wr, wg, wb] = self.weights();
+        match self {
+            Self::Average => (r + g + b) / 3,
+            _ => (wr * r + wg * g + wb * b) / 10000,
+        }
+    }
+
+    /// The grey of a pixel.
+    pub fn luma(self, rgb: RGB) -> u8 {
+        self.weigh(rgb.map(u32::from)) as u8
+    }
+
+    /// The grey of a 16 bit pixel.
+    pub fn luma16(self, rgb: RGB16) -> u16 {
+        self.weigh(rgb.map(u32::from)) as u16
+    }
+}
+
 /// YA pixel
 pub type YA = [u8; 2];
 impl PFrom<1> for YA {
@@ -94,3 +145,293 @@ impl PFrom<3> for RGBA {
         f.join(255)
     }
 }
+
+/// 16 bit Y pixel
+pub type Y16 = [u16; 1];
+impl PFrom<2, u16> for Y16 {
+    fn pfrom(f: YA16) -> Self {
+        f.init()
+    }
+}
+
+impl PFrom<3, u16> for Y16 {
+    fn pfrom(f: RGB16) -> Self {
+        [Luma::Rec709.luma16(f)]
+    }
+}
+
+impl PFrom<4, u16> for Y16 {
+    fn pfrom(f: RGBA16) -> Self {
+        PFrom::pfrom(f.init())
+    }
+}
+
+/// 16 bit YA pixel
+pub type YA16 = [u16; 2];
+impl PFrom<1, u16> for YA16 {
+    fn pfrom(f: Y16) -> Self {
+        f.join(u16::MAX)
+    }
+}
+
+impl PFrom<3, u16> for YA16 {
+    fn pfrom(f: RGB16) -> Self {
+        Y16::pfrom(f).join(u16::MAX)
+    }
+}
+
+impl PFrom<4, u16> for YA16 {
+    fn pfrom(f: RGBA16) -> Self {
+        Y16::pfrom(f.init()).join(u16::MAX)
+    }
+}
+
+/// 16 bit RGB pixel
+pub type RGB16 = [u16; 3];
+
+impl PFrom<1, u16> for RGB16 {
+    fn pfrom([y]: Y16) -> Self {
+        [y; 3]
+    }
+}
+
+impl PFrom<2, u16> for RGB16 {
+    fn pfrom([y, _]: YA16) -> Self {
+        [y; 3]
+    }
+}
+
+impl PFrom<4, u16> for RGB16 {
+    fn pfrom(f: RGBA16) -> Self {
+        f.init()
+    }
+}
+
+/// 16 bit RGBA pixel
+pub type RGBA16 = [u16; 4];
+
+impl PFrom<1, u16> for RGBA16 {
+    fn pfrom([y]: Y16) -> Self {
+        [y; 3].join(u16::MAX)
+    }
+}
+
+impl PFrom<2, u16> for RGBA16 {
+    fn pfrom([y, a]: YA16) -> Self {
+        [y; 3].join(a)
+    }
+}
+
+impl PFrom<3, u16> for RGBA16 {
+    fn pfrom(f: RGB16) -> Self {
+        f.join(u16::MAX)
+    }
+}
+
+/// Mix the channels of a pixel with a (css `feColorMatrix` style) matrix, of `N` rows of `N + 1` columns.
+///
+/// Each output channel is a weighted sum of the input channels (as 0.0-1.0), plus the last column, clamped.
+pub fn mix<const N: usize>(px: [u8; N], m: &[[f32; N + 1]; N]) -> [u8; N]
+where
+    [(); N + 1]:,
+{
+    let px = px.map(|x| x as f32 * (1.0 / 255.0));
+    m.map(|row| {
+        let x = px
+            .iter()
+            .zip(&row)
+            .fold(row[N], |acc, (&x, &w)| madd(x, w, acc));
+        (x.clamp(0.0, 1.0) * 255.0).round() as u8
+    })
+}
+
+// perceptual color spaces. all of these take and give sRGB as 0.0-1.0.
+
+/// The hue (in degrees) of some rgb, with its maximum and chroma.
+fn hue([r, g, b]: [f32; 3], max: f32, chroma: f32) -> f32 {
+    if chroma == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / chroma).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / chroma + 2.0)
+    } else {
+        60.0 * ((r - g) / chroma + 4.0)
+    }
+}
+
+/// sRGB to HSV: hue in degrees (0-360), saturation and value 0.0-1.0.
+pub fn rgb_to_hsv(rgb @ [r, g, b]: [f32; 3]) -> [f32; 3] {
+    let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+    let s = if max == 0.0 { 0.0 } else { (max - min) / max };
+    [hue(rgb, max, max - min), s, max]
+}
+
+/// HSV to sRGB. The inverse of [`rgb_to_hsv`].
+pub fn hsv_to_rgb([h, s, v]: [f32; 3]) -> [f32; 3] {
+    let f = |n: f32| {
+        let k = (n + h / 60.0).rem_euclid(6.0);
+        (-v * s).mul_add(k.min(4.0 - k).clamp(0.0, 1.0), v)
+    };
+    [f(5.0), f(3.0), f(1.0)]
+}
+
+/// sRGB to HSL: hue in degrees (0-360), saturation and lightness 0.0-1.0.
+pub fn rgb_to_hsl(rgb @ [r, g, b]: [f32; 3]) -> [f32; 3] {
+    let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+    let l = (max + min) / 2.0;
+    let s = if max == min {
+        0.0
+    } else {
+        (max - min) / (1.0 - 2.0f32.mul_add(l, -1.0).abs())
+    };
+    [hue(rgb, max, max - min), s, l]
+}
+
+/// HSL to sRGB. The inverse of [`rgb_to_hsl`].
+pub fn hsl_to_rgb([h, s, l]: [f32; 3]) -> [f32; 3] {
+    let a = s * l.min(1.0 - l);
+    let f = |n: f32| {
+        let k = (n + h / 30.0).rem_euclid(12.0);
+        (-a).mul_add((k - 3.0).min(9.0 - k).clamp(-1.0, 1.0), l)
+    };
+    [f(0.0), f(8.0), f(4.0)]
+}
+
+fn mul(m: [[f32; 3]; 3], v: [f32; 3]) -> [f32; 3] {
+    m.map(|[a, b, c]| madd(a, v[0], madd(b, v[1], c * v[2])))
+}
+
+/// linear sRGB to CIE XYZ (D65).
+#[allow(clippy::excessive_precision)]
+const XYZ: [[f32; 3]; 3] = [
+    [0.4124564, 0.3575761, 0.1804375],
+    [0.2126729, 0.7151522, 0.0721750],
+    [0.0193339, 0.1191920, 0.9503041],
+];
+
+/// CIE XYZ (D65) to linear sRGB.
+#[allow(clippy::excessive_precision)]
+const XYZ_INV: [[f32; 3]; 3] = [
+    [3.2404542, -1.5371385, -0.4985314],
+    [-0.9692660, 1.8760108, 0.0415560],
+    [0.0556434, -0.2040259, 1.0572252],
+];
+
+/// The D65 white point.
+const WHITE: [f32; 3] = [0.95047, 1.0, 1.08883];
+
+/// sRGB to CIELAB (D65): L 0-100, a and b roughly -128-128.
+pub fn rgb_to_lab(rgb: [f32; 3]) -> [f32; 3] {
+    const E: f32 = 216.0 / 24389.0;
+    let f = |t: f32| {
+        if t > E {
+            t.cbrt()
+        } else {
+            t.mul_add(24389.0 / 27.0 / 116.0, 16.0 / 116.0)
+        }
+    };
+    let xyz = mul(XYZ, rgb.map(linear));
+    let [x, y, z] = std::array::from_fn(|i| f(xyz[i] / WHITE[i]));
+    [116.0f32.mul_add(y, -16.0), 500.0 * (x - y), 200.0 * (y - z)]
+}
+
+/// CIELAB (D65) to sRGB. The inverse of [`rgb_to_lab`].
+pub fn lab_to_rgb([l, a, b]: [f32; 3]) -> [f32; 3] {
+    let f = |t: f32| {
+        if t > 6.0 / 29.0 {
+            t * t * t
+        } else {
+            t.mul_add(116.0, -16.0) * 27.0 / 24389.0
+        }
+    };
+    let y = (l + 16.0) / 116.0;
+    let xyz = [a.mul_add(1.0 / 500.0, y), y, b.mul_add(-1.0 / 200.0, y)];
+    mul(XYZ_INV, std::array::from_fn(|i| f(xyz[i]) * WHITE[i])).map(srgb)
+}
+
+/// linear sRGB to LMS.
+#[allow(clippy::excessive_precision)]
+const LMS: [[f32; 3]; 3] = [
+    [0.4122214708, 0.5363325363, 0.0514459929],
+    [0.2119034982, 0.6806995451, 0.1073969566],
+    [0.0883024619, 0.2817188376, 0.6299787005],
+];
+
+/// cube rooted LMS to OKLab.
+#[allow(clippy::excessive_precision)]
+const OKLAB: [[f32; 3]; 3] = [
+    [0.2104542553, 0.7936177850, -0.0040720468],
+    [1.9779984951, -2.4285922050, 0.4505937099],
+    [0.0259040371, 0.7827717662, -0.8086757660],
+];
+
+/// OKLab to cube rooted LMS.
+#[allow(clippy::excessive_precision)]
+const OKLAB_INV: [[f32; 3]; 3] = [
+    [1.0, 0.3963377774, 0.2158037573],
+    [1.0, -0.1055613458, -0.0638541728],
+    [1.0, -0.0894841775, -1.2914855480],
+];
+
+/// LMS to linear sRGB.
+#[allow(clippy::excessive_precision)]
+const LMS_INV: [[f32; 3]; 3] = [
+    [4.0767416621, -3.3077115913, 0.2309699292],
+    [-1.2684380046, 2.6097574011, -0.3413193965],
+    [-0.0041960863, -0.7034186147, 1.7076147010],
+];
+
+/// sRGB to [OKLab](https://bottosson.github.io/posts/oklab/): L 0.0-1.0, a and b roughly -0.4-0.4.
+pub fn rgb_to_oklab(rgb: [f32; 3]) -> [f32; 3] {
+    mul(OKLAB, mul(LMS, rgb.map(linear)).map(f32::cbrt))
+}
+
+/// OKLab to sRGB. The inverse of [`rgb_to_oklab`].
+pub fn oklab_to_rgb(lab: [f32; 3]) -> [f32; 3] {
+    mul(LMS_INV, mul(OKLAB_INV, lab).map(|x| x * x * x)).map(srgb)
+}
+
+/// sRGB to OKLCH, the polar form of OKLab: L 0.0-1.0, chroma 0.0-0.4, hue in degrees (0-360).
+pub fn rgb_to_oklch(rgb: [f32; 3]) -> [f32; 3] {
+    let [l, a, b] = rgb_to_oklab(rgb);
+    [l, a.hypot(b), b.atan2(a).to_degrees().rem_euclid(360.0)]
+}
+
+/// OKLCH to sRGB. The inverse of [`rgb_to_oklch`].
+pub fn oklch_to_rgb([l, c, h]: [f32; 3]) -> [f32; 3] {
+    let (sin, cos) = h.to_radians().sin_cos();
+    oklab_to_rgb([l, c * cos, c * sin])
+}
+
+#[test]
+fn spaces() {
+    let close = |a: [f32; 3], b: [f32; 3], e: f32| a.iter().zip(b).all(|(a, b)| (a - b).abs() < e);
+    assert!(close(rgb_to_hsv([1.0, 0.5, 0.0]), [30.0, 1.0, 1.0], 1e-4));
+    assert!(close(rgb_to_hsl([0.0, 0.0, 1.0]), [240.0, 1.0, 0.5], 1e-4));
+    assert!(close(rgb_to_lab([1.0; 3]), [100.0, 0.0, 0.0], 1e-2));
+    assert!(close(
+        rgb_to_lab([1.0, 0.0, 0.0]),
+        [53.24, 80.09, 67.20],
+        5e-2
+    ));
+    assert!(close(rgb_to_oklab([1.0; 3]), [1.0, 0.0, 0.0], 1e-3));
+    assert!(close(
+        rgb_to_oklch([1.0, 0.0, 0.0]),
+        [0.628, 0.2577, 29.23],
+        1e-2
+    ));
+    for rgb in [
+        [0.0; 3],
+        [1.0; 3],
+        [0.2, 0.4, 0.9],
+        [0.9, 0.1, 0.5],
+        [0.5, 0.5, 0.2],
+    ] {
+        assert!(close(hsv_to_rgb(rgb_to_hsv(rgb)), rgb, 1e-5));
+        assert!(close(hsl_to_rgb(rgb_to_hsl(rgb)), rgb, 1e-5));
+        assert!(close(lab_to_rgb(rgb_to_lab(rgb)), rgb, 1e-4));
+        assert!(close(oklab_to_rgb(rgb_to_oklab(rgb)), rgb, 1e-4));
+        assert!(close(oklch_to_rgb(rgb_to_oklch(rgb)), rgb, 1e-4));
+    }
+}
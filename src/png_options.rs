@@ -0,0 +1,519 @@
+//! png encoder options, and the reductions and interlacing that the png crate does not do for us.
+use crate::{Error, meta::Metadata};
+use std::collections::HashMap;
+
+/// What [`PngOptions::new`] writes.
+static SRGB: Metadata = Metadata::SRGB;
+
+/// How hard to compress a png.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PngCompression {
+    /// Store the data uncompressed.
+    None,
+    /// Fast, but light compression.
+    Fast,
+    /// A balance of speed and size.
+    #[default]
+    Balanced,
+    /// Slow, for the smallest files.
+    Best,
+}
+
+/// How the rows of a png are filtered, before compression.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PngFilter {
+    /// No filtering.
+    None,
+    /// Difference to the pixel on the left.
+    Sub,
+    /// Difference to the pixel above.
+    Up,
+    /// Difference to the average of the left and above pixels.
+    Average,
+    /// Difference to the paeth predictor.
+    Paeth,
+    /// Pick the best filter for each row.
+    #[default]
+    Adaptive,
+}
+
+/// Png encoder options, for [`WritePng::write_with`](crate::WritePng::write_with) and [`Image::try_save_with`](crate::Image::try_save_with).
+///
+/// ```
+/// # use fimg::{PngOptions, PngCompression};
+/// let options = PngOptions::new()
+///     .compression(PngCompression::Best)
+///     .interlace(true)
+///     .reduce(true);
+/// ```
+#[must_use]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PngOptions<'a> {
+    pub(crate) compression: PngCompression,
+    pub(crate) filter: PngFilter,
+    pub(crate) interlace: bool,
+    pub(crate) reduce: bool,
+    pub(crate) meta: &'a Metadata,
+}
+
+impl PngOptions<'static> {
+    /// Default options: balanced compression, adaptive filtering, no interlacing, no reduction, and the sRGB [`Metadata::SRGB`].
+    pub const fn new() -> Self {
+        Self {
+            compression: PngCompression::Balanced,
+            filter: PngFilter::Adaptive,
+            interlace: false,
+            reduce: false,
+            meta: &SRGB,
+        }
+    }
+}
+
+impl Default for PngOptions<'static> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> PngOptions<'a> {
+    /// Set the compression level.
+    pub const fn compression(self, compression: PngCompression) -> Self {
+        Self {
+            compression,
+            ..self
+        }
+    }
+
+    /// Set the row filter.
+    pub const fn filter(self, filter: PngFilter) -> Self {
+        Self { filter, ..self }
+    }
+
+    /// Write an Adam7 interlaced png, which can be shown at a lower detail before it has fully loaded.
+    pub const fn interlace(self, interlace: bool) -> Self {
+        Self { interlace, ..self }
+    }
+
+    /// Write the image with the smallest color type and bit depth that can represent it exactly.
+    ///
+    /// 16 bit images that fit in 8 bits become 8 bit, opaque images lose their alpha,
+    /// gray images lose their color, and images with at most 256 colors become indexed.
+    pub const fn reduce(self, reduce: bool) -> Self {
+        Self { reduce, ..self }
+    }
+
+    /// Write this metadata, instead of [`Metadata::SRGB`].
+    pub const fn meta<'b>(self, meta: &'b Metadata) -> PngOptions<'b> {
+        PngOptions { meta, ..self }
+    }
+}
+
+/// Pixel data, one byte per sample below 8 bits, big endian at 16 bits.
+struct Raw<'a> {
+    data: std::borrow::Cow<'a, [u8]>,
+    channels: usize,
+    depth: u8,
+    /// rgb palette and alpha
+    palette: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl Raw<'_> {
+    const fn color(&self) -> png::ColorType {
+        use png::ColorType::*;
+        match (&self.palette, self.channels) {
+            (Some(_), _) => Indexed,
+            (None, 1) => Grayscale,
+            (None, 2) => GrayscaleAlpha,
+            (None, 3) => Rgb,
+            _ => Rgba,
+        }
+    }
+
+    /// Bytes per pixel, unpacked.
+    const fn bpp(&self) -> usize {
+        self.channels * if self.depth == 16 { 2 } else { 1 }
+    }
+
+    /// Keep these samples of every pixel.
+    fn keep(&mut self, samples: &[usize]) {
+        let s = self.bpp() / self.channels;
+        self.data = self
+            .data
+            .chunks_exact(self.bpp())
+            .flat_map(|p| samples.iter().flat_map(move |&i| &p[i * s..(i + 1) * s]))
+            .copied()
+            .collect();
+        self.channels = samples.len();
+    }
+
+    fn reduce(&mut self) {
+        if self.depth == 16 && self.data.as_chunks::<2>().0.iter().all(|[a, b]| a == b) {
+            self.data = self.data.iter().step_by(2).copied().collect();
+            self.depth = 8;
+        }
+        let (bpp, s) = (self.bpp(), self.bpp() / self.channels);
+        if self.channels.is_multiple_of(2)
+            && self
+                .data
+                .chunks_exact(bpp)
+                .all(|p| p[bpp - s..].iter().all(|&x| x == 255))
+        {
+            self.keep(&[0, 1, 2][..self.channels - 1]);
+        }
+        let (bpp, s) = (self.bpp(), self.bpp() / self.channels);
+        if self.channels >= 3
+            && self
+                .data
+                .chunks_exact(bpp)
+                .all(|p| p[..s] == p[s..2 * s] && p[..s] == p[2 * s..3 * s])
+        {
+            self.keep(&[0, 3][..self.channels - 2]);
+        }
+        if self.depth != 8 {
+            return;
+        }
+
+        let gray = (self.channels == 1)
+            .then(|| {
+                [1, 2, 4].into_iter().find(|&d| {
+                    let step = 255 / ((1 << d) - 1);
+                    self.data.iter().all(|&x| x % step == 0)
+                })
+            })
+            .flatten();
+        if let Some(d) = gray {
+            let step = 255 / ((1 << d) - 1);
+            self.data = self.data.iter().map(|x| x / step).collect();
+            self.depth = d;
+            return;
+        }
+
+        let mut colors = HashMap::<[u8; 4], u8>::new();
+        let rgba = |p: &[u8]| match *p {
+            [y] => [y, y, y, 255],
+            [y, a] => [y, y, y, a],
+            [r, g, b] => [r, g, b, 255],
+            [r, g, b, a] => [r, g, b, a],
+            _ => unreachable!(),
+        };
+        for p in self.data.chunks_exact(self.channels) {
+            let n = colors.len();
+            if n == 256 && !colors.contains_key(&rgba(p)) {
+                return;
+            }
+            colors.entry(rgba(p)).or_insert(n as u8);
+        }
+        let depth = index_depth(colors.len());
+        if self.channels == 1 && depth == 8 {
+            return;
+        }
+        let mut entries = colors.into_iter().collect::<Vec<_>>();
+        // by first appearance, with the transparent entries first, so that the tRNS chunk can be short
+        entries.sort_by_key(|&(c, i)| (c[3] == 255, i));
+        let index = entries
+            .iter()
+            .enumerate()
+            .map(|(i, &(c, _))| (c, i as u8))
+            .collect::<HashMap<_, _>>();
+        let palette = entries.iter().flat_map(|(c, _)| &c[..3]).copied().collect();
+        let alpha = entries
+            .iter()
+            .map(|(c, _)| c[3])
+            .take_while(|&a| a != 255)
+            .collect();
+        self.data = self
+            .data
+            .chunks_exact(self.channels)
+            .map(|p| index[&rgba(p)])
+            .collect();
+        self.channels = 1;
+        self.depth = depth;
+        self.palette = Some((palette, alpha));
+    }
+
+    /// Pack (at most 8 bit) rows of this many pixels into bytes, each row beginning on a byte.
+    fn pack(&self, width: usize) -> std::borrow::Cow<'_, [u8]> {
+        if self.depth >= 8 {
+            return std::borrow::Cow::Borrowed(&self.data);
+        }
+        let per = 8 / self.depth as usize;
+        self.data
+            .chunks_exact(width)
+            .flat_map(|row| {
+                row.chunks(per).map(|x| {
+                    x.iter().enumerate().fold(0, |acc, (i, &v)| {
+                        acc | v << (8 - self.depth as usize * (i + 1))
+                    })
+                })
+            })
+            .collect()
+    }
+}
+
+/// The smallest bit depth that can index a palette of this size.
+const fn index_depth(n: usize) -> u8 {
+    match n {
+        0..=2 => 1,
+        3..=4 => 2,
+        5..=16 => 4,
+        _ => 8,
+    }
+}
+
+/// Filter a row, using the previous one, appending the filter type and the filtered row to `out`.
+pub(crate) fn filter(kind: PngFilter, bpp: usize, prev: &[u8], row: &[u8], out: &mut Vec<u8>) {
+    let left = |i: usize| if i >= bpp { row[i - bpp] } else { 0 };
+    let corner = |i: usize| if i >= bpp { prev[i - bpp] } else { 0 };
+    let paeth = |a: u8, b: u8, c: u8| {
+        let p = a as i16 + b as i16 - c as i16;
+        let (pa, pb, pc) = (
+            (p - a as i16).abs(),
+            (p - b as i16).abs(),
+            (p - c as i16).abs(),
+        );
+        if pa <= pb && pa <= pc {
+            a
+        } else if pb <= pc {
+            b
+        } else {
+            c
+        }
+    };
+    let apply = |kind: PngFilter, i: usize| {
+        let x = row[i];
+        match kind {
+            PngFilter::Sub => x.wrapping_sub(left(i)),
+            PngFilter::Up => x.wrapping_sub(prev[i]),
+            PngFilter::Average => x.wrapping_sub(((left(i) as u16 + prev[i] as u16) / 2) as u8),
+            PngFilter::Paeth => x.wrapping_sub(paeth(left(i), prev[i], corner(i))),
+            _ => x,
+        }
+    };
+    let kind = match kind {
+        PngFilter::Adaptive => [
+            PngFilter::None,
+            PngFilter::Sub,
+            PngFilter::Up,
+            PngFilter::Average,
+            PngFilter::Paeth,
+        ]
+        .into_iter()
+        .min_by_key(|&k| {
+            (0..row.len())
+                .map(|i| (apply(k, i) as i8).unsigned_abs() as u64)
+                .sum::<u64>()
+        })
+        .unwrap(),
+        k => k,
+    };
+    out.push(match kind {
+        PngFilter::Sub => 1,
+        PngFilter::Up => 2,
+        PngFilter::Average => 3,
+        PngFilter::Paeth => 4,
+        _ => 0,
+    });
+    out.extend((0..row.len()).map(|i| apply(kind, i)));
+}
+
+/// The filtered, compressed, Adam7 interlaced image data.
+fn interlace(raw: &Raw, (width, height): (usize, usize), options: &PngOptions) -> Vec<u8> {
+    const PASSES: [(usize, usize, usize, usize); 7] = [
+        (0, 0, 8, 8),
+        (4, 0, 8, 8),
+        (0, 4, 4, 8),
+        (2, 0, 4, 4),
+        (0, 2, 2, 4),
+        (1, 0, 2, 2),
+        (0, 1, 1, 2),
+    ];
+    let bpp = raw.bpp();
+    let filter_bpp = (raw.channels * raw.depth as usize).div_ceil(8);
+    let mut out = vec![];
+    for (x0, y0, dx, dy) in PASSES {
+        let (w, h) = (
+            width.saturating_sub(x0).div_ceil(dx),
+            height.saturating_sub(y0).div_ceil(dy),
+        );
+        if w == 0 || h == 0 {
+            continue;
+        }
+        let pass = Raw {
+            data: (y0..height)
+                .step_by(dy)
+                .flat_map(|y| (x0..width).step_by(dx).map(move |x| y * width + x))
+                .flat_map(|i| &raw.data[i * bpp..(i + 1) * bpp])
+                .copied()
+                .collect(),
+            channels: raw.channels,
+            depth: raw.depth,
+            palette: None,
+        };
+        let packed = pass.pack(w);
+        let stride = packed.len() / h;
+        let mut prev = &vec![0; stride][..];
+        for row in packed.chunks_exact(stride) {
+            filter(options.filter, filter_bpp, prev, row, &mut out);
+            prev = row;
+        }
+    }
+    miniz_oxide::deflate::compress_to_vec_zlib(&out, level(options.compression))
+}
+
+/// The zlib level of a compression.
+pub(crate) const fn level(compression: PngCompression) -> u8 {
+    match compression {
+        PngCompression::None => 0,
+        PngCompression::Fast => 1,
+        PngCompression::Balanced => 6,
+        PngCompression::Best => 9,
+    }
+}
+
+/// Write a png of (8 bit, or big endian 16 bit) samples.
+pub(crate) fn write(
+    f: &mut impl std::io::Write,
+    (width, height): (u32, u32),
+    (channels, depth): (usize, u8),
+    data: &[u8],
+    options: &PngOptions,
+) -> Result<(), Error> {
+    let mut raw = Raw {
+        data: data.into(),
+        channels,
+        depth,
+        palette: None,
+    };
+    if options.reduce {
+        raw.reduce();
+    }
+    write_raw(f, (width, height), &raw, options)
+}
+
+/// Write a png of palette indices, with a (at most 256 entry) rgb palette, and the alpha of its first entries.
+pub(crate) fn write_indexed(
+    f: &mut impl std::io::Write,
+    (width, height): (u32, u32),
+    indices: &[u8],
+    (palette, alpha): (Vec<u8>, Vec<u8>),
+    options: &PngOptions,
+) -> Result<(), Error> {
+    let raw = Raw {
+        data: indices.into(),
+        channels: 1,
+        depth: index_depth(palette.len() / 3),
+        palette: Some((palette, alpha)),
+    };
+    write_raw(f, (width, height), &raw, options)
+}
+
+fn write_raw(
+    f: &mut impl std::io::Write,
+    (width, height): (u32, u32),
+    raw: &Raw,
+    options: &PngOptions,
+) -> Result<(), Error> {
+    let depth = png::BitDepth::from_u8(raw.depth).unwrap();
+    let mut enc = crate::png_encoder(f, (width, height), (raw.color(), depth), options);
+    if let Some((palette, alpha)) = &raw.palette {
+        enc.set_palette(&palette[..]);
+        if !alpha.is_empty() {
+            enc.set_trns(&alpha[..]);
+        }
+    }
+    let mut w = enc.write_header()?;
+    if options.interlace {
+        let data = interlace(raw, (width as usize, height as usize), options);
+        for chunk in data.chunks(1 << 30) {
+            w.write_chunk(png::chunk::IDAT, chunk)?;
+        }
+    } else {
+        w.write_image_data(&raw.pack(width as usize))?;
+    }
+    w.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DynImage, Image, ReadPng, WritePng};
+
+    fn roundtrip<const N: usize>(
+        i: &Image<Box<[u8]>, N>,
+        options: &PngOptions,
+    ) -> (DynImage<Box<[u8]>>, png::ColorType, png::BitDepth, bool)
+    where
+        Image<Box<[u8]>, N>: WritePng,
+    {
+        let mut out = vec![];
+        i.write_with(&mut out, options).unwrap();
+        let info = png::Decoder::new(std::io::Cursor::new(&out))
+            .read_info()
+            .unwrap()
+            .info()
+            .clone();
+        let d = DynImage::read(&mut std::io::Cursor::new(&out)).unwrap();
+        (d, info.color_type, info.bit_depth, info.interlaced)
+    }
+
+    #[test]
+    fn reduce() {
+        use png::{BitDepth as B, ColorType as C};
+        let o = &PngOptions::new().reduce(true);
+        let cat = Image::<Vec<u8>, 3>::open("tdata/small_cat.png").boxed();
+        let (d, c, b, _) = roundtrip(&cat, o);
+        assert_eq!((c, b), (C::Rgb, B::Eight));
+        assert_eq!(d.rgb().bytes(), cat.bytes());
+
+        // opaque rgba
+        let rgba = DynImage::Rgb(cat.as_ref()).rgba();
+        let (d, c, _, _) = roundtrip(&rgba, o);
+        assert_eq!(c, C::Rgb);
+        assert_eq!(d.rgba().bytes(), rgba.bytes());
+
+        // 3 colors, one transparent
+        let px = [[255, 0, 0, 255], [0, 0, 255, 128], [0, 255, 0, 255]];
+        let few = Image::<Box<[u8]>, 4>::build(7, 5).buf((0..35).flat_map(|i| px[i % 3]).collect());
+        let (d, c, b, _) = roundtrip(&few, o);
+        assert_eq!((c, b), (C::Indexed, B::Two));
+        assert_eq!(d.rgba().bytes(), few.bytes());
+
+        // black and white
+        let bw = Image::<Box<[u8]>, 1>::build(9, 3).buf((0..27).map(|i| [0, 255][i % 2]).collect());
+        let (d, c, b, _) = roundtrip(&bw, o);
+        assert_eq!((c, b), (C::Grayscale, B::One));
+        assert_eq!(d.y().bytes(), bw.bytes());
+
+        let (d, c, b, _) = roundtrip(&bw, &PngOptions::new());
+        assert_eq!((c, b), (C::Grayscale, B::Eight));
+        assert_eq!(d.y().bytes(), bw.bytes());
+    }
+
+    #[test]
+    fn interlace() {
+        let cat = Image::<Vec<u8>, 3>::open("tdata/small_cat.png").boxed();
+        for filter in [PngFilter::None, PngFilter::Paeth, PngFilter::Adaptive] {
+            let o = PngOptions::new().interlace(true).filter(filter);
+            let (d, _, _, interlaced) = roundtrip(&cat, &o);
+            assert!(interlaced);
+            assert_eq!(d.rgb().bytes(), cat.bytes());
+        }
+        let deep = Image::<Box<[u16]>, 2>::build(13, 11)
+            .buf((0..13 * 11 * 2u16).map(|x| x.wrapping_mul(331)).collect());
+        let mut out = vec![];
+        let o = PngOptions::new().interlace(true).reduce(true);
+        WritePng::<u16>::write_with(&deep, &mut out, &o).unwrap();
+        let d = DynImage::read16(&mut std::io::Cursor::new(&out)).unwrap();
+        assert_eq!(Image::<Box<[u16]>, 2>::from(d).buffer(), deep.buffer());
+        // low bit depths, and sizes smaller than a pass
+        for (w, h) in [(1, 1), (3, 2), (9, 17)] {
+            let px = [[0, 0, 0, 255], [255, 255, 255, 255], [9, 9, 9, 0]];
+            let i = Image::<Box<[u8]>, 4>::build(w, h)
+                .buf((0..w * h).flat_map(|i| px[i as usize % 3]).collect());
+            let o = PngOptions::new().interlace(true).reduce(true);
+            let (d, ..) = roundtrip(&i, &o);
+            assert_eq!(d.rgba().bytes(), i.bytes());
+        }
+    }
+}
@@ -0,0 +1,356 @@
+//! reading and writing pngs a few rows at a time.
+use crate::{Error, Image, PngFilter, PngOptions, meta::Metadata, pixels::convert::PFrom};
+use miniz_oxide::deflate::core::{
+    CompressorOxide, TDEFLFlush, TDEFLStatus, compress, create_comp_flags_from_zip_params,
+};
+use std::io::{BufRead, Seek, Write};
+
+/// Reads a png a band of rows at a time, so that images much larger than memory can be processed.
+///
+/// Every band is converted to `N` channels and 8 bits: 16 bit pngs lose their low byte, as with [`png::Transformations::STRIP_16`].
+///
+/// ```
+/// # use fimg::{PngRowReader, PngRowWriter, PngOptions};
+/// let f = std::io::BufReader::new(std::fs::File::open("tdata/small_cat.png")?);
+/// let mut rows = PngRowReader::<_, 3>::new(f)?;
+/// let mut out = vec![];
+/// let mut w = PngRowWriter::<_, 3>::new(&mut out, (rows.width(), rows.height()), &PngOptions::new())?;
+/// while let Some(band) = rows.next_band(16)? {
+///     w.write_rows(band.cloner().flip_h().as_ref())?;
+/// }
+/// w.finish()?;
+/// # Ok::<(), fimg::Error>(())
+/// ```
+pub struct PngRowReader<R: BufRead + Seek, const N: usize> {
+    reader: png::Reader<R>,
+    /// channels of the decoded rows
+    channels: usize,
+    scratch: Vec<u8>,
+    band: Vec<u8>,
+    /// rows left to read
+    left: u32,
+}
+
+impl<R: BufRead + Seek, const N: usize> PngRowReader<R, N>
+where
+    [u8; N]: PFrom<1>,
+    [u8; N]: PFrom<2>,
+    [u8; N]: PFrom<3>,
+    [u8; N]: PFrom<4>,
+{
+    /// Read the header of a png. Interlaced pngs cannot be read row by row, and return a [`Error::Decode`].
+    pub fn new(f: R) -> Result<Self, Error> {
+        let mut dec = png::Decoder::new(f);
+        dec.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let reader = dec.read_info()?;
+        if reader.info().interlaced {
+            return Err(Error::Decode("interlaced pngs cannot be streamed".into()));
+        }
+        let channels = reader.output_color_type().0.samples();
+        let width = reader.info().width as usize;
+        Ok(Self {
+            channels,
+            scratch: vec![0; width * channels],
+            band: vec![],
+            left: reader.info().height,
+            reader,
+        })
+    }
+
+    /// Width of the image.
+    pub fn width(&self) -> u32 {
+        self.reader.info().width
+    }
+
+    /// Height of the image.
+    pub fn height(&self) -> u32 {
+        self.reader.info().height
+    }
+
+    /// The metadata read so far. Text that comes after the image data is only seen once every row has been read.
+    pub fn meta(&self) -> Result<Metadata, Error> {
+        crate::png_meta(self.reader.info())
+    }
+
+    /// Read the next row, or [`None`] once the image is done.
+    pub fn next_row(&mut self) -> Result<Option<Image<&[u8], N>>, Error> {
+        self.next_band(1)
+    }
+
+    /// Read the next (at most) `rows` rows, or [`None`] once the image is done.
+    /// The last band is shorter if the height is not a multiple of `rows`.
+    pub fn next_band(&mut self, rows: u32) -> Result<Option<Image<&[u8], N>>, Error> {
+        if self.left == 0 || rows == 0 {
+            return Ok(None);
+        }
+        let rows = rows.min(self.left);
+        let width = self.width();
+        let stride = width as usize * N;
+        self.band.resize(stride * rows as usize, 0);
+        for row in self.band.chunks_exact_mut(stride) {
+            if self.channels == N {
+                self.reader.read_row(row)?;
+                continue;
+            }
+            self.reader.read_row(&mut self.scratch)?;
+            macro_rules! convert {
+                ($c:literal) => {
+                    for (to, &from) in row
+                        .as_chunks_mut::<N>()
+                        .0
+                        .iter_mut()
+                        .zip(self.scratch.as_chunks::<$c>().0)
+                    {
+                        *to = PFrom::pfrom(from);
+                    }
+                };
+            }
+            match self.channels {
+                1 => convert!(1),
+                2 => convert!(2),
+                3 => convert!(3),
+                _ => convert!(4),
+            }
+        }
+        self.left -= rows;
+        if self.left == 0 {
+            // text may come after the image data; a bad trailing chunk only loses that
+            _ = self.reader.finish();
+        }
+        Ok(Some(Image::build(width, rows).buf(&self.band[..])))
+    }
+}
+
+/// Writes a png a band of rows at a time, so that images much larger than memory can be written.
+///
+/// Interlacing and color reduction need the whole image, so they cannot be used. See [`PngRowReader`] for an example.
+pub struct PngRowWriter<W: Write, const N: usize> {
+    writer: png::Writer<W>,
+    deflate: Box<CompressorOxide>,
+    filter: PngFilter,
+    prev: Vec<u8>,
+    filtered: Vec<u8>,
+    /// compressed data not yet written as a `IDAT`
+    idat: Vec<u8>,
+    width: u32,
+    /// rows left to write
+    left: u32,
+}
+
+/// Size of the `IDAT` chunks.
+const IDAT: usize = 1 << 16;
+
+impl<W: Write, const N: usize> PngRowWriter<W, N> {
+    /// Write the header of a `width` × `height` png, with these [`PngOptions`].
+    ///
+    /// Returns a [`Error::Encode`] if the options ask for interlacing or reduction,
+    /// and a [`Error::UnsupportedColor`] for more than 4 channels.
+    pub fn new(f: W, (width, height): (u32, u32), options: &PngOptions) -> Result<Self, Error> {
+        use png::ColorType::*;
+        if options.interlace || options.reduce {
+            return Err(Error::Encode(
+                "interlaced or reduced pngs cannot be streamed".into(),
+            ));
+        }
+        let color = match N {
+            1 => Grayscale,
+            2 => GrayscaleAlpha,
+            3 => Rgb,
+            4 => Rgba,
+            _ => return Err(Error::UnsupportedColor("pngs have 1 to 4 channels")),
+        };
+        let enc = crate::png_encoder(f, (width, height), (color, png::BitDepth::Eight), options);
+        let flags = create_comp_flags_from_zip_params(
+            crate::png_options::level(options.compression).into(),
+            1, // zlib header
+            0,
+        );
+        Ok(Self {
+            writer: enc.write_header()?,
+            deflate: Box::new(CompressorOxide::new(flags)),
+            filter: options.filter,
+            prev: vec![0; width as usize * N],
+            filtered: vec![],
+            idat: vec![],
+            width,
+            left: height,
+        })
+    }
+
+    /// Write the next rows of the image. They must be as wide as the image.
+    pub fn write_rows(&mut self, rows: Image<&[u8], N>) -> Result<(), Error> {
+        if rows.width() != self.width {
+            return Err(Error::Encode("rows are not as wide as the image".into()));
+        }
+        if rows.height() > self.left {
+            return Err(Error::Encode("more rows than the image has".into()));
+        }
+        self.filtered.clear();
+        for row in rows.buffer().chunks_exact(self.prev.len()) {
+            crate::png_options::filter(self.filter, N, &self.prev, row, &mut self.filtered);
+            self.prev.copy_from_slice(row);
+        }
+        self.compress(TDEFLFlush::None)?;
+        self.left -= rows.height();
+        Ok(())
+    }
+
+    /// Compress [`Self::filtered`], writing `IDAT`s as they fill up.
+    fn compress(&mut self, flush: TDEFLFlush) -> Result<(), Error> {
+        let mut input = &self.filtered[..];
+        loop {
+            let at = self.idat.len();
+            self.idat.resize(at + IDAT, 0);
+            let (status, read, wrote) =
+                compress(&mut self.deflate, input, &mut self.idat[at..], flush);
+            self.idat.truncate(at + wrote);
+            input = &input[read..];
+            if self.idat.len() >= IDAT || (status == TDEFLStatus::Done && !self.idat.is_empty()) {
+                self.writer.write_chunk(png::chunk::IDAT, &self.idat)?;
+                self.idat.clear();
+            }
+            match status {
+                TDEFLStatus::Done => return Ok(()),
+                TDEFLStatus::Okay
+                    if flush == TDEFLFlush::None && input.is_empty() && wrote < IDAT =>
+                {
+                    return Ok(());
+                }
+                TDEFLStatus::Okay => {}
+                _ => return Err(Error::Encode("deflate failed".into())),
+            }
+        }
+    }
+
+    /// Finish the png. Returns a [`Error::Encode`] if not every row was written.
+    pub fn finish(mut self) -> Result<(), Error> {
+        if self.left != 0 {
+            return Err(Error::Encode(
+                format!("{} rows were never written", self.left).into(),
+            ));
+        }
+        self.filtered.clear();
+        self.compress(TDEFLFlush::Finish)?;
+        self.writer.finish()?;
+        Ok(())
+    }
+}
+
+/// Scales a png a band of rows at a time.
+#[cfg(feature = "scale")]
+macro_rules! scale {
+    ($n:literal) => {
+        impl<W: Write> PngRowWriter<W, $n> {
+            /// Scale the rows left in `reader` to the rows left in this writer, reading about `rows` rows at a time, with a given scaling algorithm.
+            ///
+            /// Each band is scaled on its own, so filters can not see past its edges: taller bands make the seams rarer.
+            pub fn scale_rows<A: crate::scale::traits::ScalingAlgorithm, R: BufRead + Seek>(
+                &mut self,
+                reader: &mut PngRowReader<R, $n>,
+                rows: u32,
+            ) -> Result<(), Error> {
+                let (width, height) = (self.width, self.left);
+                let (from_width, from_height) = (reader.width(), reader.left);
+                let mut band = vec![];
+                // rows read, and rows written
+                let (mut read, mut wrote) = (0u64, 0u32);
+                while let Some(next) = reader.next_band(rows)? {
+                    band.extend_from_slice(next.bytes());
+                    read += next.height() as u64;
+                    let to = (read * height as u64 / from_height as u64) as u32;
+                    if to == wrote {
+                        continue;
+                    }
+                    let from_rows = (band.len() / (from_width as usize * $n)) as u32;
+                    let scaled = Image::<_, $n>::build(from_width, from_rows)
+                        .buf(&mut band[..])
+                        .scale::<A>(width, to - wrote);
+                    self.write_rows(scaled.as_ref())?;
+                    band.clear();
+                    wrote = to;
+                }
+                Ok(())
+            }
+        }
+    };
+}
+#[cfg(feature = "scale")]
+scale!(1);
+#[cfg(feature = "scale")]
+scale!(2);
+#[cfg(feature = "scale")]
+scale!(3);
+#[cfg(feature = "scale")]
+scale!(4);
+
+#[test]
+fn stream() {
+    use crate::{DynImage, ReadPng, WritePng, meta::Text};
+    let cat = DynImage::open("tdata/small_cat.png");
+    let meta = Metadata {
+        text: vec![Text::new("Title", "cat")],
+        ..Metadata::SRGB
+    };
+    let mut file = vec![];
+    cat.write_meta(&mut file, &meta).unwrap();
+    for band in [1, 7, 1000] {
+        let mut rows = PngRowReader::<_, 4>::new(std::io::Cursor::new(&file)).unwrap();
+        let (w, h) = (rows.width(), rows.height());
+        let mut out = vec![];
+        let mut writer =
+            PngRowWriter::<_, 4>::new(&mut out, (w, h), &PngOptions::new().meta(&meta)).unwrap();
+        let mut seen = 0;
+        while let Some(rows) = rows.next_band(band).unwrap() {
+            assert!(rows.height() <= band);
+            seen += rows.height();
+            writer.write_rows(rows).unwrap();
+        }
+        assert_eq!(seen, h);
+        assert_eq!(rows.meta().unwrap(), meta);
+        writer.finish().unwrap();
+        let (d, m) = DynImage::read_meta(&mut std::io::Cursor::new(&out)).unwrap();
+        assert_eq!(d.rgba().bytes(), cat.rgba().bytes());
+        assert_eq!(m, meta);
+    }
+
+    // conversion
+    let mut rows = PngRowReader::<_, 1>::new(std::io::Cursor::new(&file)).unwrap();
+    let first = rows.next_row().unwrap().unwrap();
+    assert_eq!(first.bytes(), &cat.to_y().bytes()[..first.bytes().len()]);
+
+    // misuse
+    let mut out = vec![];
+    let mut writer = PngRowWriter::<_, 3>::new(&mut out, (4, 2), &PngOptions::new()).unwrap();
+    let row = Image::<_, 3>::build(4, 1).buf(&[0; 12][..]);
+    assert!(
+        writer
+            .write_rows(Image::build(2, 1).buf(&[0; 6][..]))
+            .is_err()
+    );
+    writer.write_rows(row).unwrap();
+    assert!(matches!(writer.finish(), Err(Error::Encode(_))));
+    assert!(PngRowWriter::<_, 3>::new(vec![], (4, 2), &PngOptions::new().interlace(true)).is_err());
+    assert!(matches!(
+        PngRowWriter::<_, 5>::new(vec![], (4, 2), &PngOptions::new()),
+        Err(Error::UnsupportedColor(_))
+    ));
+}
+
+#[cfg(feature = "scale")]
+#[test]
+fn scale() {
+    use crate::{ReadPng, WritePng, scale::Nearest};
+    let cat = Image::<_, 3>::open("tdata/small_cat.png");
+    let mut file = vec![];
+    cat.write(&mut file).unwrap();
+    let (w, h) = (cat.width() / 2, cat.height() / 2);
+    for band in [2, 8, 1000] {
+        let mut rows = PngRowReader::<_, 3>::new(std::io::Cursor::new(&file)).unwrap();
+        let mut out = vec![];
+        let mut writer = PngRowWriter::<_, 3>::new(&mut out, (w, h), &PngOptions::new()).unwrap();
+        writer.scale_rows::<Nearest, _>(&mut rows, band).unwrap();
+        writer.finish().unwrap();
+        let scaled = Image::<Box<[u8]>, 3>::read(&mut std::io::Cursor::new(&out)).unwrap();
+        assert_eq!(scaled.bytes(), cat.scale::<Nearest>(w, h).bytes());
+    }
+}
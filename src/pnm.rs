@@ -0,0 +1,467 @@
+//! [Netpbm](https://netpbm.sourceforge.net/doc/#formats) encoding and decoding.
+//!
+//! Reads P1 through P6 (plain and raw) and P7 (PAM). Writes PGM, PPM, and PAM (for images with alpha), and PBM with [`encode_pbm`].
+use crate::{DynImage, Error, Image};
+use std::io::Write;
+
+/// The most pixels of a decoded image.
+const MAX_PIXELS: usize = 400_000_000;
+
+/// Read a netpbm image.
+pub trait ReadPnm: Sized {
+    /// Read a netpbm image into an image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error>;
+}
+
+/// Write a netpbm image.
+///
+/// `C` is the component type: `u8` for 8 bit images, `u16` for 16 bit images.
+pub trait WritePnm<C = u8> {
+    /// Write this netpbm image. (PGM, PPM, or PAM)
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error>;
+}
+
+macro_rules! fail {
+    ($x:literal) => {
+        return Err(Error::Decode(concat!("pnm: ", $x).into()))
+    };
+}
+
+/// A component that can be written as a netpbm sample.
+trait Sample: Copy + std::fmt::Display {
+    const MAXVAL: u32;
+    fn extend(self, out: &mut Vec<u8>);
+}
+
+impl Sample for u8 {
+    const MAXVAL: u32 = Self::MAX as u32;
+    fn extend(self, out: &mut Vec<u8>) {
+        out.push(self);
+    }
+}
+
+impl Sample for u16 {
+    const MAXVAL: u32 = Self::MAX as u32;
+    fn extend(self, out: &mut Vec<u8>) {
+        out.extend(self.to_be_bytes());
+    }
+}
+
+/// Writes the samples in lines no longer than 70 characters, as the spec wants.
+fn plain<T: std::fmt::Display>(out: &mut Vec<u8>, samples: impl Iterator<Item = T>, sep: &str) {
+    let mut line = 0;
+    for x in samples {
+        let x = x.to_string();
+        if line + sep.len() + x.len() > 70 {
+            out.push(b'\n');
+            line = 0;
+        } else if line != 0 {
+            out.extend(sep.as_bytes());
+            line += sep.len();
+        }
+        out.extend(x.as_bytes());
+        line += x.len();
+    }
+    out.push(b'\n');
+}
+
+fn encode_any<S: Sample, const N: usize>(image: Image<&[S], N>, plain_: bool) -> Vec<u8>
+where
+    // netpbm images have 1 to 4 channels
+    [(); ((N >= 1) & (N <= 4)) as usize - 1]:,
+{
+    let (w, h, max) = (image.width(), image.height(), S::MAXVAL);
+    let mut out = vec![];
+    match N {
+        1 | 3 => write!(
+            out,
+            "P{}\n{w} {h}\n{max}\n",
+            N / 2 + if plain_ { 2 } else { 5 }
+        ),
+        _ => write!(
+            out,
+            "P7\nWIDTH {w}\nHEIGHT {h}\nDEPTH {N}\nMAXVAL {max}\nTUPLTYPE {}\nENDHDR\n",
+            ["GRAYSCALE_ALPHA", "RGB_ALPHA"][N / 4]
+        ),
+    }
+    .unwrap();
+    if plain_ && N % 2 == 1 {
+        plain(&mut out, image.buffer().iter(), " ");
+    } else {
+        out.reserve(size_of_val(*image.buffer()));
+        for &x in *image.buffer() {
+            x.extend(&mut out);
+        }
+    }
+    out
+}
+
+/// Encode an image to PGM (Y), PPM (RGB), or PAM (YA, RGBA).
+/// If `plain`, writes the ASCII variant (P2 / P3). PAM has no ASCII variant, and is always raw.
+pub fn encode<const N: usize>(image: Image<&[u8], N>, plain: bool) -> Vec<u8>
+where
+    [(); ((N >= 1) & (N <= 4)) as usize - 1]:,
+{
+    encode_any(image, plain)
+}
+
+/// Encode a 16 bit image to PGM (Y), PPM (RGB), or PAM (YA, RGBA). See [`encode`].
+pub fn encode16<const N: usize>(image: Image<&[u16], N>, plain: bool) -> Vec<u8>
+where
+    [(); ((N >= 1) & (N <= 4)) as usize - 1]:,
+{
+    encode_any(image, plain)
+}
+
+/// Encode a Y image to a PBM (P1 / P4). Pixels darker than 128 are black.
+pub fn encode_pbm(image: Image<&[u8], 1>, plain_: bool) -> Vec<u8> {
+    let (w, h) = (image.width(), image.height());
+    let mut out = format!("P{}\n{w} {h}\n", if plain_ { 1 } else { 4 }).into_bytes();
+    for row in image.buffer().chunks_exact(w as usize) {
+        let bits = row.iter().map(|&x| (x < 128) as u8);
+        if plain_ {
+            plain(&mut out, bits, "");
+        } else {
+            out.extend(row.chunks(8).map(|x| {
+                x.iter()
+                    .enumerate()
+                    .fold(0, |acc, (i, &x)| acc | ((x < 128) as u8) << (7 - i))
+            }));
+        }
+    }
+    out
+}
+
+/// skip whitespace and comments
+fn skip(data: &mut &[u8]) {
+    loop {
+        match data.first() {
+            Some(b'#') => {
+                let end = data.iter().position(|&x| x == b'\n').unwrap_or(data.len());
+                *data = &data[end..];
+            }
+            Some(x) if x.is_ascii_whitespace() => *data = &data[1..],
+            _ => return,
+        }
+    }
+}
+
+/// read a word, after skipping whitespace
+fn word<'a>(data: &mut &'a [u8]) -> &'a [u8] {
+    skip(data);
+    let n = data.iter().take_while(|x| !x.is_ascii_whitespace()).count();
+    let (x, rest) = data.split_at(n);
+    *data = rest;
+    x
+}
+
+fn number(data: &mut &[u8]) -> Result<u32, Error> {
+    let x = word(data);
+    if x.is_empty() || !x.iter().all(u8::is_ascii_digit) {
+        fail!("expected a number")
+    }
+    // SAFETY: ascii digits
+    match unsafe { std::str::from_utf8_unchecked(x) }.parse() {
+        Ok(x) => Ok(x),
+        Err(_) => fail!("number too large"),
+    }
+}
+
+enum Samples {
+    Eight(Box<[u8]>),
+    Sixteen(Box<[u16]>),
+}
+
+impl Samples {
+    /// scale samples to 255 (or 65535, for `maxval > 255`)
+    fn new(
+        n: usize,
+        maxval: u32,
+        mut next: impl FnMut() -> Result<u32, Error>,
+    ) -> Result<Self, Error> {
+        let scale = |x: u32, to: u32| match x.min(maxval) {
+            x if maxval == to => x,
+            x => (x * to + maxval / 2) / maxval,
+        };
+        Ok(if maxval <= 255 {
+            Self::Eight(
+                (0..n)
+                    .map(|_| next().map(|x| scale(x, 255) as u8))
+                    .collect::<Result<_, _>>()?,
+            )
+        } else {
+            Self::Sixteen(
+                (0..n)
+                    .map(|_| next().map(|x| scale(x, 65535) as u16))
+                    .collect::<Result<_, _>>()?,
+            )
+        })
+    }
+
+    /// raw samples: one byte for `maxval < 256`, two (big endian) otherwise.
+    fn raw(data: &[u8], n: usize, maxval: u32) -> Result<Self, Error> {
+        let size = if maxval <= 255 { 1 } else { 2 };
+        if n.checked_mul(size).is_none_or(|x| data.len() < x) {
+            fail!("unexpected end of data")
+        }
+        let mut data = data.iter().copied();
+        let mut next = || data.next().map_or(0, u32::from);
+        match size {
+            1 => Self::new(n, maxval, || Ok(next())),
+            _ => Self::new(n, maxval, || Ok(next() << 8 | next())),
+        }
+    }
+
+    fn image(self, width: u32, height: u32, depth: usize) -> Result<DynImage<Box<[u8]>>, Error> {
+        macro_rules! n {
+            ($variant:ident, $n:literal, $buf:expr) => {
+                DynImage::$variant(Image::<_, $n>::build(width, height).try_buf($buf)?)
+            };
+        }
+        Ok(match (depth, self) {
+            (1, Self::Eight(x)) => n!(Y, 1, x),
+            (2, Self::Eight(x)) => n!(Ya, 2, x),
+            (3, Self::Eight(x)) => n!(Rgb, 3, x),
+            (4, Self::Eight(x)) => n!(Rgba, 4, x),
+            (1, Self::Sixteen(x)) => n!(Y16, 1, x),
+            (2, Self::Sixteen(x)) => n!(Ya16, 2, x),
+            (3, Self::Sixteen(x)) => n!(Rgb16, 3, x),
+            (4, Self::Sixteen(x)) => n!(Rgba16, 4, x),
+            _ => return Err(Error::UnsupportedColor("pam depth")),
+        })
+    }
+}
+
+/// the single whitespace between the header and the raster
+fn raster(data: &[u8]) -> Result<&[u8], Error> {
+    match data.split_first() {
+        Some((x, rest)) if x.is_ascii_whitespace() => Ok(rest),
+        _ => fail!("expected whitespace after header"),
+    }
+}
+
+fn size(width: u32, height: u32, depth: usize, maxval: u32) -> Result<usize, Error> {
+    if maxval == 0 || maxval > 65535 {
+        fail!("bad maxval")
+    }
+    if width == 0 || height == 0 {
+        fail!("zero width or height")
+    }
+    match (width as usize)
+        .checked_mul(height as usize)
+        .filter(|&x| x <= MAX_PIXELS)
+        .and_then(|x| x.checked_mul(depth))
+    {
+        Some(x) => Ok(x),
+        None => fail!("image too large"),
+    }
+}
+
+fn pam(mut data: &[u8]) -> Result<DynImage<Box<[u8]>>, Error> {
+    let (mut width, mut height, mut depth, mut maxval) = (None, None, None, None);
+    let mut tupltype = &[][..];
+    loop {
+        match word(&mut data) {
+            b"ENDHDR" => {
+                let Some(end) = data.iter().position(|&x| x == b'\n') else {
+                    fail!("unexpected end of data")
+                };
+                data = &data[end + 1..];
+                break;
+            }
+            b"WIDTH" => width = Some(number(&mut data)?),
+            b"HEIGHT" => height = Some(number(&mut data)?),
+            b"DEPTH" => depth = Some(number(&mut data)? as usize),
+            b"MAXVAL" => maxval = Some(number(&mut data)?),
+            b"TUPLTYPE" => tupltype = word(&mut data),
+            b"" => fail!("unexpected end of data"),
+            _ => fail!("bad header"),
+        }
+    }
+    let (Some(width), Some(height), Some(depth), Some(maxval)) = (width, height, depth, maxval)
+    else {
+        fail!("missing header field")
+    };
+    match (tupltype, depth) {
+        (b"BLACKANDWHITE" | b"GRAYSCALE", 1)
+        | (b"BLACKANDWHITE_ALPHA" | b"GRAYSCALE_ALPHA", 2)
+        | (b"RGB", 3)
+        | (b"RGB_ALPHA", 4) => {}
+        (b"BLACKANDWHITE" | b"GRAYSCALE" | b"BLACKANDWHITE_ALPHA" | b"GRAYSCALE_ALPHA", _)
+        | (b"RGB" | b"RGB_ALPHA", _) => fail!("tupltype does not match depth"),
+        _ => {}
+    }
+    let n = size(width, height, depth, maxval)?;
+    Samples::raw(data, n, maxval)?.image(width, height, depth)
+}
+
+/// Decode a netpbm image (P1-P7). Samples are scaled to 0-255, or 0-65535 (producing a 16 bit image) if the maxval is above 255.
+pub fn decode(data: &[u8]) -> Result<DynImage<Box<[u8]>>, Error> {
+    let Some((&[b'P', kind @ b'1'..=b'7'], mut data)) = data.split_first_chunk::<2>() else {
+        fail!("bad magic")
+    };
+    let kind = kind - b'0';
+    if kind == 7 {
+        return pam(data);
+    }
+    let width = number(&mut data)?;
+    let height = number(&mut data)?;
+    let maxval = match kind {
+        1 | 4 => 1,
+        _ => number(&mut data)?,
+    };
+    let depth = if matches!(kind, 3 | 6) { 3 } else { 1 };
+    let n = size(width, height, depth, maxval)?;
+    let samples = match kind {
+        1 => Samples::new(n, 1, || {
+            skip(&mut data);
+            match data.split_first() {
+                Some((b @ (b'0' | b'1'), rest)) => {
+                    data = rest;
+                    Ok((b'1' - b) as u32)
+                }
+                _ => fail!("expected a bit"),
+            }
+        })?,
+        4 => {
+            let data = raster(data)?;
+            let stride = (width as usize).div_ceil(8);
+            if data.len() < stride * height as usize {
+                fail!("unexpected end of data")
+            }
+            Samples::Eight(
+                data.chunks_exact(stride)
+                    .take(height as usize)
+                    .flat_map(|row| {
+                        (0..width as usize).map(|x| {
+                            if row[x / 8] >> (7 - x % 8) & 1 == 1 {
+                                0
+                            } else {
+                                255
+                            }
+                        })
+                    })
+                    .collect(),
+            )
+        }
+        2 | 3 => Samples::new(n, maxval, || number(&mut data))?,
+        _ => Samples::raw(raster(data)?, n, maxval)?,
+    };
+    samples.image(width, height, depth)
+}
+
+impl ReadPnm for DynImage<Box<[u8]>> {
+    /// Read a netpbm image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        let mut data = vec![];
+        f.read_to_end(&mut data)?;
+        decode(&data)
+    }
+}
+
+impl<const N: usize> ReadPnm for Image<Box<[u8]>, N>
+where
+    Self: From<DynImage<Box<[u8]>>>,
+{
+    /// Read a netpbm image, converting it to this many channels.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        DynImage::read(f).map(Into::into)
+    }
+}
+
+impl<T: AsRef<[u8]>, const N: usize> WritePnm for Image<T, N>
+where
+    [(); ((N >= 1) & (N <= 4)) as usize - 1]:,
+{
+    /// Write this image as a raw PGM, PPM, or PAM.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        f.write_all(&encode(self.as_ref(), false))?;
+        Ok(())
+    }
+}
+
+impl<T: AsRef<[u16]>, const N: usize> WritePnm<u16> for Image<T, N>
+where
+    [(); ((N >= 1) & (N <= 4)) as usize - 1]:,
+{
+    /// Write this 16 bit image as a raw PGM, PPM, or PAM.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        f.write_all(&encode16(self.as_ref(), false))?;
+        Ok(())
+    }
+}
+
+impl<T: AsRef<[u8]> + crate::Buffer> WritePnm for DynImage<T> {
+    /// Write this image as a raw PGM, PPM, or PAM.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        crate::r#dyn::e!(self, |i| WritePnm::write(i, f), |i| {
+            WritePnm::<u16>::write(i, f)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handwritten() {
+        let i = decode(b"P1\n# a comment\n3 2\n0 1 0\n101").unwrap();
+        assert_eq!(i.bytes(), [255, 0, 255, 0, 255, 0]);
+        let i = decode(b"P2 2 1 15 0 15").unwrap();
+        assert_eq!(i.bytes(), [0, 255]);
+        let i = decode(b"P4\n10 1\n\xff\xc0").unwrap();
+        assert_eq!(i.bytes(), [0; 10]);
+        let i = decode(b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 2\nMAXVAL 1\nTUPLTYPE BLACKANDWHITE_ALPHA\nENDHDR\n\x01\x00").unwrap();
+        assert_eq!(i.get_ya().unwrap().bytes(), [255, 0]);
+        let i = decode(b"P3 1 1 1000 1000 0 500").unwrap();
+        assert!(i.is_16());
+        assert!(matches!(decode(b"P6 1 1 255\n\x00"), Err(Error::Decode(_))));
+        for bad in [
+            &b"P4\n0 5\n "[..],
+            b"P4\n5 0\n ",
+            b"P2 0 0 255 ",
+            b"P5 65535 65535 255\n",
+        ] {
+            assert!(matches!(decode(bad), Err(Error::Decode(_))));
+        }
+        assert!(matches!(
+            decode(
+                b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 3\nMAXVAL 255\nTUPLTYPE GRAYSCALE\nENDHDR\n\0\0\0"
+            ),
+            Err(Error::Decode(_))
+        ));
+    }
+
+    #[cfg(feature = "save")]
+    #[test]
+    fn roundtrip() {
+        let cat = Image::<_, 4>::open("tdata/small_cat.png");
+        let rgb = Image::<Box<[u8]>, 3>::from(cat.as_ref());
+        let y = Image::<Box<[u8]>, 1>::from(rgb.as_ref());
+        assert_eq!(
+            decode(&encode(cat.as_ref(), false)).unwrap().bytes(),
+            cat.bytes()
+        );
+        for plain in [false, true] {
+            assert_eq!(
+                decode(&encode(rgb.as_ref(), plain)).unwrap().bytes(),
+                rgb.bytes()
+            );
+            assert_eq!(
+                decode(&encode(y.as_ref(), plain)).unwrap().bytes(),
+                y.bytes()
+            );
+            let wide = rgb.to_u16();
+            let back = decode(&encode16(wide.as_ref(), plain)).unwrap();
+            assert_eq!(Image::<Box<[u16]>, 3>::from(back).buffer(), wide.buffer());
+            let bits = decode(&encode_pbm(y.as_ref(), plain)).unwrap();
+            assert!(
+                bits.bytes()
+                    .iter()
+                    .zip(y.bytes())
+                    .all(|(&a, &b)| a == if b < 128 { 0 } else { 255 })
+            );
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! premultiplied alpha images.
+//!
+//! a premultiplied pixel holds its color already multiplied by its alpha.
+//! blending is then a multiply and an add, and filters (blur, scale) dont bleed the color of transparent pixels into their neighbours.
+use crate::{BlendingOverlay, BlendingOverlayAt, Image};
+
+/// An image with premultiplied alpha (ya or rgba), as opposed to a plain (straight alpha) [`Image`].
+///
+/// Make one with [`Image::premultiply`], and go back with [`Premultiplied::unpremultiply`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Premultiplied<T, const N: usize>(Image<T, N>);
+
+impl<T, const N: usize> Premultiplied<T, N> {
+    /// Treat this image as premultiplied. Does not convert anything.
+    pub const fn new(image: Image<T, N>) -> Self {
+        Self(image)
+    }
+
+    /// Take the image out, still premultiplied.
+    pub fn into_inner(self) -> Image<T, N> {
+        self.0
+    }
+
+    /// The premultiplied image.
+    pub const fn image(&self) -> &Image<T, N> {
+        &self.0
+    }
+
+    /// The premultiplied image, mutably. Keep every channel at or below the alpha.
+    pub const fn image_mut(&mut self) -> &mut Image<T, N> {
+        &mut self.0
+    }
+
+    /// Reference this image.
+    pub fn as_ref<U>(&self) -> Premultiplied<&[U], N>
+    where
+        T: AsRef<[U]>,
+    {
+        Premultiplied(self.0.as_ref())
+    }
+}
+
+/// `x * y / 255`, rounded.
+const fn mul(x: u8, y: u8) -> u8 {
+    let x = x as u32 * y as u32 + 128;
+    ((x + (x >> 8)) >> 8) as u8
+}
+
+/// Premultiply a pixel.
+pub fn premultiply<const N: usize>(mut px: [u8; N]) -> [u8; N] {
+    let a = px[N - 1];
+    px[..N - 1].iter_mut().for_each(|x| *x = mul(*x, a));
+    px
+}
+
+/// Unpremultiply a pixel. Fully transparent pixels become transparent black.
+pub fn unpremultiply<const N: usize>(mut px: [u8; N]) -> [u8; N] {
+    let a = px[N - 1] as u32;
+    px[..N - 1].iter_mut().for_each(|x| {
+        *x = match a {
+            0 => 0,
+            a => ((*x as u32 * 255 + a / 2) / a).min(255) as u8,
+        }
+    });
+    px
+}
+
+/// Blend premultiplied `fg` onto premultiplied `bg` (source over).
+pub fn blend<const N: usize>(bg: &mut [u8; N], fg: [u8; N]) {
+    let inverse = 255 - fg[N - 1];
+    for (b, f) in bg.iter_mut().zip(fg) {
+        *b = f.saturating_add(mul(*b, inverse));
+    }
+}
+
+macro_rules! premultiply {
+    ($n:literal) => {
+        impl<T: AsRef<[u8]>> Image<T, $n> {
+            /// Premultiply the alpha of this image.
+            pub fn premultiply(&self) -> Premultiplied<Box<[u8]>, $n> {
+                // SAFETY: length unchanged
+                Premultiplied(unsafe {
+                    self.as_ref().mapped(|x| {
+                        x.as_chunks::<$n>()
+                            .0
+                            .iter()
+                            .flat_map(|&p| premultiply(p))
+                            .collect()
+                    })
+                })
+            }
+        }
+
+        impl<T: AsRef<[u8]>> Premultiplied<T, $n> {
+            /// Divide the alpha back out, to a plain image.
+            pub fn unpremultiply(&self) -> Image<Box<[u8]>, $n> {
+                // SAFETY: length unchanged
+                unsafe {
+                    self.0.as_ref().mapped(|x| {
+                        x.as_chunks::<$n>()
+                            .0
+                            .iter()
+                            .flat_map(|&p| unpremultiply(p))
+                            .collect()
+                    })
+                }
+            }
+        }
+
+        #[cfg(feature = "blur")]
+        impl<T: AsMut<[u8]> + AsRef<[u8]>> Premultiplied<T, $n> {
+            /// Blur this image. Transparent pixels dont darken their neighbours.
+            pub fn blur(&mut self, radius: usize) {
+                self.0.blur_in(radius);
+            }
+        }
+    };
+}
+
+premultiply!(2);
+premultiply!(4);
+
+#[cfg(feature = "scale")]
+impl<T: AsRef<[u8]>, const N: usize> Premultiplied<T, N> {
+    /// Scale this image with a given scaling algorithm. No alpha handling is needed.
+    pub fn scale<A: crate::scale::traits::ScalingAlgorithm>(
+        &self,
+        width: u32,
+        height: u32,
+    ) -> Premultiplied<Box<[u8]>, N>
+    where
+        crate::scale::traits::ChannelCount<N>: crate::scale::traits::ToImageView<N>,
+    {
+        Premultiplied(A::scale_opaque(
+            self.0.as_ref(),
+            width.try_into().unwrap(),
+            height.try_into().unwrap(),
+        ))
+    }
+}
+
+impl<const N: usize, T: AsMut<[u8]> + AsRef<[u8]>, U: AsRef<[u8]>>
+    BlendingOverlay<Premultiplied<U, N>> for Premultiplied<T, N>
+{
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    unsafe fn overlay_blended(&mut self, with: &Premultiplied<U, N>) -> &mut Self {
+        debug_assert!(self.0.width() == with.0.width());
+        debug_assert!(self.0.height() == with.0.height());
+        for (bg, &fg) in self.0.flatten_mut().iter_mut().zip(with.0.flatten()) {
+            blend(bg, fg);
+        }
+        self
+    }
+}
+
+impl<const N: usize, T: AsMut<[u8]> + AsRef<[u8]>, U: AsRef<[u8]>>
+    BlendingOverlayAt<Premultiplied<U, N>> for Premultiplied<T, N>
+{
+    #[inline]
+    unsafe fn overlay_blended_at(
+        &mut self,
+        with: &Premultiplied<U, N>,
+        x: u32,
+        y: u32,
+    ) -> &mut Self {
+        for j in 0..with.0.height() {
+            for i in 0..with.0.width() {
+                // SAFETY: i, j is in bounds.
+                let fg = unsafe { with.0.pixel(i, j) };
+                // SAFETY: caller promises i + x, j + y is in bounds.
+                blend(unsafe { self.0.pixel_mut(i + x, j + y) }, *fg);
+            }
+        }
+        self
+    }
+}
+
+#[test]
+fn roundtrip() {
+    // opaque is exact
+    let cat = Image::<_, 4>::open("tdata/small_cat.png");
+    assert_eq!(cat.premultiply().unpremultiply().bytes(), cat.bytes());
+    for c in 0..=255 {
+        for a in 0..=255 {
+            assert_eq!(mul(c, a), ((c as f32 * a as f32) / 255.0).round() as u8);
+            let [p, _] = unpremultiply(premultiply([c, a]));
+            // the error is about half a step of 255 / a
+            assert!(p.abs_diff(c) as u32 * a as u32 <= 255, "{c} {a} {p}");
+        }
+    }
+}
+
+#[test]
+fn over() {
+    let mut bg = Image::<_, 2>::build(2, 1)
+        .buf(vec![200, 255, 100, 100])
+        .premultiply();
+    let mut fg = Image::<_, 2>::build(2, 1).buf(vec![50, 128, 77, 0]);
+    // SAFETY: same size
+    unsafe { bg.overlay_blended(&fg.premultiply()) };
+    // an opaque background stays opaque
+    assert_eq!(bg.unpremultiply().bytes(), [125, 255, 99, 100]);
+    // transparent pixels keep no color
+    fg.chunked_mut().for_each(|p| p[1] = 0);
+    assert!(fg.premultiply().image().bytes().iter().all(|&x| x == 0));
+}
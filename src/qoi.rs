@@ -0,0 +1,231 @@
+//! [QOI](https://qoiformat.org) encoding and decoding.
+//!
+//! Lossless, and much faster to encode than png.
+use crate::{DynImage, Error, Image, pixels::convert::PFrom};
+
+/// Read a qoi image.
+pub trait ReadQoi: Sized {
+    /// Read a qoi into an image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error>;
+}
+
+/// Write a qoi image.
+pub trait WriteQoi {
+    /// Write this qoi image.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error>;
+}
+
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const OP_RUN: u8 = 0xc0;
+const OP_RGB: u8 = 0xfe;
+const OP_RGBA: u8 = 0xff;
+const END: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+/// same limit as the reference implementation.
+const MAX_PIXELS: usize = 400_000_000;
+
+const fn hash([r, g, b, a]: [u8; 4]) -> usize {
+    (r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64
+}
+
+/// Encode an image to qoi.
+/// Y and YA images are written as RGB and RGBA.
+pub fn encode<const N: usize>(image: Image<&[u8], N>) -> Vec<u8>
+where
+    [u8; 4]: PFrom<N>,
+{
+    let mut out = Vec::with_capacity(14 + image.buffer().len() + image.buffer().len() / 4 + 8);
+    out.extend(b"qoif");
+    out.extend(image.width().to_be_bytes());
+    out.extend(image.height().to_be_bytes());
+    out.extend([if N == 2 || N == 4 { 4 } else { 3 }, 0]);
+
+    let mut index = [[0; 4]; 64];
+    let mut prev = [0, 0, 0, 255];
+    let mut run = 0;
+    for &px in image.flatten() {
+        let px = <[u8; 4] as PFrom<N>>::pfrom(px);
+        if px == prev {
+            run += 1;
+            if run == 62 {
+                out.push(OP_RUN | (run - 1));
+                run = 0;
+            }
+            continue;
+        }
+        if run > 0 {
+            out.push(OP_RUN | (run - 1));
+            run = 0;
+        }
+        let h = hash(px);
+        if index[h] == px {
+            out.push(OP_INDEX | h as u8);
+        } else {
+            index[h] = px;
+            if px[3] == prev[3] {
+                let [vr, vg, vb] = [0, 1, 2].map(|i| px[i].wrapping_sub(prev[i]) as i8);
+                let (vg_r, vg_b) = (vr.wrapping_sub(vg), vb.wrapping_sub(vg));
+                if (-2..2).contains(&vr) && (-2..2).contains(&vg) && (-2..2).contains(&vb) {
+                    out.push(
+                        OP_DIFF | ((vr + 2) as u8) << 4 | ((vg + 2) as u8) << 2 | (vb + 2) as u8,
+                    );
+                } else if (-8..8).contains(&vg_r)
+                    && (-32..32).contains(&vg)
+                    && (-8..8).contains(&vg_b)
+                {
+                    out.extend([
+                        OP_LUMA | (vg + 32) as u8,
+                        ((vg_r + 8) as u8) << 4 | (vg_b + 8) as u8,
+                    ]);
+                } else {
+                    out.extend([OP_RGB, px[0], px[1], px[2]]);
+                }
+            } else {
+                out.extend([OP_RGBA, px[0], px[1], px[2], px[3]]);
+            }
+        }
+        prev = px;
+    }
+    if run > 0 {
+        out.push(OP_RUN | (run - 1));
+    }
+    out.extend(END);
+    out
+}
+
+/// Decode a qoi image. Produces a [`DynImage::Rgb`] or a [`DynImage::Rgba`].
+pub fn decode(data: &[u8]) -> Result<DynImage<Box<[u8]>>, Error> {
+    macro_rules! fail {
+        ($x:literal) => {
+            return Err(Error::Decode(concat!("qoi: ", $x).into()))
+        };
+    }
+    let Some((header, mut data)) = data.split_first_chunk::<14>() else {
+        fail!("missing header")
+    };
+    if header[..4] != *b"qoif" {
+        fail!("bad magic")
+    }
+    let width = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    let height = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+    let channels = match header[12] {
+        x @ (3 | 4) => x as usize,
+        _ => fail!("bad channel count"),
+    };
+    let n = width as usize * height as usize;
+    if n > MAX_PIXELS {
+        fail!("image too large")
+    }
+
+    let mut out = Vec::with_capacity(n * channels);
+    let mut index = [[0u8; 4]; 64];
+    let mut px = [0, 0, 0, 255];
+    let mut i = 0;
+    while i < n {
+        let Some((&b, rest)) = data.split_first() else {
+            fail!("unexpected end of data")
+        };
+        data = rest;
+        let mut run = 1;
+        match b {
+            OP_RGB => {
+                let Some((&[r, g, b], rest)) = data.split_first_chunk::<3>() else {
+                    fail!("unexpected end of data")
+                };
+                data = rest;
+                px = [r, g, b, px[3]];
+            }
+            OP_RGBA => {
+                let Some((&x, rest)) = data.split_first_chunk::<4>() else {
+                    fail!("unexpected end of data")
+                };
+                data = rest;
+                px = x;
+            }
+            OP_INDEX..OP_DIFF => px = index[b as usize],
+            OP_DIFF..OP_LUMA => {
+                px[0] = px[0].wrapping_add((b >> 4 & 3).wrapping_sub(2));
+                px[1] = px[1].wrapping_add((b >> 2 & 3).wrapping_sub(2));
+                px[2] = px[2].wrapping_add((b & 3).wrapping_sub(2));
+            }
+            OP_LUMA..OP_RUN => {
+                let Some((&b2, rest)) = data.split_first() else {
+                    fail!("unexpected end of data")
+                };
+                data = rest;
+                let vg = (b & 0x3f).wrapping_sub(32);
+                px[0] = px[0].wrapping_add(vg.wrapping_sub(8).wrapping_add(b2 >> 4));
+                px[1] = px[1].wrapping_add(vg);
+                px[2] = px[2].wrapping_add(vg.wrapping_sub(8).wrapping_add(b2 & 0x0f));
+            }
+            _ => run = (b & 0x3f) as usize + 1,
+        }
+        index[hash(px)] = px;
+        let run = run.min(n - i);
+        for _ in 0..run {
+            out.extend_from_slice(&px[..channels]);
+        }
+        i += run;
+    }
+    let out = out.into_boxed_slice();
+    Ok(match channels {
+        3 => Image::<_, 3>::build(width, height).try_buf(out)?.into(),
+        _ => Image::<_, 4>::build(width, height).try_buf(out)?.into(),
+    })
+}
+
+impl ReadQoi for DynImage<Box<[u8]>> {
+    /// Read a qoi image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        let mut data = vec![];
+        f.read_to_end(&mut data)?;
+        decode(&data)
+    }
+}
+
+impl<const N: usize> ReadQoi for Image<Box<[u8]>, N>
+where
+    Self: From<DynImage<Box<[u8]>>>,
+{
+    /// Read a qoi image, converting it to this many channels.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        DynImage::read(f).map(Into::into)
+    }
+}
+
+impl<T: AsRef<[u8]>, const N: usize> WriteQoi for Image<T, N>
+where
+    [u8; 4]: PFrom<N>,
+{
+    /// Write this image as a qoi.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        f.write_all(&encode(self.as_ref()))?;
+        Ok(())
+    }
+}
+
+impl<T: AsRef<[u8]> + crate::Buffer> WriteQoi for DynImage<T> {
+    /// Write this image as a qoi. 16 bit images are reduced to 8 bit.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        crate::r#dyn::e!(narrow self, |i| WriteQoi::write(i, f))
+    }
+}
+
+#[cfg(feature = "save")]
+#[test]
+fn roundtrip() {
+    let cat = Image::<_, 4>::open("tdata/cat.png");
+    let qoi = encode(cat.as_ref());
+    assert_eq!(&qoi[..4], b"qoif");
+    assert_eq!(&qoi[qoi.len() - 8..], END);
+    assert_eq!(decode(&qoi).unwrap().to_rgba().bytes(), cat.bytes());
+
+    let y = Image::<_, 1>::open("tdata/small_cat.png");
+    let mut qoi = vec![];
+    WriteQoi::write(&y, &mut qoi).unwrap();
+    let back = <Image<Box<[u8]>, 3> as ReadQoi>::read(&mut &qoi[..]).unwrap();
+    assert_eq!(Image::<Box<[u8]>, 1>::from(back).bytes(), y.bytes());
+
+    assert!(matches!(decode(&qoi[..20]), Err(Error::Decode(_))));
+}
@@ -0,0 +1,500 @@
+//! raw pixel buffers, laid out as a [`Layout`] says. (GPU readbacks, camera SDKs, ...)
+//!
+//! ```
+//! # use fimg::{Image, raw::{self, Layout, Order}};
+//! // a bgra readback, with rows padded to 256 bytes
+//! let data = vec![0; 256 * 2];
+//! let layout = Layout::new(50, 2, Order::Bgra).stride(256);
+//! let image: Image<Box<[u8]>, 4> = raw::decode(&data, &layout)?.into();
+//! assert_eq!(raw::encode(image.as_ref(), &layout)?, data);
+//! # Ok::<(), fimg::Error>(())
+//! ```
+use crate::{DynImage, Error, Image};
+
+/// The order of the channels of a pixel.
+///
+/// `X` channels are padding: ignored when reading, and written as fully opaque.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Order {
+    /// Gray.
+    Y,
+    /// Gray, alpha.
+    Ya,
+    /// Red, green, blue.
+    Rgb,
+    /// Blue, green, red.
+    Bgr,
+    /// Red, green, blue, alpha.
+    Rgba,
+    /// Blue, green, red, alpha.
+    Bgra,
+    /// Alpha, red, green, blue.
+    Argb,
+    /// Alpha, blue, green, red.
+    Abgr,
+    /// Red, green, blue, padding.
+    Rgbx,
+    /// Blue, green, red, padding.
+    Bgrx,
+    /// Padding, red, green, blue.
+    Xrgb,
+    /// Padding, blue, green, red.
+    Xbgr,
+}
+
+/// Where each channel goes, in a Y, YA, RGB, or RGBA pixel (or nowhere, for padding).
+type Slots = &'static [Option<usize>];
+
+impl Order {
+    const fn slots(self) -> Slots {
+        match self {
+            Self::Y => &[Some(0)],
+            Self::Ya => &[Some(0), Some(1)],
+            Self::Rgb => &[Some(0), Some(1), Some(2)],
+            Self::Bgr => &[Some(2), Some(1), Some(0)],
+            Self::Rgba => &[Some(0), Some(1), Some(2), Some(3)],
+            Self::Bgra => &[Some(2), Some(1), Some(0), Some(3)],
+            Self::Argb => &[Some(3), Some(0), Some(1), Some(2)],
+            Self::Abgr => &[Some(3), Some(2), Some(1), Some(0)],
+            Self::Rgbx => &[Some(0), Some(1), Some(2), None],
+            Self::Bgrx => &[Some(2), Some(1), Some(0), None],
+            Self::Xrgb => &[None, Some(0), Some(1), Some(2)],
+            Self::Xbgr => &[None, Some(2), Some(1), Some(0)],
+        }
+    }
+
+    /// The channels of the image this makes. (Y = 1, YA = 2, RGB = 3, RGBA = 4)
+    #[must_use]
+    pub const fn channels(self) -> usize {
+        match self {
+            Self::Y => 1,
+            Self::Ya => 2,
+            Self::Rgb | Self::Bgr | Self::Rgbx | Self::Bgrx | Self::Xrgb | Self::Xbgr => 3,
+            _ => 4,
+        }
+    }
+}
+
+/// The type of a channel.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Component {
+    /// 0-255.
+    #[default]
+    U8,
+    /// 0-65535.
+    U16,
+    /// 0.0-1.0.
+    F32,
+}
+
+impl Component {
+    /// Size in bytes.
+    #[must_use]
+    pub const fn size(self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::U16 => 2,
+            Self::F32 => 4,
+        }
+    }
+}
+
+/// Byte order of multi byte components.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Endian {
+    /// Least significant byte first.
+    Little,
+    /// Most significant byte first.
+    Big,
+}
+
+impl Endian {
+    /// The byte order of this machine.
+    pub const NATIVE: Self = if cfg!(target_endian = "little") {
+        Self::Little
+    } else {
+        Self::Big
+    };
+}
+
+/// How the pixels of a raw buffer are laid out.
+#[must_use]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Layout {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) order: Order,
+    pub(crate) component: Component,
+    pub(crate) stride: Option<usize>,
+    pub(crate) endian: Endian,
+    pub(crate) premultiplied: bool,
+}
+
+impl Layout {
+    /// A tightly packed `width` × `height` buffer of 8 bit pixels in this order, not premultiplied.
+    pub const fn new(width: u32, height: u32, order: Order) -> Self {
+        Self {
+            width,
+            height,
+            order,
+            component: Component::U8,
+            stride: None,
+            endian: Endian::NATIVE,
+            premultiplied: false,
+        }
+    }
+
+    /// Set the type of the channels.
+    pub const fn component(self, component: Component) -> Self {
+        Self { component, ..self }
+    }
+
+    /// Set the distance between the starts of two rows, in bytes. Must be at least a row long.
+    pub const fn stride(self, stride: usize) -> Self {
+        Self {
+            stride: Some(stride),
+            ..self
+        }
+    }
+
+    /// Set the byte order of the channels. Defaults to [`Endian::NATIVE`].
+    pub const fn endian(self, endian: Endian) -> Self {
+        Self { endian, ..self }
+    }
+
+    /// Set whether the color channels are multiplied by the alpha.
+    pub const fn premultiplied(self, premultiplied: bool) -> Self {
+        Self {
+            premultiplied,
+            ..self
+        }
+    }
+
+    /// The size of a pixel, in bytes.
+    #[must_use]
+    pub const fn pixel_size(&self) -> usize {
+        self.order.slots().len() * self.component.size()
+    }
+
+    /// The size of a row (without padding), in bytes.
+    #[must_use]
+    pub const fn row_size(&self) -> usize {
+        self.width as usize * self.pixel_size()
+    }
+
+    /// The distance between the starts of two rows, in bytes.
+    #[must_use]
+    pub const fn row_stride(&self) -> usize {
+        match self.stride {
+            Some(x) => x,
+            None => self.row_size(),
+        }
+    }
+
+    /// The size of the buffer. The last row does not need its padding.
+    /// [`None`] if it does not fit in a [`usize`].
+    #[must_use]
+    pub const fn len(&self) -> Option<usize> {
+        match self.height {
+            0 => Some(0),
+            h => match self.row_stride().checked_mul(h as usize - 1) {
+                Some(x) => x.checked_add(self.row_size()),
+                None => None,
+            },
+        }
+    }
+
+    /// Whether this is a layout for a empty image.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Returns the [`len`](Self::len) of a usable layout.
+    fn check(&self) -> Result<usize, &'static str> {
+        if self.is_empty() {
+            return Err("raw: zero width or height");
+        }
+        if self.row_stride() < self.row_size() {
+            return Err("raw: stride is smaller than a row");
+        }
+        self.len().ok_or("raw: image too large")
+    }
+
+    fn read(&self, x: &[u8]) -> f32 {
+        let be = self.endian == Endian::Big;
+        match self.component {
+            Component::U8 => x[0] as f32 / 255.0,
+            Component::U16 => {
+                let x = [x[0], x[1]];
+                (if be {
+                    u16::from_be_bytes(x)
+                } else {
+                    u16::from_le_bytes(x)
+                }) as f32
+                    / 65535.0
+            }
+            Component::F32 => {
+                let x = [x[0], x[1], x[2], x[3]];
+                if be {
+                    f32::from_be_bytes(x)
+                } else {
+                    f32::from_le_bytes(x)
+                }
+            }
+        }
+    }
+
+    fn write(&self, x: f32, out: &mut Vec<u8>) {
+        let be = self.endian == Endian::Big;
+        match self.component {
+            Component::U8 => out.push(u8::from_f32(x)),
+            Component::U16 => {
+                let x = u16::from_f32(x);
+                out.extend(if be { x.to_be_bytes() } else { x.to_le_bytes() })
+            }
+            Component::F32 => out.extend(if be { x.to_be_bytes() } else { x.to_le_bytes() }),
+        }
+    }
+}
+
+/// A component of a [`Image`].
+trait Sample: Copy {
+    fn from_f32(x: f32) -> Self;
+    fn to_f32(self) -> f32;
+}
+
+impl Sample for u8 {
+    fn from_f32(x: f32) -> Self {
+        (x.clamp(0.0, 1.0) * 255.0).round() as Self
+    }
+    fn to_f32(self) -> f32 {
+        self as f32 / 255.0
+    }
+}
+
+impl Sample for u16 {
+    fn from_f32(x: f32) -> Self {
+        (x.clamp(0.0, 1.0) * 65535.0).round() as Self
+    }
+    fn to_f32(self) -> f32 {
+        self as f32 / 65535.0
+    }
+}
+
+impl Sample for f32 {
+    fn from_f32(x: f32) -> Self {
+        x
+    }
+    fn to_f32(self) -> f32 {
+        self
+    }
+}
+
+/// Multiply (or divide) the color channels of this `n` channel pixel by its alpha.
+fn premultiply(px: &mut [f32; 4], n: usize, divide: bool) {
+    if n % 2 == 1 {
+        return;
+    }
+    let a = px[n - 1];
+    for x in &mut px[..n - 1] {
+        *x = match (divide, a) {
+            (true, 0.0) => 0.0,
+            (true, _) => *x / a,
+            (false, _) => *x * a,
+        };
+    }
+}
+
+fn decode_samples<S: Sample>(data: &[u8], layout: &Layout) -> Result<Vec<S>, Error> {
+    let expected = layout.check().map_err(|x| Error::Decode(x.into()))?;
+    if data.len() < expected {
+        return Err(Error::DimensionMismatch {
+            expected,
+            got: data.len(),
+        });
+    }
+    let (slots, size, n) = (
+        layout.order.slots(),
+        layout.component.size(),
+        layout.order.channels(),
+    );
+    let mut out = Vec::with_capacity(layout.width as usize * layout.height as usize * n);
+    for y in 0..layout.height as usize {
+        let row = &data[y * layout.row_stride()..][..layout.row_size()];
+        for p in row.chunks_exact(layout.pixel_size()) {
+            let mut px = [0.0; 4];
+            for (&slot, x) in slots.iter().zip(p.chunks_exact(size)) {
+                if let Some(slot) = slot {
+                    px[slot] = layout.read(x);
+                }
+            }
+            if layout.premultiplied {
+                premultiply(&mut px, n, true);
+            }
+            out.extend(px[..n].iter().map(|&x| S::from_f32(x)));
+        }
+    }
+    Ok(out)
+}
+
+/// Decode a raw buffer. 8 bit components become a 8 bit image, and 16 bit and float components become a 16 bit image.
+///
+/// The buffer may be longer than [`Layout::len`].
+pub fn decode(data: &[u8], layout: &Layout) -> Result<DynImage<Box<[u8]>>, Error> {
+    let (w, h) = (layout.width, layout.height);
+    macro_rules! n {
+        ($t:ty, $($n:literal => $v:ident),+) => {{
+            let buf = decode_samples::<$t>(data, layout)?.into_boxed_slice();
+            match layout.order.channels() {
+                $($n => DynImage::$v(Image::build(w, h).buf(buf)),)+
+                _ => unreachable!(),
+            }
+        }};
+    }
+    Ok(match layout.component {
+        Component::U8 => n!(u8, 1 => Y, 2 => Ya, 3 => Rgb, 4 => Rgba),
+        _ => n!(u16, 1 => Y16, 2 => Ya16, 3 => Rgb16, 4 => Rgba16),
+    })
+}
+
+/// Decode a raw buffer to a float image, which must have as many channels as the [`Order`].
+pub fn decode_f32<const N: usize>(
+    data: &[u8],
+    layout: &Layout,
+) -> Result<Image<Box<[f32]>, N>, Error> {
+    if layout.order.channels() != N {
+        return Err(Error::UnsupportedColor(
+            "raw: the order has a different number of channels",
+        ));
+    }
+    let buf = decode_samples::<f32>(data, layout)?;
+    Ok(Image::build(layout.width, layout.height).buf(buf.into()))
+}
+
+fn encode_samples<S: Sample, const N: usize>(
+    image: Image<&[S], N>,
+    layout: &Layout,
+) -> Result<Vec<u8>, Error> {
+    if (image.width(), image.height()) != (layout.width, layout.height) {
+        return Err(Error::Encode(
+            "raw: the layout is not the size of the image".into(),
+        ));
+    }
+    let len = layout.check().map_err(|x| Error::Encode(x.into()))?;
+    let n = layout.order.channels();
+    let mut out = Vec::with_capacity(len);
+    for row in image.buffer().chunks_exact(image.width() as usize * N) {
+        let end = out.len() + layout.row_stride();
+        for px in row.as_chunks::<N>().0 {
+            let px = px.map(|x| x.to_f32());
+            // to y, ya, rgb, or rgba
+            let (y, alpha) = match N {
+                1 | 2 => (px[0], px.get(1)),
+                _ => (
+                    0.0722f32.mul_add(px[2], 0.2126f32.mul_add(px[0], 0.7152 * px[1])),
+                    px.get(3),
+                ),
+            };
+            let alpha = alpha.copied().unwrap_or(1.0);
+            let mut p = match (n, N) {
+                (1 | 2, _) => [y, alpha, 0.0, 0.0],
+                (_, 1 | 2) => [y, y, y, alpha],
+                _ => [px[0], px[1], px[2], alpha],
+            };
+            if layout.premultiplied {
+                premultiply(&mut p, n, false);
+            }
+            for &slot in layout.order.slots() {
+                layout.write(slot.map_or(1.0, |s| p[s]), &mut out);
+            }
+        }
+        out.resize(end, 0);
+    }
+    Ok(out)
+}
+
+/// Encode a image to a raw buffer, converting it to the channels of the [`Order`].
+/// Every row is padded to the stride, including the last.
+///
+/// The layout must be the size of the image.
+pub fn encode<const N: usize>(image: Image<&[u8], N>, layout: &Layout) -> Result<Vec<u8>, Error> {
+    encode_samples(image, layout)
+}
+
+/// Encode a 16 bit image to a raw buffer. See [`encode`].
+pub fn encode16<const N: usize>(
+    image: Image<&[u16], N>,
+    layout: &Layout,
+) -> Result<Vec<u8>, Error> {
+    encode_samples(image, layout)
+}
+
+/// Encode a float image to a raw buffer. See [`encode`].
+pub fn encode_f32<const N: usize>(
+    image: Image<&[f32], N>,
+    layout: &Layout,
+) -> Result<Vec<u8>, Error> {
+    encode_samples(image, layout)
+}
+
+#[test]
+fn layouts() {
+    let px = [[255, 0, 0, 255], [0, 128, 255, 128], [10, 20, 30, 0]];
+    let rgba = Image::<Box<[u8]>, 4>::build(3, 1).buf(px.as_flattened().into());
+    let l = Layout::new(3, 1, Order::Bgra);
+    let bgra = encode(rgba.as_ref(), &l).unwrap();
+    assert_eq!(bgra[..4], [0, 0, 255, 255]);
+    assert_eq!(decode(&bgra, &l).unwrap().bytes(), rgba.bytes());
+
+    // padding, 16 bit big endian
+    let l = Layout::new(3, 1, Order::Xrgb)
+        .component(Component::U16)
+        .endian(Endian::Big)
+        .stride(32);
+    let x = encode(rgba.as_ref(), &l).unwrap();
+    assert_eq!(x.len(), 32);
+    assert_eq!(x[..8], [255, 255, 255, 255, 0, 0, 0, 0]);
+    let back = Image::<Box<[u8]>, 3>::from(decode(&x[..l.len().unwrap()], &l).unwrap());
+    assert_eq!(back.bytes(), [255, 0, 0, 0, 128, 255, 10, 20, 30]);
+
+    // premultiplied floats
+    let l = Layout::new(3, 1, Order::Rgba)
+        .component(Component::F32)
+        .premultiplied(true);
+    let x = encode(rgba.as_ref(), &l).unwrap();
+    let f = decode_f32::<4>(&x, &l).unwrap();
+    for (&a, &b) in f.buffer()[..8].iter().zip(&rgba.bytes()[..8]) {
+        assert!((a - b as f32 / 255.0).abs() < 1e-6);
+    }
+    // no color survives a alpha of 0
+    assert_eq!(f.buffer()[8..], [0.0; 4]);
+    assert_eq!(
+        decode(&x, &l).unwrap().rgba().bytes()[..8],
+        rgba.bytes()[..8]
+    );
+
+    // gray
+    let l = Layout::new(3, 1, Order::Ya);
+    let y = encode(rgba.as_ref(), &l).unwrap();
+    assert_eq!(y, [54, 255, 110, 128, 19, 0]);
+
+    assert!(matches!(
+        decode(&y[..5], &l),
+        Err(Error::DimensionMismatch {
+            expected: 6,
+            got: 5
+        })
+    ));
+    assert!(decode(&y, &l.stride(2)).is_err());
+    assert!(decode_f32::<3>(&y, &l).is_err());
+    assert!(encode(rgba.as_ref(), &Layout::new(2, 1, Order::Y)).is_err());
+    // empty, and too large to address
+    assert!(matches!(
+        decode(&y, &Layout::new(0, 2, Order::Y).stride(1)),
+        Err(Error::Decode(_))
+    ));
+    let l = Layout::new(1, 3, Order::Y).stride(usize::MAX);
+    assert_eq!(l.len(), None);
+    assert!(matches!(decode(&y, &l), Err(Error::Decode(_))));
+}
@@ -80,6 +80,22 @@ macro_rules! alg {
                 // SAFETY: ctor
                 unsafe { Image::new(dst.width(), dst.height(), dst.into_vec().into()) }
             }
+
+            fn scale_linear<const N: usize>(
+                i: Image<&[u8], N>,
+                w: NonZeroU32,
+                h: NonZeroU32,
+            ) -> Image<std::boxed::Box<[u8]>, N>
+            where
+                ChannelCount<N>: Wide<N>,
+            {
+                linear_light::<<ChannelCount<N> as Wide<N>>::P, N>(
+                    i,
+                    w,
+                    h,
+                    fr::ResizeAlg::Convolution(fr::FilterType::$for),
+                )
+            }
         }
     };
 }
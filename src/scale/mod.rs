@@ -16,7 +16,12 @@
 //! let i = Image::<_, 3>::open("tdata/small_cat.png");
 //! let scaled = i.scale::<Lanczos3>(2144, 1424);
 //! ```
-use crate::Image;
+use crate::{
+    Image,
+    convert::u16_to_u8,
+    linear::{linear, srgb},
+};
+use std::sync::LazyLock;
 
 mod algorithms;
 pub mod traits;
@@ -69,6 +74,91 @@ transparent!(2, YA);
 opaque!(3, RGB);
 transparent!(4, RGBA);
 
+impl<T: AsRef<[u8]>, const N: usize> Image<T, N> {
+    /// Scale this image in linear light, with a given scaling algorithm.
+    ///
+    /// [`scale`](Image::scale) mixes sRGB values, which darkens fine detail when downscaling.
+    /// This converts to 16 bit linear light (premultiplying alpha), scales, and converts back.
+    pub fn scale_linear<A: traits::ScalingAlgorithm>(
+        &self,
+        width: u32,
+        height: u32,
+    ) -> Image<std::boxed::Box<[u8]>, N>
+    where
+        traits::ChannelCount<N>: traits::Wide<N>,
+    {
+        A::scale_linear(
+            self.as_ref(),
+            width.try_into().unwrap(),
+            height.try_into().unwrap(),
+        )
+    }
+}
+
+static WIDEN: LazyLock<[u16; 256]> =
+    LazyLock::new(|| std::array::from_fn(|x| (linear(x as f32 / 255.0) * 65535.0).round() as u16));
+
+static NARROW: LazyLock<std::boxed::Box<[u8]>> = LazyLock::new(|| {
+    (0..=u16::MAX)
+        .map(|x| (srgb(x as f32 / 65535.0) * 255.0).round() as u8)
+        .collect()
+});
+
+/// sRGB to premultiplied 16 bit linear light.
+fn widen<const N: usize>(i: Image<&[u8], N>) -> Vec<u16> {
+    let alpha = N == 2 || N == 4;
+    i.chunked()
+        .flat_map(|p| {
+            let a = if alpha { p[N - 1] as u32 * 257 } else { 65535 };
+            std::array::from_fn::<u16, N, _>(|c| match c {
+                _ if alpha && c == N - 1 => a as u16,
+                _ => ((WIDEN[p[c] as usize] as u32 * a + 32767) / 65535) as u16,
+            })
+        })
+        .collect()
+}
+
+/// The inverse of [`widen`], from native endian bytes.
+fn narrow<const N: usize>(x: &[u8]) -> std::boxed::Box<[u8]> {
+    let alpha = N == 2 || N == 4;
+    x.as_chunks::<2>()
+        .0
+        .as_chunks::<N>()
+        .0
+        .iter()
+        .flat_map(|p| {
+            let p = p.map(u16::from_ne_bytes);
+            let a = if alpha { p[N - 1] as u32 } else { 65535 };
+            std::array::from_fn::<u8, N, _>(|c| match c {
+                _ if alpha && c == N - 1 => u16_to_u8(p[c]),
+                _ if a == 0 => 0,
+                _ => NARROW[((p[c] as u32 * 65535 + a / 2) / a).min(65535) as usize],
+            })
+        })
+        .collect()
+}
+
+/// Scale in linear light, with 16 bit pixels `P`.
+fn linear_light<P: fr::PixelExt + fr::Convolution, const N: usize>(
+    i: Image<&[u8], N>,
+    w: std::num::NonZeroU32,
+    h: std::num::NonZeroU32,
+    alg: fr::ResizeAlg,
+) -> Image<std::boxed::Box<[u8]>, N> {
+    let (width, height) = (i.width, i.height);
+    let mut wide = widen(i);
+    // SAFETY: a u16 is two initialized bytes, and the u16s are aligned for P
+    let bytes =
+        unsafe { std::slice::from_raw_parts_mut(wide.as_mut_ptr().cast::<u8>(), wide.len() * 2) };
+    // SAFETY: N 16 bit channels per pixel, like P
+    let src = unsafe { fr::Image::<P>::from_slice_u8(width, height, bytes) };
+    let mut dst = fr::Image::<P>::new(w, h);
+    // SAFETY: swear, the pixel types are the same
+    unsafe { fr::Resizer::new(alg).resize(&src.view(), &mut dst.view_mut()) };
+    // SAFETY: ctor
+    unsafe { Image::new(dst.width(), dst.height(), narrow::<N>(&dst.into_vec())) }
+}
+
 #[test]
 fn test_nearest() {
     let i = Image::<_, 3>::open("tdata/cat.png");
@@ -77,3 +167,32 @@ fn test_nearest() {
         &*Image::<_, 3>::open("tdata/small_cat.png").buffer
     );
 }
+
+#[test]
+fn test_linear() {
+    // a fine checkerboard averages to half the light, not half the sRGB value
+    let buf = (0..64u32)
+        .flat_map(|i| [((i + i / 8) % 2 * 255) as u8, 255])
+        .collect::<Vec<_>>();
+    let mut i = Image::<_, 2>::build(8, 8).buf(buf);
+    let naive = i.scale::<Box>(4, 4);
+    let linear = i.scale_linear::<Box>(4, 4);
+    assert!(
+        naive
+            .chunked()
+            .all(|&[y, a]| y.abs_diff(128) <= 1 && a == 255)
+    );
+    assert!(
+        linear
+            .chunked()
+            .all(|&[y, a]| y.abs_diff(188) <= 1 && a == 255)
+    );
+    assert_eq!(
+        i.scale_linear::<Nearest>(4, 4).bytes(),
+        i.scale::<Nearest>(4, 4).bytes()
+    );
+    // exact at the same size
+    let cat = Image::<_, 4>::open("tdata/small_cat.png");
+    let wide = widen(cat.as_ref()).into_iter().flat_map(u16::to_ne_bytes);
+    assert_eq!(&*narrow::<4>(&wide.collect::<Vec<_>>()), cat.bytes());
+}
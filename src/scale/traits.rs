@@ -33,6 +33,19 @@ pub trait ScalingAlgorithm {
     ) -> Image<Box<[u8]>, N>
     where
         ChannelCount<N>: AlphaDiv<N>;
+    /// Scale in linear light. Alpha, if any, is premultiplied.
+    ///
+    /// Defaults to [`scale_opaque`](Self::scale_opaque), which is right for algorithms that dont mix pixels.
+    fn scale_linear<const N: usize>(
+        i: Image<&[u8], N>,
+        w: NonZeroU32,
+        h: NonZeroU32,
+    ) -> Image<Box<[u8]>, N>
+    where
+        ChannelCount<N>: Wide<N>,
+    {
+        Self::scale_opaque(i, w, h)
+    }
 }
 
 /// helper
@@ -53,6 +66,12 @@ pub trait AlphaDiv<const N: usize>: Sealed + ToImageView<N> {
     fn unhandle(i: &mut fr::Image<<Self as AlphaDiv<N>>::P>);
 }
 
+/// helper
+pub trait Wide<const N: usize>: Sealed + ToImageView<N> {
+    #[doc(hidden)]
+    type P: fr::PixelExt + fr::Convolution;
+}
+
 /// Generic helper for [`Image`] and [`fr::Image`] transfers.
 pub struct ChannelCount<const N: usize> {}
 
@@ -96,3 +115,16 @@ macro_rules! adiv {
 
 adiv!(2, U8x2);
 adiv!(4, U8x4);
+
+macro_rules! wide {
+    ($n:literal, $which:ident) => {
+        impl Wide<$n> for ChannelCount<$n> {
+            type P = fr::$which;
+        }
+    };
+}
+
+wide!(1, U16);
+wide!(2, U16x2);
+wide!(3, U16x3);
+wide!(4, U16x4);
@@ -0,0 +1,143 @@
+//! perceptual color spaces.
+//!
+//! convert rgb(a) images to float images in a [`ColorSpace`], edit them, and convert back.
+//! the pixel conversions live in [`pixels::convert`](crate::pixels::convert).
+//!
+//! ```
+//! # use fimg::{Image, space::Oklab};
+//! let i = Image::<_, 3>::build(2, 1).buf(vec![255u8, 0, 0, 0, 0, 255]);
+//! // darken, perceptually
+//! let darker = i.in_space::<Oklab>(|[l, a, b]| [l * 0.8, a, b]);
+//! // or keep the float image around
+//! let lab = i.to_space::<Oklab, u8>();
+//! let back = lab.from_space::<Oklab>().to_u8();
+//! ```
+use crate::{Image, pixels::convert as px};
+
+/// A color space, with three channels.
+pub trait ColorSpace {
+    /// Convert from sRGB (0.0-1.0).
+    fn from_rgb(rgb: [f32; 3]) -> [f32; 3];
+    /// Convert to sRGB (0.0-1.0). Out of gamut colors may leave 0.0-1.0.
+    fn to_rgb(x: [f32; 3]) -> [f32; 3];
+}
+
+macro_rules! space {
+    ($(#[$doc:meta])* $name:ident, $from:ident, $to:ident) => {
+        $(#[$doc])*
+        pub struct $name;
+        impl ColorSpace for $name {
+            fn from_rgb(rgb: [f32; 3]) -> [f32; 3] {
+                px::$from(rgb)
+            }
+
+            fn to_rgb(x: [f32; 3]) -> [f32; 3] {
+                px::$to(x)
+            }
+        }
+    };
+}
+
+space!(
+    /// Hue (degrees), saturation, value. See [`px::rgb_to_hsv`].
+    Hsv, rgb_to_hsv, hsv_to_rgb
+);
+space!(
+    /// Hue (degrees), saturation, lightness. See [`px::rgb_to_hsl`].
+    Hsl, rgb_to_hsl, hsl_to_rgb
+);
+space!(
+    /// CIELAB, under D65. See [`px::rgb_to_lab`].
+    Lab, rgb_to_lab, lab_to_rgb
+);
+space!(
+    /// [OKLab](https://bottosson.github.io/posts/oklab/). See [`px::rgb_to_oklab`].
+    Oklab, rgb_to_oklab, oklab_to_rgb
+);
+space!(
+    /// OKLCH: lightness, chroma and hue (degrees). See [`px::rgb_to_oklch`].
+    Oklch, rgb_to_oklch, oklch_to_rgb
+);
+
+/// Apply `f` to the color of every pixel.
+fn each<const N: usize>(
+    x: Image<Box<[f32]>, N>,
+    f: impl Fn([f32; 3]) -> [f32; 3],
+) -> Image<Box<[f32]>, N> {
+    // SAFETY: length unchanged
+    unsafe {
+        x.mapped(|mut x| {
+            for p in x.as_chunks_mut::<N>().0 {
+                let rgb = f([p[0], p[1], p[2]]);
+                p[..3].copy_from_slice(&rgb);
+            }
+            x
+        })
+    }
+}
+
+fn clamp(rgb: [f32; 3]) -> [f32; 3] {
+    rgb.map(|x| x.clamp(0.0, 1.0))
+}
+
+macro_rules! conv {
+    ($n:literal) => {
+        impl<T> Image<T, $n> {
+            /// Convert this (sRGB) image to a color space. Alpha is kept.
+            pub fn to_space<S: ColorSpace, U>(&self) -> Image<Box<[f32]>, $n>
+            where
+                T: AsRef<[U]>,
+                for<'a> Image<Box<[f32]>, $n>: From<Image<&'a [U], $n>>,
+            {
+                each(self.to_f32(), S::from_rgb)
+            }
+
+            /// Convert this image, in a color space, back to sRGB (clamped to 0.0-1.0). Alpha is kept.
+            pub fn from_space<S: ColorSpace>(&self) -> Image<Box<[f32]>, $n>
+            where
+                T: AsRef<[f32]>,
+            {
+                // SAFETY: length unchanged
+                let x = unsafe { self.as_ref().mapped(Box::<[f32]>::from) };
+                each(x, |x| clamp(S::to_rgb(x)))
+            }
+
+            /// Edit the colors of this image in a color space. Alpha is kept.
+            /// ```
+            /// # use fimg::{Image, space::Hsv};
+            /// let i = Image::<_, 3>::build(2, 1).buf(vec![255u8, 0, 0, 0, 0, 255]);
+            /// // rotate the hue: red to cyan, blue to yellow
+            /// let edited = i.in_space::<Hsv>(|[h, s, v]| [h + 180.0, s, v]);
+            /// assert_eq!(edited.bytes(), [0, 255, 255, 255, 255, 0]);
+            /// ```
+            pub fn in_space<S: ColorSpace>(
+                &self,
+                f: impl Fn([f32; 3]) -> [f32; 3],
+            ) -> Image<Box<[u8]>, $n>
+            where
+                T: AsRef<[u8]>,
+            {
+                each(self.to_f32(), |x| clamp(S::to_rgb(f(S::from_rgb(x))))).to_u8()
+            }
+        }
+    };
+}
+
+conv!(3);
+conv!(4);
+
+#[test]
+fn roundtrip() {
+    let cat = Image::<_, 4>::open("tdata/small_cat.png");
+    fn check<S: ColorSpace>(cat: &Image<Vec<u8>, 4>) {
+        assert_eq!(
+            cat.to_space::<S, u8>().from_space::<S>().to_u8().bytes(),
+            cat.bytes()
+        );
+    }
+    check::<Hsv>(&cat);
+    check::<Hsl>(&cat);
+    check::<Lab>(&cat);
+    check::<Oklab>(&cat);
+    check::<Oklch>(&cat);
+}
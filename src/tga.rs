@@ -0,0 +1,351 @@
+//! [TGA](https://en.wikipedia.org/wiki/Truevision_TGA) encoding and decoding.
+//!
+//! Reads colormapped, truecolor (15, 16, 24, and 32 bit), and grayscale images, optionally RLE compressed, in any orientation.
+use crate::{
+    DynImage, Error, Image,
+    indexed::{IndexedImage, Paletted},
+};
+
+/// Read a tga image.
+pub trait ReadTga: Sized {
+    /// Read a tga into an image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error>;
+}
+
+/// Write a tga image.
+pub trait WriteTga {
+    /// Write this tga image.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error>;
+}
+
+macro_rules! fail {
+    ($x:literal) => {
+        return Err(Error::Decode(concat!("tga: ", $x).into()))
+    };
+}
+
+/// Convert a little endian 15, 16, 24, or 32 bit pixel to RGBA.
+fn rgba(px: &[u8], alpha: bool) -> [u8; 4] {
+    match *px {
+        [a, b] => {
+            let x = u16::from_le_bytes([a, b]);
+            let c = |s: u16| ((x >> s & 31) as u32 * 255 / 31) as u8;
+            [
+                c(10),
+                c(5),
+                c(0),
+                if !alpha || x >> 15 == 1 { 255 } else { 0 },
+            ]
+        }
+        [b, g, r] => [r, g, b, 255],
+        [b, g, r, a] => [r, g, b, a],
+        _ => unreachable!(),
+    }
+}
+
+enum Pixels {
+    Indexed(Box<[u8]>, Box<[[u8; 4]]>, bool),
+    Y(Box<[u8]>),
+    Ya(Box<[u8]>),
+    Rgb(Box<[u8]>),
+    Rgba(Box<[u8]>),
+}
+
+fn parse(d: &[u8]) -> Result<(u32, u32, Pixels), Error> {
+    let Some((h, mut data)) = d.split_first_chunk::<18>() else {
+        fail!("missing header")
+    };
+    let [id, cmap, kind, ..] = *h;
+    let u16 = |at: usize| u16::from_le_bytes([h[at], h[at + 1]]) as usize;
+    let (first, entries, entry) = (u16(3), u16(5), h[7]);
+    let (w, ht, depth, desc) = (u16(12), u16(14), h[16], h[17]);
+    let alpha = desc & 0x0f != 0;
+    let Some(rest) = data.get(id as usize..) else {
+        fail!("unexpected end of data")
+    };
+    data = rest;
+
+    let palette = match cmap {
+        0 => None,
+        1 => {
+            if !matches!(entry, 15 | 16 | 24 | 32) {
+                fail!("unsupported colormap entry size")
+            }
+            let size = (entry as usize).div_ceil(8);
+            let Some(map) = data.get(..entries * size) else {
+                fail!("unexpected end of data")
+            };
+            data = &data[entries * size..];
+            Some(
+                map.chunks_exact(size)
+                    .map(|x| rgba(x, alpha))
+                    .collect::<Vec<_>>(),
+            )
+        }
+        _ => fail!("bad colormap type"),
+    };
+
+    let bytes = match (kind & !8, depth) {
+        (1, 8 | 16) if palette.is_some() => depth as usize / 8,
+        (2, 15 | 16 | 24 | 32) | (3, 8 | 16) => (depth as usize).div_ceil(8),
+        (1..=3, _) => fail!("unsupported bit depth"),
+        _ => fail!("unsupported image type"),
+    };
+    let n = w * ht;
+    let mut raw = Vec::with_capacity(n.min(data.len()) * bytes);
+    if kind & 8 == 0 {
+        let Some(x) = data.get(..n * bytes) else {
+            fail!("unexpected end of data")
+        };
+        raw.extend_from_slice(x);
+    } else {
+        while raw.len() < n * bytes {
+            let Some((&p, rest)) = data.split_first() else {
+                fail!("unexpected end of data")
+            };
+            let count = (p & 0x7f) as usize + 1;
+            let len = if p & 0x80 != 0 { bytes } else { bytes * count };
+            let Some(px) = rest.get(..len) else {
+                fail!("unexpected end of data")
+            };
+            data = &rest[len..];
+            if p & 0x80 != 0 {
+                for _ in 0..count {
+                    raw.extend_from_slice(px);
+                }
+            } else {
+                raw.extend_from_slice(px);
+            }
+        }
+        raw.truncate(n * bytes);
+    }
+
+    // reorient to top left
+    let (right_to_left, top_down) = (desc & 0x10 != 0, desc & 0x20 != 0);
+    if !top_down || right_to_left {
+        let row = w * bytes;
+        let mut out = Vec::with_capacity(raw.len());
+        for y in 0..ht {
+            let y = if top_down { y } else { ht - 1 - y };
+            let row = &raw[y * row..][..row];
+            if right_to_left {
+                out.extend(row.chunks_exact(bytes).rev().flatten());
+            } else {
+                out.extend_from_slice(row);
+            }
+        }
+        raw = out;
+    }
+
+    let pixels = match (kind & !8, bytes) {
+        (1, _) => {
+            let mut palette = palette.unwrap_or_default();
+            let index = |i: usize| i.saturating_sub(first).min(255) as u8;
+            let indices = match bytes {
+                1 => raw.iter().map(|&x| index(x as usize)).collect(),
+                _ => raw
+                    .chunks_exact(2)
+                    .map(|x| index(u16::from_le_bytes([x[0], x[1]]) as usize))
+                    .collect(),
+            };
+            palette.resize(256, [0, 0, 0, 255]);
+            let has_alpha = entry == 32 || (entry == 16 && alpha);
+            Pixels::Indexed(indices, palette.into(), has_alpha)
+        }
+        (3, 1) => Pixels::Y(raw.into()),
+        (3, _) => Pixels::Ya(raw.into()),
+        (_, 3) => Pixels::Rgb(
+            raw.chunks_exact(3)
+                .flat_map(|x| [x[2], x[1], x[0]])
+                .collect(),
+        ),
+        (_, 4) => Pixels::Rgba(raw.chunks_exact(4).flat_map(|x| rgba(x, true)).collect()),
+        _ if alpha => Pixels::Rgba(raw.chunks_exact(2).flat_map(|x| rgba(x, true)).collect()),
+        _ => Pixels::Rgb(
+            raw.chunks_exact(2)
+                .flat_map(|x| {
+                    let [r, g, b, _] = rgba(x, false);
+                    [r, g, b]
+                })
+                .collect(),
+        ),
+    };
+    Ok((w as u32, ht as u32, pixels))
+}
+
+/// Decode a tga image. Colormapped images are expanded.
+pub fn decode(data: &[u8]) -> Result<DynImage<Box<[u8]>>, Error> {
+    let (w, h, pixels) = parse(data)?;
+    macro_rules! n {
+        ($n:literal, $buf:expr) => {
+            Image::<_, $n>::build(w, h).try_buf($buf)?.into()
+        };
+    }
+    Ok(match pixels {
+        Pixels::Indexed(i, p, true) => n!(4, i.iter().flat_map(|&i| p[i as usize]).collect()),
+        Pixels::Indexed(i, p, false) => n!(
+            3,
+            i.iter()
+                .flat_map(|&i| &p[i as usize][..3])
+                .copied()
+                .collect()
+        ),
+        Pixels::Y(x) => n!(1, x),
+        Pixels::Ya(x) => n!(2, x),
+        Pixels::Rgb(x) => n!(3, x),
+        Pixels::Rgba(x) => n!(4, x),
+    })
+}
+
+/// Decode a colormapped tga image.
+pub fn decode_indexed(data: &[u8]) -> Result<Paletted, Error> {
+    let (w, h, pixels) = parse(data)?;
+    let Pixels::Indexed(i, p, _) = pixels else {
+        return Err(Error::UnsupportedColor("tga is not colormapped"));
+    };
+    IndexedImage::from_raw_parts(Image::build(w, h).try_buf(i)?, p)
+        .map_err(|x| Error::Decode(x.into()))
+}
+
+/// Encode an image to tga. Y and YA images are written as grayscale, RGB and RGBA as truecolor.
+/// If `rle`, the pixels are run length encoded.
+pub fn encode<const N: usize>(image: Image<&[u8], N>, rle: bool) -> Vec<u8>
+where
+    // tga images have 1 to 4 channels
+    [(); ((N >= 1) & (N <= 4)) as usize - 1]:,
+{
+    let mut out = vec![0, 0, if N < 3 { 3 } else { 2 } | if rle { 8 } else { 0 }];
+    out.extend([0; 9]);
+    out.extend((image.width() as u16).to_le_bytes());
+    out.extend((image.height() as u16).to_le_bytes());
+    // top left origin, alpha bits
+    out.extend([N as u8 * 8, 0x20 | if matches!(N, 2 | 4) { 8 } else { 0 }]);
+    let px = |p: &[u8]| -> [u8; N] {
+        let mut p: [u8; N] = p.try_into().unwrap();
+        if N >= 3 {
+            p.swap(0, 2);
+        }
+        p
+    };
+    let rows = image.buffer().chunks_exact(image.width() as usize * N);
+    if !rle {
+        out.extend(rows.flat_map(|x| x.chunks_exact(N)).flat_map(px));
+        return out;
+    }
+    // packets may not cross rows
+    for row in rows {
+        let mut row = row.chunks_exact(N).peekable();
+        let mut raw = vec![];
+        while let Some(p) = row.next() {
+            let mut run = 1;
+            while run < 128 && row.peek() == Some(&p) {
+                row.next();
+                run += 1;
+            }
+            if (run > 1 || raw.len() == 128) && !raw.is_empty() {
+                out.push(raw.len() as u8 - 1);
+                out.extend(raw.drain(..).flat_map(px));
+            }
+            if run > 1 {
+                out.push(0x80 | (run - 1));
+                out.extend(px(p));
+            } else {
+                raw.push(p);
+            }
+        }
+        if !raw.is_empty() {
+            out.push(raw.len() as u8 - 1);
+            out.extend(raw.drain(..).flat_map(px));
+        }
+    }
+    out
+}
+
+impl ReadTga for DynImage<Box<[u8]>> {
+    /// Read a tga image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        let mut data = vec![];
+        f.read_to_end(&mut data)?;
+        decode(&data)
+    }
+}
+
+impl ReadTga for Paletted {
+    /// Read a colormapped tga image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        let mut data = vec![];
+        f.read_to_end(&mut data)?;
+        decode_indexed(&data)
+    }
+}
+
+impl<const N: usize> ReadTga for Image<Box<[u8]>, N>
+where
+    Self: From<DynImage<Box<[u8]>>>,
+{
+    /// Read a tga image, converting it to this many channels.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        DynImage::read(f).map(Into::into)
+    }
+}
+
+impl<T: AsRef<[u8]>, const N: usize> WriteTga for Image<T, N>
+where
+    [(); ((N >= 1) & (N <= 4)) as usize - 1]:,
+{
+    /// Write this image as a RLE compressed tga.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        if self.width() > u16::MAX as u32 || self.height() > u16::MAX as u32 {
+            return Err(Error::Encode("tga: image too large".into()));
+        }
+        f.write_all(&encode(self.as_ref(), true))?;
+        Ok(())
+    }
+}
+
+impl<T: AsRef<[u8]> + crate::Buffer> WriteTga for DynImage<T> {
+    /// Write this image as a RLE compressed tga. 16 bit images are reduced to 8 bit.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        crate::r#dyn::e!(narrow self, |i| WriteTga::write(i, f))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "save")]
+    #[test]
+    fn roundtrip() {
+        let cat = Image::<_, 4>::open("tdata/small_cat.png");
+        let rgb = Image::<Box<[u8]>, 3>::from(cat.as_ref());
+        let y = Image::<Box<[u8]>, 1>::from(cat.as_ref());
+        for rle in [false, true] {
+            assert_eq!(
+                decode(&encode(cat.as_ref(), rle)).unwrap().bytes(),
+                cat.bytes()
+            );
+            assert_eq!(
+                decode(&encode(rgb.as_ref(), rle)).unwrap().bytes(),
+                rgb.bytes()
+            );
+            assert_eq!(decode(&encode(y.as_ref(), rle)).unwrap().bytes(), y.bytes());
+        }
+    }
+
+    #[test]
+    fn colormapped() {
+        // 2x2, bottom left origin, RLE, 24 bit colormap of 2 entries starting at 1
+        let mut data = vec![0, 1, 9, 1, 0, 2, 0, 24, 0, 0, 0, 0, 2, 0, 2, 0, 8, 0];
+        data.extend([0, 0, 255, 255, 0, 0]);
+        data.extend([0x81, 2, 0x01, 1, 2]);
+        let i = decode_indexed(&data).unwrap();
+        let (buf, pal) = i.into_raw_parts();
+        assert_eq!(&**buf.buffer(), [0, 1, 1, 1]);
+        assert_eq!(&pal[..2], [[255, 0, 0, 255], [0, 0, 255, 255]]);
+        assert_eq!(
+            decode(&data).unwrap().bytes(),
+            [255, 0, 0, 0, 0, 255, 0, 0, 255, 0, 0, 255]
+        );
+    }
+}
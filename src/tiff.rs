@@ -0,0 +1,899 @@
+//! [TIFF](https://en.wikipedia.org/wiki/TIFF) encoding and decoding.
+//!
+//! Reads the first image of a tiff: strips or tiles, uncompressed, PackBits, LZW, or Deflate compressed,
+//! with 1 to 16 bit gray, RGB (either with alpha), or paletted samples.
+//! Writes 8 and 16 bit gray, gray alpha, RGB, and RGBA strips.
+use crate::{
+    DynImage, Error, Image,
+    indexed::{IndexedImage, Paletted},
+};
+use std::collections::HashMap;
+
+/// Read a tiff image.
+pub trait ReadTiff: Sized {
+    /// Read a tiff into an image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error>;
+}
+
+/// Write a tiff image.
+///
+/// `C` is the component type: `u8` for 8 bit images, `u16` for 16 bit images.
+pub trait WriteTiff<C = u8> {
+    /// Write this tiff image, with [`Compression::Lzw`].
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        self.write_with(f, Compression::default())
+    }
+
+    /// Write this tiff image, with this compression.
+    fn write_with(
+        &self,
+        f: &mut impl std::io::Write,
+        compression: Compression,
+    ) -> Result<(), Error>;
+}
+
+macro_rules! fail {
+    ($x:literal) => {
+        return Err(Error::Decode(concat!("tiff: ", $x).into()))
+    };
+}
+
+/// How the image data of a tiff is compressed.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Compression {
+    /// Uncompressed.
+    None,
+    /// Run length encoding, per row.
+    PackBits,
+    /// Lempel-Ziv-Welch, with a horizontal predictor.
+    #[default]
+    Lzw,
+    /// zlib, with a horizontal predictor.
+    Deflate,
+}
+
+impl Compression {
+    const fn tag(self) -> u32 {
+        match self {
+            Self::None => 1,
+            Self::PackBits => 32773,
+            Self::Lzw => 5,
+            Self::Deflate => 8,
+        }
+    }
+}
+
+/// same limit as [`qoi`](crate::qoi).
+const MAX_PIXELS: usize = 400_000_000;
+/// The most samples of a decoded image: four for every pixel.
+const MAX_SAMPLES: usize = MAX_PIXELS * 4;
+
+/// Bytes of a tiff, in its byte order.
+struct Data<'a> {
+    data: &'a [u8],
+    big: bool,
+}
+
+impl Data<'_> {
+    fn u16(&self, at: usize) -> Option<u16> {
+        let &x = self.data.get(at..)?.first_chunk::<2>()?;
+        Some(match self.big {
+            true => u16::from_be_bytes(x),
+            false => u16::from_le_bytes(x),
+        })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let &x = self.data.get(at..)?.first_chunk::<4>()?;
+        Some(match self.big {
+            true => u32::from_be_bytes(x),
+            false => u32::from_le_bytes(x),
+        })
+    }
+
+    /// The integer tags of the image directory at `at`.
+    fn ifd(&self, at: usize) -> Result<HashMap<u16, Vec<u32>>, Error> {
+        let Some(n) = self.u16(at) else {
+            fail!("unexpected end of data")
+        };
+        let mut tags = HashMap::new();
+        for e in (0..n as usize).map(|i| at + 2 + i * 12) {
+            let (Some(tag), Some(kind), Some(count)) =
+                (self.u16(e), self.u16(e + 2), self.u32(e + 4))
+            else {
+                fail!("unexpected end of data")
+            };
+            let size = match kind {
+                // bytes
+                1 | 6 | 7 => 1,
+                // shorts
+                3 | 8 => 2,
+                // longs
+                4 | 9 => 4,
+                // text, rationals, floats
+                _ => continue,
+            };
+            let count = count as usize;
+            let mut at = e + 8;
+            if size * count > 4 {
+                at = self.u32(at).unwrap_or_default() as usize;
+            }
+            if self.data.len() < at.saturating_add(size * count) {
+                fail!("tag past the end of the data")
+            }
+            let values = (0..count)
+                .map(|i| {
+                    let at = at + i * size;
+                    match size {
+                        1 => self.data[at] as u32,
+                        2 => self.u16(at).unwrap() as u32,
+                        _ => self.u32(at).unwrap(),
+                    }
+                })
+                .collect();
+            tags.insert(tag, values);
+        }
+        Ok(tags)
+    }
+}
+
+/// The samples of a tiff.
+struct Decoded {
+    width: u32,
+    height: u32,
+    bits: usize,
+    photometric: u32,
+    /// samples per pixel
+    spp: usize,
+    /// the first extra sample, if any
+    extra: Option<u32>,
+    samples: Vec<u16>,
+    /// the color map of a paletted image
+    palette: Option<Vec<[u8; 4]>>,
+}
+
+fn parse(data: &[u8]) -> Result<Decoded, Error> {
+    let big = match data {
+        [b'I', b'I', 42, 0, ..] => false,
+        [b'M', b'M', 0, 42, ..] => true,
+        [b'I', b'I', 43, 0, ..] | [b'M', b'M', 0, 43, ..] => fail!("bigtiff is not supported"),
+        _ => fail!("bad magic"),
+    };
+    let d = Data { data, big };
+    let tags = d.ifd(d.u32(4).unwrap_or_default() as usize)?;
+    let get = |t: u16| tags.get(&t).and_then(|v| v.first().copied());
+    let (Some(width), Some(height)) = (get(256), get(257)) else {
+        fail!("missing size")
+    };
+    let (w, h) = (width as usize, height as usize);
+    if w == 0 || h == 0 {
+        fail!("bad size")
+    }
+    if w.checked_mul(h).is_none_or(|x| x > MAX_PIXELS) {
+        fail!("image too large")
+    }
+    let spp = get(277).unwrap_or(1) as usize;
+    if !(1..=16).contains(&spp) {
+        fail!("bad samples per pixel")
+    }
+    let bits = tags
+        .get(&258)
+        .filter(|x| !x.is_empty())
+        .map_or(&[1][..], |x| &x[..]);
+    if bits.iter().any(|&x| x != bits[0]) {
+        return Err(Error::UnsupportedColor("tiff: mixed bit depths"));
+    }
+    let bits = bits[0] as usize;
+    if !matches!(bits, 1 | 2 | 4 | 8 | 16) {
+        return Err(Error::UnsupportedColor("tiff: bit depth"));
+    }
+    if get(339).is_some_and(|x| x != 1) {
+        return Err(Error::UnsupportedColor("tiff: non integer samples"));
+    }
+    let Some(photometric) = get(262) else {
+        fail!("missing photometric interpretation")
+    };
+    let color = match photometric {
+        0..=1 => 1,
+        2 => 3,
+        3 if bits <= 8 => 1,
+        _ => return Err(Error::UnsupportedColor("tiff: photometric interpretation")),
+    };
+    if spp < color {
+        fail!("too few samples per pixel")
+    }
+    let palette = match photometric {
+        3 => {
+            let n = 1 << bits;
+            let Some(map) = tags.get(&320).filter(|x| x.len() == n * 3) else {
+                fail!("missing color map")
+            };
+            Some(
+                (0..n)
+                    .map(|i| [i, n + i, 2 * n + i].map(|i| (map[i] >> 8) as u8))
+                    .map(|[r, g, b]| [r, g, b, 255])
+                    .collect(),
+            )
+        }
+        _ => None,
+    };
+    let planar = get(284) == Some(2);
+    let predictor = match get(317).unwrap_or(1) {
+        x @ 1..=2 => x,
+        _ => fail!("unsupported predictor"),
+    };
+    let compression = get(259).unwrap_or(1);
+
+    // strips are tiles as wide as the image
+    let (offsets, counts, cw, ch) = match get(322) {
+        Some(tw) => (324, 325, tw, get(323).unwrap_or_default()),
+        None => (273, 279, width, get(278).unwrap_or(height).min(height)),
+    };
+    let (Some(offsets), Some(counts)) = (tags.get(&offsets), tags.get(&counts)) else {
+        fail!("missing image data")
+    };
+    let (cw, ch) = (cw as usize, ch as usize);
+    // tiles are multiples of 16, and need not be larger than that
+    if cw == 0 || ch == 0 || cw > w.next_multiple_of(16) || ch > h.next_multiple_of(16) {
+        fail!("bad tile size")
+    }
+    let (across, down) = (w.div_ceil(cw), h.div_ceil(ch));
+    let planes = if planar { spp } else { 1 };
+    // samples per pixel of a chunk
+    let spc = if planar { 1 } else { spp };
+    let chunks = across * down * planes;
+    if offsets.len() < chunks || counts.len() < chunks {
+        fail!("missing image data")
+    }
+    let Some(row_bytes) = cw
+        .checked_mul(spc * bits)
+        .map(|x| x.div_ceil(8))
+        .filter(|x| x.checked_mul(ch).is_some())
+    else {
+        fail!("image too large")
+    };
+    let mask = if bits == 16 { 0xffff } else { (1 << bits) - 1 };
+    let Some(size) = (w * h).checked_mul(spp).filter(|&x| x <= MAX_SAMPLES) else {
+        fail!("image too large")
+    };
+    let mut samples = vec![0; size];
+    let mut row = vec![0; cw * spc];
+    for i in 0..chunks {
+        let (plane, y, x) = (i / (across * down), i / across % down * ch, i % across * cw);
+        let (at, len) = (offsets[i] as usize, counts[i] as usize);
+        let Some(chunk) = data.get(at..at.saturating_add(len)) else {
+            fail!("image data past the end of the data")
+        };
+        // the last strip may be short, and the rows past the image are not needed
+        let rows = ch.min(h - y);
+        let raw = decompress(compression, chunk, row_bytes * rows)?;
+        let cols = cw.min(w - x);
+        for (dy, bytes) in raw.chunks_exact(row_bytes).enumerate() {
+            unpack(bytes, bits, big, &mut row);
+            if predictor == 2 {
+                for i in spc..row.len() {
+                    row[i] = row[i].wrapping_add(row[i - spc]) & mask;
+                }
+            }
+            let to = &mut samples[((y + dy) * w + x) * spp..][..cols * spp];
+            for (to, from) in to.chunks_exact_mut(spp).zip(row.chunks_exact(spc)) {
+                to[plane..plane + spc].copy_from_slice(from);
+            }
+        }
+    }
+    Ok(Decoded {
+        width,
+        height,
+        bits,
+        photometric,
+        spp,
+        extra: (spp > color).then(|| get(338)).flatten(),
+        samples,
+        palette,
+    })
+}
+
+/// Unpack a row of `bits` bit samples.
+fn unpack(bytes: &[u8], bits: usize, big: bool, out: &mut [u16]) {
+    match bits {
+        8 => out.iter_mut().zip(bytes).for_each(|(o, &x)| *o = x as u16),
+        16 => {
+            for (o, &x) in out.iter_mut().zip(bytes.as_chunks::<2>().0) {
+                *o = if big {
+                    u16::from_be_bytes(x)
+                } else {
+                    u16::from_le_bytes(x)
+                };
+            }
+        }
+        _ => {
+            for (i, o) in out.iter_mut().enumerate() {
+                let bit = i * bits;
+                *o = (bytes[bit / 8] >> (8 - bits - bit % 8) & ((1 << bits) - 1)) as u16;
+            }
+        }
+    }
+}
+
+/// Decompress a strip (or tile), to `len` bytes. Short data is padded with zeroes.
+fn decompress(compression: u32, data: &[u8], len: usize) -> Result<Vec<u8>, Error> {
+    let mut out = match compression {
+        1 => data[..len.min(data.len())].to_vec(),
+        5 => unlzw(data, len)?,
+        8 | 32946 => match miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(data, len) {
+            Ok(x) => x,
+            Err(x) if x.status == miniz_oxide::inflate::TINFLStatus::HasMoreOutput => x.output,
+            Err(x) => return Err(Error::Decode(format!("tiff: {x}").into())),
+        },
+        32773 => unpackbits(data, len),
+        _ => return Err(Error::UnsupportedColor("tiff: compression")),
+    };
+    if out.len() < len {
+        fail!("truncated image data")
+    }
+    out.truncate(len);
+    Ok(out)
+}
+
+fn unpackbits(mut data: &[u8], len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len);
+    while out.len() < len
+        && let Some((&n, rest)) = data.split_first()
+    {
+        data = rest;
+        match n {
+            0..=127 => {
+                let (literal, rest) = data.split_at((n as usize + 1).min(data.len()));
+                out.extend(literal);
+                data = rest;
+            }
+            // no-op
+            128 => {}
+            _ => {
+                if let Some((&x, rest)) = data.split_first() {
+                    out.extend(std::iter::repeat_n(x, 257 - n as usize));
+                    data = rest;
+                }
+            }
+        }
+    }
+    out
+}
+
+fn packbits(row: &[u8], out: &mut Vec<u8>) {
+    let run = |i: usize| {
+        row[i..]
+            .iter()
+            .take(128)
+            .take_while(|&&x| x == row[i])
+            .count()
+    };
+    let mut i = 0;
+    while i < row.len() {
+        let r = run(i);
+        if r >= 3 {
+            out.extend([(257 - r) as u8, row[i]]);
+            i += r;
+            continue;
+        }
+        let mut j = i;
+        while j < row.len() && j - i < 128 && (j == i || run(j) < 3) {
+            j += 1;
+        }
+        out.push((j - i - 1) as u8);
+        out.extend(&row[i..j]);
+        i = j;
+    }
+}
+
+const CLEAR: u16 = 256;
+const END: u16 = 257;
+
+/// Decode (msb first, early change) lzw, stopping after `len` bytes.
+fn unlzw(data: &[u8], len: usize) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::with_capacity(len);
+    // the (start, length) in out of the strings of the codes from 258
+    let mut table = Vec::<(usize, usize)>::with_capacity(4096 - 258);
+    let mut prev = None::<(usize, usize)>;
+    let (mut acc, mut bits, mut width) = (0u32, 0, 9);
+    let mut data = data.iter();
+    while out.len() < len {
+        while bits < width {
+            let Some(&x) = data.next() else {
+                return Ok(out);
+            };
+            acc = acc << 8 | x as u32;
+            bits += 8;
+        }
+        bits -= width;
+        let code = (acc >> bits & ((1 << width) - 1)) as u16;
+        match code {
+            CLEAR => {
+                table.clear();
+                (prev, width) = (None, 9);
+                continue;
+            }
+            END => break,
+            _ => {}
+        }
+        let start = out.len();
+        let string = if code < 256 {
+            out.push(code as u8);
+            (start, 1)
+        } else if let Some(&(s, l)) = table.get(code as usize - 258) {
+            out.extend_from_within(s..s + l);
+            (start, l)
+        } else if let Some((s, l)) = prev
+            && code as usize == table.len() + 258
+        {
+            out.extend_from_within(s..s + l);
+            out.push(out[s]);
+            (start, l + 1)
+        } else {
+            fail!("bad lzw code")
+        };
+        // the previous string is followed by the first byte of this one
+        if let Some((s, l)) = prev
+            && table.len() < 4096 - 258
+        {
+            table.push((s, l + 1));
+        }
+        prev = Some(string);
+        if table.len() + 258 + 1 == 1 << width && width < 12 {
+            width += 1;
+        }
+    }
+    Ok(out)
+}
+
+/// Encode (msb first, early change) lzw.
+fn lzw(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    let (mut acc, mut bits) = (0u32, 0);
+    let mut emit = |code: u16, width: u32| {
+        acc = acc << width | code as u32;
+        bits += width;
+        while bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    };
+    let mut table = HashMap::<(u16, u8), u16>::new();
+    let (mut next, mut width) = (258, 9);
+    emit(CLEAR, width);
+    let mut string = None::<u16>;
+    for &x in data {
+        let Some(s) = string else {
+            string = Some(x as u16);
+            continue;
+        };
+        if let Some(&code) = table.get(&(s, x)) {
+            string = Some(code);
+            continue;
+        }
+        emit(s, width);
+        table.insert((s, x), next);
+        next += 1;
+        if next == 4094 {
+            emit(CLEAR, width);
+            table.clear();
+            (next, width) = (258, 9);
+        } else if next == 1 << width {
+            width += 1;
+        }
+        string = Some(x as u16);
+    }
+    if let Some(s) = string {
+        emit(s, width);
+        // the decoder adds a entry for this string too
+        if next + 1 == 1 << width && width < 12 {
+            width += 1;
+        }
+    }
+    emit(END, width);
+    emit(0, 7);
+    out
+}
+
+/// Write a little endian tiff, with these chunks of image data and these `(tag, type, values)` tags.
+/// The offsets and sizes of the chunks are written to the `chunk` tags.
+fn file(chunks: &[Vec<u8>], mut tags: Vec<(u16, u16, Vec<u32>)>, chunk: (u16, u16)) -> Vec<u8> {
+    let mut out = b"II*\0\0\0\0\0".to_vec();
+    let mut offsets = vec![];
+    for c in chunks {
+        offsets.push(out.len() as u32);
+        out.extend(c);
+    }
+    tags.push((chunk.0, 4, offsets));
+    tags.push((chunk.1, 4, chunks.iter().map(|x| x.len() as u32).collect()));
+    tags.sort_by_key(|x| x.0);
+    if out.len() % 2 == 1 {
+        out.push(0);
+    }
+    let at = out.len();
+    out[4..8].copy_from_slice(&(at as u32).to_le_bytes());
+    // values that do not fit in a entry come after the directory
+    let mut extra = at + 2 + tags.len() * 12 + 4;
+    let mut values = vec![];
+    out.extend((tags.len() as u16).to_le_bytes());
+    for (tag, kind, v) in tags {
+        let bytes = match kind {
+            3 => v.iter().flat_map(|&x| (x as u16).to_le_bytes()).collect(),
+            _ => v.iter().flat_map(|&x| x.to_le_bytes()).collect::<Vec<_>>(),
+        };
+        // rationals are two longs
+        let count = if kind == 5 { v.len() / 2 } else { v.len() };
+        out.extend(tag.to_le_bytes());
+        out.extend(kind.to_le_bytes());
+        out.extend((count as u32).to_le_bytes());
+        if bytes.len() <= 4 {
+            out.extend(&bytes);
+            out.resize(out.len() + 4 - bytes.len(), 0);
+        } else {
+            out.extend((extra as u32).to_le_bytes());
+            extra += bytes.len();
+            values.extend(bytes);
+        }
+    }
+    out.extend([0; 4]);
+    out.extend(values);
+    out
+}
+
+/// Write a tiff of rows of (already predicted, for lzw and deflate) little endian samples.
+fn write(
+    rows: &[u8],
+    (width, height): (u32, u32),
+    (channels, bits): (usize, usize),
+    compression: Compression,
+) -> Vec<u8> {
+    let row = width as usize * channels * bits / 8;
+    let rows_per_strip = (8192 / row).clamp(1, height as usize);
+    let chunks = rows
+        .chunks(row * rows_per_strip)
+        .map(|strip| match compression {
+            Compression::None => strip.to_vec(),
+            Compression::PackBits => {
+                let mut out = vec![];
+                strip.chunks_exact(row).for_each(|r| packbits(r, &mut out));
+                out
+            }
+            Compression::Lzw => lzw(strip),
+            Compression::Deflate => miniz_oxide::deflate::compress_to_vec_zlib(strip, 6),
+        })
+        .collect::<Vec<_>>();
+    let mut tags = vec![
+        (256, 4, vec![width]),
+        (257, 4, vec![height]),
+        (258, 3, vec![bits as u32; channels]),
+        (259, 3, vec![compression.tag()]),
+        (262, 3, vec![if channels < 3 { 1 } else { 2 }]),
+        (277, 3, vec![channels as u32]),
+        (278, 4, vec![rows_per_strip as u32]),
+        // 72 dpi
+        (282, 5, vec![72, 1]),
+        (283, 5, vec![72, 1]),
+        (284, 3, vec![1]),
+        (296, 3, vec![2]),
+    ];
+    if matches!(compression, Compression::Lzw | Compression::Deflate) {
+        tags.push((317, 3, vec![2]));
+    }
+    if channels % 2 == 0 {
+        // unassociated alpha
+        tags.push((338, 3, vec![2]));
+    }
+    file(&chunks, tags, (273, 279))
+}
+
+/// Encode an 8 bit image to a tiff.
+pub fn encode<const N: usize>(image: Image<&[u8], N>, compression: Compression) -> Vec<u8>
+where
+    [(); ((N >= 1) & (N <= 4)) as usize - 1]:,
+{
+    let mut rows = image.buffer().to_vec();
+    if matches!(compression, Compression::Lzw | Compression::Deflate) {
+        for row in rows.chunks_exact_mut(image.width() as usize * N) {
+            for i in (N..row.len()).rev() {
+                row[i] = row[i].wrapping_sub(row[i - N]);
+            }
+        }
+    }
+    write(&rows, (image.width(), image.height()), (N, 8), compression)
+}
+
+/// Encode a 16 bit image to a tiff.
+pub fn encode16<const N: usize>(image: Image<&[u16], N>, compression: Compression) -> Vec<u8>
+where
+    [(); ((N >= 1) & (N <= 4)) as usize - 1]:,
+{
+    let mut rows = image.buffer().to_vec();
+    if matches!(compression, Compression::Lzw | Compression::Deflate) {
+        for row in rows.chunks_exact_mut(image.width() as usize * N) {
+            for i in (N..row.len()).rev() {
+                row[i] = row[i].wrapping_sub(row[i - N]);
+            }
+        }
+    }
+    let rows = rows
+        .iter()
+        .flat_map(|x| x.to_le_bytes())
+        .collect::<Vec<_>>();
+    write(&rows, (image.width(), image.height()), (N, 16), compression)
+}
+
+/// Decode the first image of a tiff. Paletted images are expanded to RGB, and images of less than 8 bits become 8 bit.
+pub fn decode(data: &[u8]) -> Result<DynImage<Box<[u8]>>, Error> {
+    let Decoded {
+        width: w,
+        height: h,
+        bits,
+        photometric,
+        spp,
+        extra,
+        samples,
+        palette,
+    } = parse(data)?;
+    if let Some(palette) = palette {
+        let buf = samples
+            .iter()
+            .step_by(spp)
+            .flat_map(|&i| &palette[i as usize][..3])
+            .copied()
+            .collect();
+        return Ok(Image::<_, 3>::build(w, h).buf(buf).into());
+    }
+    let color = if photometric == 2 { 3 } else { 1 };
+    // 1: associated (premultiplied) alpha, 2: unassociated alpha
+    let alpha = matches!(extra, Some(1 | 2));
+    let n = color + alpha as usize;
+    let max = if bits == 16 { 0xffff } else { (1 << bits) - 1 };
+    let mut px = vec![];
+    for s in samples.chunks_exact(spp) {
+        let mut p = [0u32; 4];
+        for (p, &s) in p.iter_mut().zip(&s[..n]) {
+            *p = s as u32;
+        }
+        if photometric == 0 {
+            // white is zero
+            p[0] = max - p[0];
+        }
+        if extra == Some(1) && p[color] != 0 {
+            let a = p[color];
+            for c in &mut p[..color] {
+                *c = (*c * max / a).min(max);
+            }
+        }
+        px.extend(p[..n].iter().map(|&x| x as u16));
+    }
+    if bits == 16 {
+        let px = px.into_boxed_slice();
+        return Ok(match n {
+            1 => DynImage::Y16(Image::build(w, h).buf(px)),
+            2 => DynImage::Ya16(Image::build(w, h).buf(px)),
+            3 => DynImage::Rgb16(Image::build(w, h).buf(px)),
+            _ => DynImage::Rgba16(Image::build(w, h).buf(px)),
+        });
+    }
+    let px = px
+        .into_iter()
+        .map(|x| (x as u32 * 255 / max) as u8)
+        .collect::<Box<_>>();
+    Ok(match n {
+        1 => DynImage::Y(Image::build(w, h).buf(px)),
+        2 => DynImage::Ya(Image::build(w, h).buf(px)),
+        3 => DynImage::Rgb(Image::build(w, h).buf(px)),
+        _ => DynImage::Rgba(Image::build(w, h).buf(px)),
+    })
+}
+
+/// Decode the first image of a paletted (1, 2, 4, or 8 bit) tiff.
+pub fn decode_indexed(data: &[u8]) -> Result<Paletted, Error> {
+    let d = parse(data)?;
+    let Some(palette) = d.palette else {
+        return Err(Error::UnsupportedColor("tiff is not paletted"));
+    };
+    let i = d.samples.iter().step_by(d.spp).map(|&x| x as u8).collect();
+    IndexedImage::from_raw_parts(Image::build(d.width, d.height).buf(i), palette.into())
+        .map_err(|x| Error::Decode(x.into()))
+}
+
+impl ReadTiff for DynImage<Box<[u8]>> {
+    /// Read a tiff image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        let mut data = vec![];
+        f.read_to_end(&mut data)?;
+        decode(&data)
+    }
+}
+
+impl ReadTiff for Paletted {
+    /// Read a paletted tiff image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        let mut data = vec![];
+        f.read_to_end(&mut data)?;
+        decode_indexed(&data)
+    }
+}
+
+impl<const N: usize> ReadTiff for Image<Box<[u8]>, N>
+where
+    Self: From<DynImage<Box<[u8]>>>,
+{
+    /// Read a tiff image, converting it to this many channels, and 8 bits.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        DynImage::read(f).map(Into::into)
+    }
+}
+
+impl<const N: usize> ReadTiff for Image<Box<[u16]>, N>
+where
+    Self: From<DynImage<Box<[u8]>>>,
+{
+    /// Read a tiff image, converting it to this many channels.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        DynImage::read(f).map(Into::into)
+    }
+}
+
+macro_rules! writer {
+    ($n:literal) => {
+        impl<T: AsRef<[u8]>> WriteTiff for Image<T, $n> {
+            /// Write this image as a tiff.
+            fn write_with(
+                &self,
+                f: &mut impl std::io::Write,
+                compression: Compression,
+            ) -> Result<(), Error> {
+                f.write_all(&encode(self.as_ref(), compression))?;
+                Ok(())
+            }
+        }
+
+        impl<T: AsRef<[u16]>> WriteTiff<u16> for Image<T, $n> {
+            /// Write this 16 bit image as a tiff.
+            fn write_with(
+                &self,
+                f: &mut impl std::io::Write,
+                compression: Compression,
+            ) -> Result<(), Error> {
+                f.write_all(&encode16(self.as_ref(), compression))?;
+                Ok(())
+            }
+        }
+    };
+}
+writer!(1);
+writer!(2);
+writer!(3);
+writer!(4);
+
+impl<T: AsRef<[u8]> + crate::Buffer> WriteTiff for DynImage<T> {
+    /// Write this image as a tiff.
+    fn write_with(
+        &self,
+        f: &mut impl std::io::Write,
+        compression: Compression,
+    ) -> Result<(), Error> {
+        crate::r#dyn::e!(self, |i| WriteTiff::write_with(i, f, compression), |i| {
+            WriteTiff::<u16>::write_with(i, f, compression)
+        })
+    }
+}
+
+#[test]
+fn roundtrip() {
+    let buf = (0..97 * 61 * 4)
+        .map(|i: u32| (i * 7 % 251 + i / 700) as u8)
+        .collect::<Vec<_>>();
+    let i = Image::<_, 4>::build(97, 61).buf(&buf[..]);
+    let wide = i.to_u16();
+    for compression in [
+        Compression::None,
+        Compression::PackBits,
+        Compression::Lzw,
+        Compression::Deflate,
+    ] {
+        let tiff = encode(i, compression);
+        let back = Image::<Box<[u8]>, 4>::from(decode(&tiff).unwrap());
+        assert_eq!(back.bytes(), i.bytes(), "{compression:?}");
+        let y = Image::<Box<[u8]>, 1>::from(i);
+        let back = decode(&encode(y.as_ref(), compression)).unwrap();
+        assert!(matches!(back, DynImage::Y(_)));
+        assert_eq!(back.to_y().bytes(), y.bytes());
+        let tiff = encode16(wide.as_ref(), compression);
+        let back = Image::<Box<[u16]>, 4>::from(decode(&tiff).unwrap());
+        assert_eq!(back.buffer(), wide.buffer(), "{compression:?}");
+    }
+    // long enough for the lzw table to fill up
+    let noise = (0..300_000u32)
+        .map(|x| (x.wrapping_mul(2_654_435_761) >> 24) as u8)
+        .collect::<Vec<_>>();
+    assert_eq!(unlzw(&lzw(&noise), noise.len()).unwrap(), noise);
+    let flat = vec![7; 100_000];
+    assert!(lzw(&flat).len() < 1000);
+    assert_eq!(unlzw(&lzw(&flat), flat.len()).unwrap(), flat);
+}
+
+#[test]
+fn tiles() {
+    // 4 bit palette, in 16 × 16 tiles
+    let (w, h) = (20, 18);
+    let index = |x: u32, y: u32| ((x + y * 3) % 16) as u8;
+    let mut chunks = vec![];
+    for ty in 0..2 {
+        for tx in 0..2 {
+            let mut tile = vec![];
+            for y in ty * 16..ty * 16 + 16 {
+                for x in (tx * 16..tx * 16 + 16).step_by(2) {
+                    tile.push(index(x, y) << 4 | index(x + 1, y));
+                }
+            }
+            let mut packed = vec![];
+            tile.chunks_exact(8).for_each(|r| packbits(r, &mut packed));
+            chunks.push(packed);
+        }
+    }
+    let map = (0..48).map(|i| i * 1000).collect();
+    let tags = vec![
+        (256, 4, vec![w]),
+        (257, 4, vec![h]),
+        (258, 3, vec![4]),
+        (259, 3, vec![32773]),
+        (262, 3, vec![3]),
+        (320, 3, map),
+        (322, 3, vec![16]),
+        (323, 3, vec![16]),
+    ];
+    let tiff = file(&chunks, tags, (324, 325));
+    let p = decode_indexed(&tiff).unwrap();
+    let (indices, palette) = p.into_raw_parts();
+    assert_eq!((indices.width(), indices.height()), (w, h));
+    for y in 0..h {
+        for x in 0..w {
+            assert_eq!(indices.buffer()[(y * w + x) as usize], index(x, y));
+        }
+    }
+    assert_eq!(
+        palette[3][..3],
+        [3000u32, 19000, 35000].map(|x| (x >> 8) as u8)
+    );
+    let rgb = Image::<Box<[u8]>, 3>::from(decode(&tiff).unwrap());
+    assert_eq!(rgb.chunked().next(), Some(&[0, 62, 125]));
+    assert!(matches!(
+        decode_indexed(&encode(
+            Image::<_, 1>::build(1, 1).buf(&[0][..]),
+            Compression::None
+        )),
+        Err(Error::UnsupportedColor(_))
+    ));
+    // not too many pixels, but too many samples
+    let tags = vec![
+        (256, 4, vec![20_000]),
+        (257, 4, vec![20_000]),
+        (258, 3, vec![8; 16]),
+        (262, 3, vec![1]),
+        (277, 3, vec![16]),
+    ];
+    assert!(decode(&file(&[vec![]], tags, (273, 279))).is_err());
+    // a tiny image, with huge tiles
+    let tags = vec![
+        (256, 4, vec![1]),
+        (257, 4, vec![1]),
+        (258, 3, vec![16; 16]),
+        (262, 3, vec![1]),
+        (277, 3, vec![16]),
+        (322, 4, vec![20_000]),
+        (323, 4, vec![20_000]),
+    ];
+    assert!(decode(&file(&[vec![0; 32]], tags, (324, 325))).is_err());
+    // a truncated strip
+    let tags = vec![
+        (256, 4, vec![4]),
+        (257, 4, vec![2]),
+        (258, 3, vec![8]),
+        (262, 3, vec![1]),
+    ];
+    assert!(decode(&file(&[vec![0; 7]], tags.clone(), (273, 279))).is_err());
+    assert!(decode(&file(&[vec![0; 8]], tags, (273, 279))).is_ok());
+}
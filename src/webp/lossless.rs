@@ -0,0 +1,939 @@
+//! the VP8L (lossless) bitstream. Pixels are `0xAARRGGBB`.
+use crate::Error;
+use std::collections::HashMap;
+
+macro_rules! fail {
+    ($x:literal) => {
+        return Err(Error::Decode(concat!("webp: ", $x).into()))
+    };
+}
+
+/// The `(x, y)` offsets of the first 120 distance codes. The distance is `x + y * width`.
+#[rustfmt::skip]
+const PLANE: [(i8, i8); 120] = [
+    (0, 1), (1, 0), (1, 1), (-1, 1), (0, 2), (2, 0), (1, 2), (-1, 2),
+    (2, 1), (-2, 1), (2, 2), (-2, 2), (0, 3), (3, 0), (1, 3), (-1, 3),
+    (3, 1), (-3, 1), (2, 3), (-2, 3), (3, 2), (-3, 2), (0, 4), (4, 0),
+    (1, 4), (-1, 4), (4, 1), (-4, 1), (3, 3), (-3, 3), (2, 4), (-2, 4),
+    (4, 2), (-4, 2), (0, 5), (3, 4), (-3, 4), (4, 3), (-4, 3), (5, 0),
+    (1, 5), (-1, 5), (5, 1), (-5, 1), (2, 5), (-2, 5), (5, 2), (-5, 2),
+    (4, 4), (-4, 4), (3, 5), (-3, 5), (5, 3), (-5, 3), (0, 6), (6, 0),
+    (1, 6), (-1, 6), (6, 1), (-6, 1), (2, 6), (-2, 6), (6, 2), (-6, 2),
+    (4, 5), (-4, 5), (5, 4), (-5, 4), (3, 6), (-3, 6), (6, 3), (-6, 3),
+    (0, 7), (7, 0), (1, 7), (-1, 7), (5, 5), (-5, 5), (7, 1), (-7, 1),
+    (4, 6), (-4, 6), (6, 4), (-6, 4), (2, 7), (-2, 7), (7, 2), (-7, 2),
+    (3, 7), (-3, 7), (7, 3), (-7, 3), (5, 6), (-5, 6), (6, 5), (-6, 5),
+    (8, 0), (4, 7), (-4, 7), (7, 4), (-7, 4), (8, 1), (8, 2), (6, 6),
+    (-6, 6), (8, 3), (5, 7), (-5, 7), (7, 5), (-7, 5), (8, 4), (6, 7),
+    (-6, 7), (7, 6), (-7, 6), (8, 5), (7, 7), (-7, 7), (8, 6), (8, 7),
+];
+
+/// The order code length code lengths are stored in.
+const ORDER: [usize; 19] = [
+    17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+/// Literals, lengths, and (without a color cache) cache indices.
+const GREEN: usize = 256 + 24;
+const DISTANCE: usize = 40;
+const MAX_LENGTH: usize = 4096;
+const WINDOW: usize = (1 << 20) - 120;
+
+/// A lsb first bit reader. Reads zeroes past the end of the data, see [`Bits::overrun`].
+struct Bits<'a> {
+    data: &'a [u8],
+    at: usize,
+    acc: u64,
+    n: u32,
+    /// bits consumed
+    read: usize,
+}
+
+impl<'a> Bits<'a> {
+    const fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            at: 0,
+            acc: 0,
+            n: 0,
+            read: 0,
+        }
+    }
+
+    fn peek(&mut self, bits: u32) -> u32 {
+        while self.n <= 56 {
+            let x = self.data.get(self.at).copied().unwrap_or(0);
+            self.acc |= (x as u64) << self.n;
+            self.n += 8;
+            self.at += 1;
+        }
+        (self.acc & ((1 << bits) - 1)) as u32
+    }
+
+    const fn skip(&mut self, bits: u32) {
+        self.acc >>= bits;
+        self.n -= bits;
+        self.read += bits as usize;
+    }
+
+    fn read(&mut self, bits: u32) -> u32 {
+        let x = self.peek(bits);
+        self.skip(bits);
+        x
+    }
+
+    /// Whether more bits were read than there are.
+    const fn overrun(&self) -> bool {
+        self.read > self.data.len() * 8
+    }
+}
+
+/// A canonical prefix code.
+struct Code {
+    /// the only symbol, of a code that takes no bits
+    single: Option<u16>,
+    /// the (symbol, length) of codes of at most 8 bits, indexed by the next 8 bits
+    fast: Box<[(u16, u8)]>,
+    /// codes of each length
+    counts: [u16; 16],
+    /// symbols, ordered by their code
+    symbols: Vec<u16>,
+}
+
+impl Code {
+    fn new(lengths: &[u8]) -> Result<Self, Error> {
+        let mut counts = [0u16; 16];
+        for &l in lengths {
+            counts[l as usize] += 1;
+        }
+        counts[0] = 0;
+        let used = lengths.iter().filter(|&&l| l != 0).count();
+        if used == 0 {
+            fail!("empty prefix code")
+        }
+        if used == 1 {
+            return Ok(Self {
+                single: lengths.iter().position(|&l| l != 0).map(|x| x as u16),
+                fast: Box::new([]),
+                counts,
+                symbols: vec![],
+            });
+        }
+        let mut left = 1i32;
+        for &c in &counts[1..] {
+            left = (left << 1) - c as i32;
+            if left < 0 {
+                fail!("oversubscribed prefix code")
+            }
+        }
+        let mut symbols = (0..lengths.len() as u16)
+            .filter(|&s| lengths[s as usize] != 0)
+            .collect::<Vec<_>>();
+        symbols.sort_by_key(|&s| lengths[s as usize]);
+        let mut fast = vec![(0, 0); 256].into_boxed_slice();
+        let mut code = 0u32;
+        let mut last = 0;
+        for &s in &symbols {
+            let l = lengths[s as usize];
+            code <<= l - last;
+            last = l;
+            if l <= 8 {
+                let rev = code.reverse_bits() >> (32 - l);
+                for hi in 0..1 << (8 - l) {
+                    fast[(rev | hi << l) as usize] = (s, l);
+                }
+            }
+            code += 1;
+        }
+        Ok(Self {
+            single: None,
+            fast,
+            counts,
+            symbols,
+        })
+    }
+
+    fn read(&self, b: &mut Bits) -> Result<u16, Error> {
+        if let Some(s) = self.single {
+            return Ok(s);
+        }
+        let bits = b.peek(15);
+        let (s, l) = self.fast[bits as usize & 0xff];
+        if l != 0 {
+            b.skip(l as u32);
+            return Ok(s);
+        }
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for len in 1..16 {
+            code |= (bits >> (len - 1)) as i32 & 1;
+            let count = self.counts[len as usize] as i32;
+            if code - first < count {
+                b.skip(len);
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        fail!("bad prefix code")
+    }
+}
+
+fn read_code(b: &mut Bits, size: usize) -> Result<Code, Error> {
+    let mut lengths = vec![0; size];
+    if b.read(1) == 1 {
+        // one or two symbols
+        let two = b.read(1) == 1;
+        let first = match b.read(1) {
+            0 => b.read(1),
+            _ => b.read(8),
+        } as usize;
+        let second = if two { b.read(8) as usize } else { first };
+        if first >= size || second >= size {
+            fail!("bad prefix code")
+        }
+        lengths[first] = 1;
+        lengths[second] = 1;
+        return Code::new(&lengths);
+    }
+    let mut cl = [0; 19];
+    for &i in &ORDER[..b.read(4) as usize + 4] {
+        cl[i] = b.read(3) as u8;
+    }
+    let cl = Code::new(&cl)?;
+    let mut max = match b.read(1) {
+        0 => size,
+        _ => {
+            let bits = 2 + 2 * b.read(3);
+            2 + b.read(bits) as usize
+        }
+    };
+    if max > size {
+        fail!("bad prefix code")
+    }
+    let (mut i, mut prev) = (0, 8);
+    while i < size && max > 0 {
+        max -= 1;
+        match cl.read(b)? {
+            l @ 0..=15 => {
+                lengths[i] = l as u8;
+                i += 1;
+                if l != 0 {
+                    prev = l as u8;
+                }
+            }
+            s => {
+                let (extra, offset) = [(2, 3), (3, 3), (7, 11)][s as usize - 16];
+                let n = b.read(extra) as usize + offset;
+                let Some(to) = lengths.get_mut(i..i + n) else {
+                    fail!("bad prefix code")
+                };
+                to.fill(if s == 16 { prev } else { 0 });
+                i += n;
+            }
+        }
+    }
+    Code::new(&lengths)
+}
+
+/// Read the extra bits of a length or distance prefix.
+fn prefix(b: &mut Bits, code: u16) -> usize {
+    if code < 4 {
+        return code as usize + 1;
+    }
+    let extra = (code as u32 - 2) >> 1;
+    let offset = (2 + (code as usize & 1)) << extra;
+    offset + b.read(extra) as usize + 1
+}
+
+/// The index of the block of pixel `i` in a image subsampled by `1 << bits`.
+const fn block(i: usize, w: usize, bits: u32) -> usize {
+    ((i / w) >> bits) * w.div_ceil(1 << bits) + ((i % w) >> bits)
+}
+
+const fn hash(px: u32, bits: u32) -> usize {
+    (0x1e35_a7bd_u32.wrapping_mul(px) >> (32 - bits)) as usize
+}
+
+/// Read a entropy coded image. Only the main image may have several groups of prefix codes.
+fn image(b: &mut Bits, w: usize, h: usize, main: bool) -> Result<Vec<u32>, Error> {
+    let cache_bits = match b.read(1) {
+        0 => 0,
+        _ => match b.read(4) {
+            x @ 1..=11 => x,
+            _ => fail!("bad color cache size"),
+        },
+    };
+    let (meta_bits, meta) = if main && b.read(1) == 1 {
+        let bits = b.read(3) + 2;
+        let meta = image(b, w.div_ceil(1 << bits), h.div_ceil(1 << bits), false)?;
+        (
+            bits,
+            meta.iter().map(|&x| (x >> 8 & 0xffff) as usize).collect(),
+        )
+    } else {
+        (0, vec![])
+    };
+    let cache_size = if cache_bits == 0 { 0 } else { 1 << cache_bits };
+    let mut groups = vec![];
+    for _ in 0..meta.iter().max().map_or(1, |&x| x + 1) {
+        groups.push([
+            read_code(b, GREEN + cache_size)?,
+            read_code(b, 256)?,
+            read_code(b, 256)?,
+            read_code(b, 256)?,
+            read_code(b, DISTANCE)?,
+        ]);
+        if b.overrun() {
+            fail!("unexpected end of data")
+        }
+    }
+    let mut out = vec![0u32; w * h];
+    let mut cache = vec![0; cache_size];
+    let (mut i, mut cached) = (0, 0);
+    while i < out.len() {
+        let g = match meta_bits {
+            0 => &groups[0],
+            _ => &groups[meta[block(i, w, meta_bits)]],
+        };
+        match g[0].read(b)? {
+            green @ 0..256 => {
+                let red = g[1].read(b)? as u32;
+                let blue = g[2].read(b)? as u32;
+                let alpha = g[3].read(b)? as u32;
+                out[i] = alpha << 24 | red << 16 | (green as u32) << 8 | blue;
+                i += 1;
+            }
+            length @ 256..280 => {
+                let length = prefix(b, length - 256);
+                let code = g[4].read(b)?;
+                let code = prefix(b, code);
+                let distance = match code {
+                    121.. => code - 120,
+                    _ => {
+                        let (x, y) = PLANE[code - 1];
+                        (x as isize + y as isize * w as isize).max(1) as usize
+                    }
+                };
+                if distance > i || i + length > out.len() {
+                    fail!("bad backward reference")
+                }
+                for j in i..i + length {
+                    out[j] = out[j - distance];
+                }
+                i += length;
+            }
+            index => {
+                out[i] = cache[index as usize - GREEN];
+                i += 1;
+            }
+        }
+        if cache_size != 0 {
+            for &px in &out[cached..i] {
+                cache[hash(px, cache_bits)] = px;
+            }
+            cached = i;
+        }
+        if b.overrun() {
+            fail!("unexpected end of data")
+        }
+    }
+    Ok(out)
+}
+
+/// Add (or subtract) pixels, per channel.
+const fn add(a: u32, b: u32) -> u32 {
+    (a & 0xff00ff00).wrapping_add(b & 0xff00ff00) & 0xff00ff00
+        | (a & 0x00ff00ff).wrapping_add(b & 0x00ff00ff) & 0x00ff00ff
+}
+
+const fn sub(a: u32, b: u32) -> u32 {
+    (a | 0x00ff00ff).wrapping_sub(b & 0xff00ff00) & 0xff00ff00
+        | (a | 0xff00ff00).wrapping_sub(b & 0x00ff00ff) & 0x00ff00ff
+}
+
+const fn average(a: u32, b: u32) -> u32 {
+    (((a ^ b) & 0xfefefefe) >> 1) + (a & b)
+}
+
+fn channels(px: u32) -> [i32; 4] {
+    px.to_be_bytes().map(|x| x as i32)
+}
+
+fn pixel(c: [i32; 4]) -> u32 {
+    u32::from_be_bytes(c.map(|x| x.clamp(0, 255) as u8))
+}
+
+/// The prediction of a predictor transform, from the left, top, top left, and top right pixels.
+fn predict(mode: u32, [l, t, tl, tr]: [u32; 4]) -> u32 {
+    match mode {
+        1 => l,
+        2 => t,
+        3 => tr,
+        4 => tl,
+        5 => average(average(l, tr), t),
+        6 => average(l, tl),
+        7 => average(l, t),
+        8 => average(tl, t),
+        9 => average(t, tr),
+        10 => average(average(l, tl), average(t, tr)),
+        11 => {
+            let (cl, ct, ctl) = (channels(l), channels(t), channels(tl));
+            let pl = (0..4).map(|i| (ct[i] - ctl[i]).abs()).sum::<i32>();
+            let pt = (0..4).map(|i| (cl[i] - ctl[i]).abs()).sum::<i32>();
+            if pl < pt { l } else { t }
+        }
+        12 => {
+            let (cl, ct, ctl) = (channels(l), channels(t), channels(tl));
+            pixel(std::array::from_fn(|i| cl[i] + ct[i] - ctl[i]))
+        }
+        13 => {
+            let (a, ctl) = (channels(average(l, t)), channels(tl));
+            pixel(std::array::from_fn(|i| a[i] + (a[i] - ctl[i]) / 2))
+        }
+        _ => 0xff000000,
+    }
+}
+
+/// The neighbours of pixel `i`, and whether it is on the first row or column.
+fn neighbours(px: &[u32], i: usize, w: usize) -> Result<[u32; 4], u32> {
+    match (i < w, i.is_multiple_of(w)) {
+        (true, true) => Err(0xff000000),
+        (true, false) => Err(px[i - 1]),
+        (false, true) => Err(px[i - w]),
+        // the top right of the last column is the first pixel of this row
+        _ => Ok([px[i - 1], px[i - w], px[i - w - 1], px[i - w + 1]]),
+    }
+}
+
+const fn delta(t: u32, c: u32) -> i32 {
+    (t as u8 as i8 as i32 * c as u8 as i8 as i32) >> 5
+}
+
+enum Transform {
+    Predictor(u32, Vec<u32>),
+    Color(u32, Vec<u32>),
+    SubtractGreen,
+    Indexing(Vec<u32>, u32),
+}
+
+/// log2 of the number of palette indices bundled into one pixel.
+const fn bundle(colors: usize) -> u32 {
+    match colors {
+        0..=2 => 3,
+        3..=4 => 2,
+        5..=16 => 1,
+        _ => 0,
+    }
+}
+
+/// Decode a VP8L image stream (without the header).
+fn stream(b: &mut Bits, w: usize, h: usize) -> Result<Vec<u32>, Error> {
+    let mut transforms = vec![];
+    let mut xs = w;
+    let mut seen = 0;
+    while b.read(1) == 1 {
+        let t = b.read(2);
+        if seen & 1 << t != 0 {
+            fail!("repeated transform")
+        }
+        seen |= 1 << t;
+        let t = match t {
+            0 | 1 => {
+                let bits = b.read(3) + 2;
+                let data = image(b, xs.div_ceil(1 << bits), h.div_ceil(1 << bits), false)?;
+                match t {
+                    0 => Transform::Predictor(bits, data),
+                    _ => Transform::Color(bits, data),
+                }
+            }
+            2 => Transform::SubtractGreen,
+            _ => {
+                let n = b.read(8) as usize + 1;
+                let mut table = image(b, n, 1, false)?;
+                for i in 1..n {
+                    table[i] = add(table[i], table[i - 1]);
+                }
+                let bits = bundle(n);
+                Transform::Indexing(table, bits)
+            }
+        };
+        transforms.push((xs, t));
+        if let Some((_, Transform::Indexing(_, bits))) = transforms.last() {
+            xs = xs.div_ceil(1 << bits);
+        }
+    }
+    let mut px = image(b, xs, h, true)?;
+    for (w, t) in transforms.into_iter().rev() {
+        match t {
+            Transform::Predictor(bits, modes) => {
+                for i in 0..px.len() {
+                    let p = match neighbours(&px, i, w) {
+                        Ok(n) => predict(modes[block(i, w, bits)] >> 8 & 0xf, n),
+                        Err(p) => p,
+                    };
+                    px[i] = add(px[i], p);
+                }
+            }
+            Transform::Color(bits, m) => {
+                for (i, p) in px.iter_mut().enumerate() {
+                    let m = m[block(i, w, bits)];
+                    let green = *p >> 8;
+                    let red = (*p >> 16) as i32 + delta(m, green);
+                    let blue = *p as i32 + delta(m >> 8, green) + delta(m >> 16, red as u32);
+                    *p = *p & 0xff00ff00 | (red as u32 & 0xff) << 16 | blue as u32 & 0xff;
+                }
+            }
+            Transform::SubtractGreen => {
+                for p in &mut px {
+                    let green = *p >> 8 & 0xff;
+                    *p = add(*p, green << 16 | green);
+                }
+            }
+            Transform::Indexing(table, bits) => {
+                let (per, size) = (1 << bits, 8 >> bits);
+                let packed_w = w.div_ceil(per);
+                px = (0..w * h)
+                    .map(|i| {
+                        let (x, y) = (i % w, i / w);
+                        let p = px[y * packed_w + x / per] >> 8;
+                        let index = p >> (x % per * size) & ((1 << size) - 1);
+                        table.get(index as usize).copied().unwrap_or(0)
+                    })
+                    .collect();
+            }
+        }
+    }
+    Ok(px)
+}
+
+/// Decode a VP8L bitstream, returning the size and the pixels.
+pub(super) fn decode(data: &[u8]) -> Result<((u32, u32), Vec<u32>), Error> {
+    let [0x2f, rest @ ..] = data else {
+        fail!("bad lossless signature")
+    };
+    let mut b = Bits::new(rest);
+    let w = b.read(14) + 1;
+    let h = b.read(14) + 1;
+    // whether alpha is used, which is only a hint
+    b.read(1);
+    if b.read(3) != 0 {
+        fail!("unknown lossless version")
+    }
+    let px = stream(&mut b, w as usize, h as usize)?;
+    Ok(((w, h), px))
+}
+
+/// A lsb first bit writer.
+#[derive(Default)]
+struct Writer {
+    out: Vec<u8>,
+    acc: u64,
+    n: u32,
+}
+
+impl Writer {
+    fn write(&mut self, x: u32, bits: u32) {
+        self.acc |= (x as u64) << self.n;
+        self.n += bits;
+        while self.n >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.n -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.n > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+/// Huffman code lengths for these counts, of at most `limit` bits.
+fn code_lengths(counts: &[u32], limit: u8) -> Vec<u8> {
+    let mut counts = counts.to_vec();
+    loop {
+        let mut lengths = vec![0; counts.len()];
+        let used = (0..counts.len()).filter(|&i| counts[i] != 0);
+        // (weight, node); leaves are the symbols, then internal nodes
+        let mut heap = used
+            .clone()
+            .map(|i| std::cmp::Reverse((counts[i] as u64, i)))
+            .collect::<std::collections::BinaryHeap<_>>();
+        match heap.len() {
+            0 => return lengths,
+            1 => {
+                lengths[used.clone().next().unwrap()] = 1;
+                return lengths;
+            }
+            _ => {}
+        }
+        let mut parent = vec![usize::MAX; counts.len()];
+        while heap.len() > 1 {
+            let std::cmp::Reverse((a, x)) = heap.pop().unwrap();
+            let std::cmp::Reverse((b, y)) = heap.pop().unwrap();
+            let node = parent.len();
+            parent.push(usize::MAX);
+            parent[x] = node;
+            parent[y] = node;
+            heap.push(std::cmp::Reverse((a + b, node)));
+        }
+        for i in used {
+            let (mut depth, mut at) = (0, i);
+            while parent[at] != usize::MAX {
+                at = parent[at];
+                depth += 1;
+            }
+            lengths[i] = depth;
+        }
+        if lengths.iter().all(|&l| l <= limit) {
+            return lengths;
+        }
+        // flatten the distribution until the code fits
+        for c in &mut counts {
+            if *c != 0 {
+                *c = (*c >> 1).max(1);
+            }
+        }
+    }
+}
+
+/// The (bit reversed) canonical codes of these lengths. A code of one symbol takes no bits.
+fn codes(lengths: &[u8]) -> Vec<(u32, u32)> {
+    if lengths.iter().filter(|&&l| l != 0).count() <= 1 {
+        return vec![(0, 0); lengths.len()];
+    }
+    let mut symbols = (0..lengths.len())
+        .filter(|&s| lengths[s] != 0)
+        .collect::<Vec<_>>();
+    symbols.sort_by_key(|&s| lengths[s]);
+    let mut codes = vec![(0, 0); lengths.len()];
+    let (mut code, mut last) = (0u32, 0);
+    for s in symbols {
+        let l = lengths[s] as u32;
+        code <<= l - last;
+        last = l;
+        codes[s] = (code.reverse_bits() >> (32 - l), l);
+        code += 1;
+    }
+    codes
+}
+
+/// Write the code lengths of a prefix code.
+fn write_code(w: &mut Writer, lengths: &[u8]) {
+    let used = (0..lengths.len())
+        .filter(|&s| lengths[s] != 0)
+        .collect::<Vec<_>>();
+    if used.len() <= 2 && used.iter().all(|&s| s < 256) {
+        w.write(1, 1);
+        w.write(used.len().saturating_sub(1) as u32, 1);
+        match used.first().copied().unwrap_or(0) {
+            s @ 0..2 => {
+                w.write(0, 1);
+                w.write(s as u32, 1);
+            }
+            s => {
+                w.write(1, 1);
+                w.write(s as u32, 8);
+            }
+        }
+        if let [_, s] = used[..] {
+            w.write(s as u32, 8);
+        }
+        return;
+    }
+    w.write(0, 1);
+    // (symbol, extra bits, bit count)
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < lengths.len() {
+        let l = lengths[i];
+        let run = lengths[i..].iter().take_while(|&&x| x == l).count();
+        match (l, run) {
+            (0, 11..) => {
+                let n = run.min(138);
+                tokens.push((18, n as u32 - 11, 7));
+                i += n;
+            }
+            (0, 3..) => {
+                tokens.push((17, run as u32 - 3, 3));
+                i += run;
+            }
+            (_, 4..) => {
+                tokens.push((l as usize, 0, 0));
+                let n = (run - 1).min(6);
+                tokens.push((16, n as u32 - 3, 2));
+                i += n + 1;
+            }
+            _ => {
+                tokens.push((l as usize, 0, 0));
+                i += 1;
+            }
+        }
+    }
+    let mut counts = [0; 19];
+    for &(s, ..) in &tokens {
+        counts[s] += 1;
+    }
+    let cl = code_lengths(&counts, 7);
+    let n = ORDER.iter().rposition(|&i| cl[i] != 0).map_or(0, |x| x + 1);
+    let n = n.max(4);
+    w.write(n as u32 - 4, 4);
+    for &i in &ORDER[..n] {
+        w.write(cl[i] as u32, 3);
+    }
+    // every symbol is written
+    w.write(0, 1);
+    let cl = codes(&cl);
+    for (s, extra, bits) in tokens {
+        let (code, len) = cl[s];
+        w.write(code, len);
+        w.write(extra, bits);
+    }
+}
+
+/// The prefix code, and the extra bits (and their count), of a length or distance.
+const fn to_prefix(x: usize) -> (usize, u32, u32) {
+    let x = x as u32 - 1;
+    if x < 4 {
+        return (x as usize, 0, 0);
+    }
+    let high = 31 - x.leading_zeros();
+    let second = x >> (high - 1) & 1;
+    let extra = high - 1;
+    ((2 * high + second) as usize, x & ((1 << extra) - 1), extra)
+}
+
+enum Token {
+    Literal(u32),
+    /// length, distance code
+    Copy(usize, usize),
+}
+
+/// Find backward references.
+fn tokens(px: &[u32], w: usize) -> Vec<Token> {
+    // the smallest code of each distance with a plane code
+    let mut plane = HashMap::new();
+    for (i, &(x, y)) in PLANE.iter().enumerate().rev() {
+        let d = (x as isize + y as isize * w as isize).max(1) as usize;
+        plane.insert(d, i + 1);
+    }
+    let code = |d: usize| plane.get(&d).copied().unwrap_or(d + 120);
+    const BITS: u32 = 16;
+    let key = |i: usize| {
+        let k = (px[i] as u64) << 32 | px[i + 1] as u64;
+        (k.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> (64 - BITS)) as usize
+    };
+    let mut head = vec![usize::MAX; 1 << BITS];
+    let mut chain = vec![usize::MAX; px.len()];
+    let insert = |i: usize, head: &mut [usize], chain: &mut [usize]| {
+        if i + 1 < px.len() {
+            let k = key(i);
+            chain[i] = head[k];
+            head[k] = i;
+        }
+    };
+    let matching = |i: usize, j: usize| {
+        px[i..]
+            .iter()
+            .zip(&px[j..])
+            .take(MAX_LENGTH)
+            .take_while(|(a, b)| a == b)
+            .count()
+    };
+    let mut out = vec![];
+    let mut i = 0;
+    while i < px.len() {
+        let (mut best, mut distance) = (0, 0);
+        for d in [1, w] {
+            if d <= i {
+                let l = matching(i, i - d);
+                if l > best {
+                    (best, distance) = (l, d);
+                }
+            }
+        }
+        if i + 1 < px.len() {
+            let mut j = head[key(i)];
+            for _ in 0..32 {
+                if j == usize::MAX || i - j > WINDOW {
+                    break;
+                }
+                let l = matching(i, j);
+                if l > best {
+                    (best, distance) = (l, i - j);
+                }
+                j = chain[j];
+            }
+        }
+        if best >= 3 {
+            out.push(Token::Copy(best, code(distance)));
+            for k in i..i + best {
+                insert(k, &mut head, &mut chain);
+            }
+            i += best;
+        } else {
+            out.push(Token::Literal(px[i]));
+            insert(i, &mut head, &mut chain);
+            i += 1;
+        }
+    }
+    out
+}
+
+/// Write a entropy coded image, with one group of prefix codes and no color cache.
+fn write_image(w: &mut Writer, px: &[u32], width: usize, main: bool) {
+    // no color cache
+    w.write(0, 1);
+    if main {
+        // one group of codes
+        w.write(0, 1);
+    }
+    let tokens = tokens(px, width);
+    let mut counts = [
+        vec![0; GREEN],
+        vec![0; 256],
+        vec![0; 256],
+        vec![0; 256],
+        vec![0; DISTANCE],
+    ];
+    for t in &tokens {
+        match *t {
+            Token::Literal(p) => {
+                let [a, r, g, b] = p.to_be_bytes();
+                counts[0][g as usize] += 1;
+                counts[1][r as usize] += 1;
+                counts[2][b as usize] += 1;
+                counts[3][a as usize] += 1;
+            }
+            Token::Copy(l, d) => {
+                counts[0][256 + to_prefix(l).0] += 1;
+                counts[4][to_prefix(d).0] += 1;
+            }
+        }
+    }
+    let codes = counts.map(|c| {
+        let l = code_lengths(&c, 15);
+        write_code(w, &l);
+        codes(&l)
+    });
+    for t in tokens {
+        match t {
+            Token::Literal(p) => {
+                let [a, r, g, b] = p.to_be_bytes();
+                for (c, x) in codes.iter().zip([g, r, b, a]) {
+                    let (code, len) = c[x as usize];
+                    w.write(code, len);
+                }
+            }
+            Token::Copy(l, d) => {
+                let (p, extra, bits) = to_prefix(l);
+                let (code, len) = codes[0][256 + p];
+                w.write(code, len);
+                w.write(extra, bits);
+                let (p, extra, bits) = to_prefix(d);
+                let (code, len) = codes[4][p];
+                w.write(code, len);
+                w.write(extra, bits);
+            }
+        }
+    }
+}
+
+/// Encode pixels as a VP8L bitstream.
+pub(super) fn encode(px: &[u32], (w, h): (u32, u32)) -> Vec<u8> {
+    let mut out = Writer::default();
+    out.write(0x2f, 8);
+    out.write(w - 1, 14);
+    out.write(h - 1, 14);
+    out.write(px.iter().any(|&p| p >> 24 != 0xff) as u32, 1);
+    out.write(0, 3);
+    let (w, h) = (w as usize, h as usize);
+
+    let mut palette = px.to_vec();
+    palette.sort_unstable();
+    palette.dedup();
+    if palette.len() <= 256 {
+        // color indexing, with small indices packed into one pixel
+        out.write(1, 1);
+        out.write(3, 2);
+        out.write(palette.len() as u32 - 1, 8);
+        let deltas = (0..palette.len())
+            .map(|i| match i {
+                0 => palette[0],
+                _ => sub(palette[i], palette[i - 1]),
+            })
+            .collect::<Vec<_>>();
+        write_image(&mut out, &deltas, palette.len(), false);
+        let bits = bundle(palette.len());
+        let (per, size) = (1 << bits, 8 >> bits);
+        let packed_w = w.div_ceil(per);
+        let mut packed = vec![0xff000000; packed_w * h];
+        for (i, p) in px.iter().enumerate() {
+            let index = palette.binary_search(p).unwrap() as u32;
+            let (x, y) = (i % w, i / w);
+            packed[y * packed_w + x / per] |= index << (8 + x % per * size);
+        }
+        out.write(0, 1);
+        write_image(&mut out, &packed, packed_w, true);
+        return out.finish();
+    }
+
+    // subtract green
+    out.write(1, 1);
+    out.write(2, 2);
+    let px = px
+        .iter()
+        .map(|&p| {
+            let green = p >> 8 & 0xff;
+            sub(p, green << 16 | green)
+        })
+        .collect::<Vec<_>>();
+
+    // a predictor for every 16 × 16 block, with the smallest residuals
+    const BITS: u32 = 4;
+    let bw = w.div_ceil(1 << BITS);
+    let mut modes = vec![0xff000000 | 11 << 8; bw * h.div_ceil(1 << BITS)];
+    for (b, mode) in modes.iter_mut().enumerate() {
+        let (bx, by) = ((b % bw) << BITS, (b / bw) << BITS);
+        let mut best = (u64::MAX, 0);
+        for m in 0..14 {
+            let mut cost = 0;
+            for y in by..(by + (1 << BITS)).min(h) {
+                for x in bx..(bx + (1 << BITS)).min(w) {
+                    let i = y * w + x;
+                    if let Ok(n) = neighbours(&px, i, w) {
+                        let r = sub(px[i], predict(m, n));
+                        cost += r
+                            .to_be_bytes()
+                            .iter()
+                            .map(|&c| (c as i8).unsigned_abs() as u64)
+                            .sum::<u64>();
+                    }
+                }
+            }
+            if cost < best.0 {
+                best = (cost, m);
+            }
+        }
+        *mode = 0xff000000 | best.1 << 8;
+    }
+    let residuals = (0..px.len())
+        .map(|i| match neighbours(&px, i, w) {
+            Ok(n) => {
+                let m = modes[block(i, w, BITS)] >> 8 & 0xf;
+                sub(px[i], predict(m, n))
+            }
+            Err(p) => sub(px[i], p),
+        })
+        .collect::<Vec<_>>();
+    out.write(1, 1);
+    out.write(0, 2);
+    out.write(BITS - 2, 3);
+    write_image(&mut out, &modes, bw, false);
+    out.write(0, 1);
+    write_image(&mut out, &residuals, w, true);
+    out.finish()
+}
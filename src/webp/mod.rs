@@ -0,0 +1,261 @@
+//! [WebP](https://developers.google.com/speed/webp) decoding and encoding.
+//!
+//! Reads and writes lossless (VP8L) webps. Extended webps are read, and animations become their first frame.
+//! Lossy (VP8) webps are not supported, and produce a [`Error::Decode`].
+use crate::{DynImage, Error, Image, pixels::convert::PFrom};
+mod lossless;
+
+/// Read a webp image.
+pub trait ReadWebp: Sized {
+    /// Read a webp into an image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error>;
+}
+
+/// Write a (lossless) webp image.
+pub trait WriteWebp {
+    /// Write this image as a lossless webp.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error>;
+}
+
+macro_rules! fail {
+    ($x:literal) => {
+        return Err(Error::Decode(concat!("webp: ", $x).into()))
+    };
+}
+
+/// The largest width or height of a webp.
+const MAX: u32 = 1 << 14;
+/// The most pixels of a decoded canvas.
+const MAX_PIXELS: u64 = 400_000_000;
+
+/// The chunks of a RIFF container (or the subchunks of a `ANMF` chunk).
+fn chunks(mut data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        let (&id, rest) = data.split_first_chunk::<4>()?;
+        let (&size, rest) = rest.split_first_chunk::<4>()?;
+        let size = u32::from_le_bytes(size) as usize;
+        let chunk = rest.get(..size).unwrap_or(rest);
+        data = rest.get(size + (size & 1)..).unwrap_or(&[]);
+        Some((id, chunk))
+    })
+}
+
+fn u24(x: &[u8]) -> u32 {
+    u32::from_le_bytes([x[0], x[1], x[2], 0])
+}
+
+/// Decode a frame: a lossless image, among some chunks.
+fn frame<'a>(
+    mut chunks: impl Iterator<Item = ([u8; 4], &'a [u8])>,
+) -> Result<((u32, u32), Vec<u32>), Error> {
+    match chunks.find(|(id, _)| matches!(id, b"VP8L" | b"VP8 ")) {
+        Some((id, data)) if &id == b"VP8L" => lossless::decode(data),
+        Some(_) => fail!("lossy (VP8) images are not supported"),
+        None => fail!("no image data"),
+    }
+}
+
+/// Decode a webp image. Produces a [`DynImage::Rgba`] if any pixel is transparent, else a [`DynImage::Rgb`].
+pub fn decode(data: &[u8]) -> Result<DynImage<Box<[u8]>>, Error> {
+    let [
+        b'R',
+        b'I',
+        b'F',
+        b'F',
+        _,
+        _,
+        _,
+        _,
+        b'W',
+        b'E',
+        b'B',
+        b'P',
+        data @ ..,
+    ] = data
+    else {
+        fail!("bad magic")
+    };
+    let mut chunks = chunks(data).peekable();
+    let ((w, h), px) = match chunks.peek() {
+        Some(&(id, x)) if &id == b"VP8X" => {
+            let Some(x) = x.get(..10) else {
+                fail!("bad extended header")
+            };
+            let (w, h) = (u24(&x[4..]) + 1, u24(&x[7..]) + 1);
+            if w as u64 * h as u64 > MAX_PIXELS {
+                fail!("image too large")
+            }
+            if x[0] & 2 == 0 {
+                frame(chunks)?
+            } else {
+                // the first frame, over a transparent canvas
+                let Some((_, f)) = chunks.find(|(id, _)| id == b"ANMF") else {
+                    fail!("no frames")
+                };
+                let Some(header) = f.get(..16) else {
+                    fail!("bad frame header")
+                };
+                let (x, y) = (u24(header) * 2, u24(&header[3..]) * 2);
+                let ((fw, fh), px) = frame(self::chunks(&f[16..]))?;
+                if x + fw > w || y + fh > h {
+                    fail!("frame out of bounds")
+                }
+                let mut canvas = vec![0; w as usize * h as usize];
+                for (row, from) in px.chunks_exact(fw as usize).enumerate() {
+                    let at = (y as usize + row) * w as usize + x as usize;
+                    canvas[at..at + fw as usize].copy_from_slice(from);
+                }
+                ((w, h), canvas)
+            }
+        }
+        _ => frame(chunks)?,
+    };
+    let buf = |n| {
+        px.iter()
+            .flat_map(|&p| {
+                let [a, r, g, b] = p.to_be_bytes();
+                [r, g, b, a].into_iter().take(n)
+            })
+            .collect()
+    };
+    Ok(if px.iter().all(|&p| p >> 24 == 0xff) {
+        DynImage::Rgb(Image::build(w, h).buf(buf(3)))
+    } else {
+        DynImage::Rgba(Image::build(w, h).buf(buf(4)))
+    })
+}
+
+/// Encode an image as a lossless webp.
+pub fn encode<const N: usize>(image: Image<&[u8], N>) -> Result<Vec<u8>, Error>
+where
+    [u8; 4]: PFrom<N>,
+{
+    if image.width() > MAX || image.height() > MAX {
+        return Err(Error::Encode("webp: image too large".into()));
+    }
+    let px = image
+        .flatten()
+        .iter()
+        .map(|&p| {
+            let [r, g, b, a] = <[u8; 4] as PFrom<N>>::pfrom(p);
+            u32::from_be_bytes([a, r, g, b])
+        })
+        .collect::<Vec<_>>();
+    let data = lossless::encode(&px, (image.width(), image.height()));
+    let padded = data.len() + (data.len() & 1);
+    let mut out = Vec::with_capacity(20 + padded);
+    out.extend(b"RIFF");
+    out.extend((12 + padded as u32).to_le_bytes());
+    out.extend(b"WEBPVP8L");
+    out.extend((data.len() as u32).to_le_bytes());
+    out.extend(data);
+    out.resize(20 + padded, 0);
+    Ok(out)
+}
+
+impl ReadWebp for DynImage<Box<[u8]>> {
+    /// Read a webp image.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        let mut data = vec![];
+        f.read_to_end(&mut data)?;
+        decode(&data)
+    }
+}
+
+impl<const N: usize> ReadWebp for Image<Box<[u8]>, N>
+where
+    Self: From<DynImage<Box<[u8]>>>,
+{
+    /// Read a webp image, converting it to this many channels.
+    fn read(f: &mut impl std::io::Read) -> Result<Self, Error> {
+        DynImage::read(f).map(Into::into)
+    }
+}
+
+macro_rules! writer {
+    ($n:literal) => {
+        impl<T: AsRef<[u8]>> WriteWebp for Image<T, $n> {
+            /// Write this image as a lossless webp.
+            fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+                f.write_all(&encode(self.as_ref())?)?;
+                Ok(())
+            }
+        }
+    };
+}
+writer!(3);
+writer!(4);
+
+impl<T: AsRef<[u8]> + crate::Buffer> WriteWebp for DynImage<T> {
+    /// Write this image as a lossless webp. 16 bit images are reduced to 8 bit.
+    fn write(&self, f: &mut impl std::io::Write) -> Result<(), Error> {
+        match self {
+            Self::Rgb(x) => x.write(f),
+            Self::Rgba(x) => x.write(f),
+            Self::Y(_) | Self::Rgb16(_) => self.rgb().write(f),
+            Self::Ya(_) | Self::Y16(_) | Self::Ya16(_) | Self::Rgba16(_) => self.rgba().write(f),
+        }
+    }
+}
+
+#[cfg(feature = "save")]
+#[test]
+fn roundtrip() {
+    let cat = Image::<_, 3>::open("tdata/small_cat.png");
+    let mut out = vec![];
+    cat.write(&mut out).unwrap();
+    assert_eq!(&out[8..16], b"WEBPVP8L");
+    let d = decode(&out).unwrap();
+    assert!(matches!(d, DynImage::Rgb(_)));
+    assert_eq!(d.rgb().bytes(), cat.bytes());
+
+    let mut cat = Image::<Box<[u8]>, 4>::from(cat.as_ref());
+    cat.chunked_mut().for_each(|[.., a]| *a /= 2);
+    let mut out = vec![];
+    cat.write(&mut out).unwrap();
+    assert_eq!(
+        Image::<Box<[u8]>, 4>::read(&mut &out[..]).unwrap().bytes(),
+        cat.bytes()
+    );
+}
+
+#[test]
+fn palette() {
+    // few colors (color indexing), and noise (no backward references)
+    let mut seed = 7u32;
+    let mut noise = || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed
+    };
+    for colors in [1, 2, 3, 5, 17, 0] {
+        let (w, h) = (37, 19);
+        let buf = (0..w * h)
+            .flat_map(|_| {
+                let x = noise();
+                let x = if colors == 0 { x } else { x % colors * 40 };
+                [
+                    x as u8,
+                    (x >> 8) as u8,
+                    (x >> 16) as u8,
+                    (x >> 24) as u8 | 1,
+                ]
+            })
+            .collect::<Vec<_>>();
+        let img = Image::<_, 4>::build(w, h).buf(&buf[..]);
+        let d = decode(&encode(img).unwrap()).unwrap();
+        assert_eq!(d.rgba().bytes(), &buf[..], "{colors} colors");
+    }
+    assert!(decode(b"RIFF\x0c\0\0\0WEBPVP8 \0\0\0\0").is_err());
+    // a tiny frame, on a huge animated canvas
+    let frame = encode(Image::<_, 4>::build(1, 1).buf(&[0u8; 4][..])).unwrap();
+    let mut anmf = [0; 16].to_vec();
+    anmf.extend(&frame[12..]);
+    let mut huge = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\x02\0\0\0".to_vec();
+    huge.extend([0xff; 6]);
+    huge.extend(b"ANMF");
+    huge.extend((anmf.len() as u32).to_le_bytes());
+    huge.extend(anmf);
+    assert!(decode(&huge).is_err());
+}